@@ -18,7 +18,16 @@
 //! To then use the binary, you can run the following command:
 //!
 //! ```bash
-//! rhasm <input_file> [-o | --output <output_file>] [-d | --disassemble [--with_symbols <symbol_file>]]
+//! rhasm <input_file> [-o | --output <output_file>] [--force | --no-overwrite] [-v | --verbose | -q | --quiet] [-d | --disassemble [-f | --follow] [--with_symbols <symbol_file> | [--reconstruct-labels] [--name-variables]] [--word-comments above|inline] [--annotations <file>]]
+//! rhasm -e | --eval <program> [-d | --disassemble]
+//! rhasm explain <instruction>
+//! rhasm --explain <code>
+//! rhasm rename <old_name> <new_name> <file>
+//! rhasm label <address> <name> <file> [--jumps-only]
+//! rhasm gen-test <file>
+//! rhasm parse <file> [--format json] [--lenient-syntax]
+//! rhasm lint <file> [--fail-on note|warning|error] [--lenient-syntax] [--message-format human|gcc|json|sarif]
+//! rhasm pack <output> <inputs>...
 //! ```
 //! ## As A Library
 //!
@@ -41,6 +50,23 @@
 //! use rhasm::*;
 //! ```
 //!
+//! Enable the `serde` feature to serialize and deserialize an [`Instruction`], its [`InstructionSpans`] and a [`SymbolTable`],
+//! e.g. to cache parse results or send them to a web frontend as JSON:
+//!
+//! ```toml
+//! [dependencies]
+//! rhasm = { version = "0.1.2", features = ["serde"] }
+//! ```
+//!
+//! The `std` feature is enabled by default. Without it the crate is `no_std` and only needs `alloc`, for Hack emulators
+//! running on embedded targets or WASM without std. The [`encoder`], [`parser`] and [`fields`] modules, [`decode_instruction`]
+//! and [`SymbolTable`] are still available, everything reading or writing through `std::io`, e.g. the [`Assembler`], is not:
+//!
+//! ```toml
+//! [dependencies]
+//! rhasm = { version = "0.1.2", default-features = false }
+//! ```
+//!
 //! As a library rhasm exposes both an [`Assembler`] and [`Disassembler`] struct that are able to read over the lines of some source file.
 //! You can then use them to either write to a file or to return the decoded instructions as a string, both line by line or all at once.
 //!
@@ -51,6 +77,10 @@
 //! When using the [`Assembler`] struct you can either receive the encoded instruction as a string 
 //! by using the [`Assembler::get_next_encoded_instruction`] method, or write the encoded instructions to a file using the [`Assembler::advance_to_end`] or [`Assembler::advance_once`] methods.
 //! 
+//! Invalid input never panics, instead these methods return a [`RhasmError`] describing what went wrong so it can be reported or recovered from.
+//! 
+//! If all you need are the machine words of a program, [`assemble`] and [`assemble_str`] return them without building an [`Assembler`] yourself.
+//! 
 //! ### Examples
 //! 
 //! Consider the sample input and expected output below
@@ -89,9 +119,9 @@
 //! # ";
 //! let mut in_file = Cursor::new(sample_input);
 //! let mut out_file = Cursor::new(Vec::new());
-//! if let Ok(mut assembler) = Assembler::build(&mut in_file, &mut out_file, None) {
-//!     assembler.advance_once();
-//!     assembler.advance_to_end();
+//! if let Ok(mut assembler) = Assembler::build(AssemblerConfig::new(&mut in_file, &mut out_file)) {
+//!     assembler.advance_once().unwrap();
+//!     assembler.advance_to_end().unwrap();
 //! }
 //! let mut actual_output = String::new();
 //! out_file.set_position(0);
@@ -125,8 +155,8 @@
 //! 
 //! // The if let statement has the additional benefit of dropping the assembler 
 //! // Thus freeing the mutable borrow on our input and output
-//! if let Ok(mut assembler) = Assembler::build(&mut in_file, &mut out_file, None) {
-//!     while let Some(encoded_instruction) = assembler.get_next_encoded_instruction() {
+//! if let Ok(mut assembler) = Assembler::build(AssemblerConfig::new(&mut in_file, &mut out_file)) {
+//!     while let Ok(Some(encoded_instruction)) = assembler.get_next_encoded_instruction() {
 //!        actual_output.push_str(&encoded_instruction);
 //!        actual_output.push('\n');
 //!    }
@@ -145,6 +175,8 @@
 //! Alternatively you can call the [`Disassembler::get_next`] or [`Disassembler::get_to_end`] method to return the instructions as [`String`].
 //! 
 //! Lastly you can call the [`Disassembler::get_and_write_next`] or [`Disassembler::get_and_write_to_end`] method to both write to the output and return a [`String`].
+//! 
+//! For quick scripts, [`disassemble`] and [`disassemble_str`] decode machine words or the text of a `.hack` file without building a [`Disassembler`].
 //!
 //! ### Examples
 //! 
@@ -187,18 +219,15 @@
 //! # @0
 //! # 0;JMP
 //! # ";
-//! let args = DisassemblerConfig {
-//!     reader: &mut reader,
-//!     writer: None::<&mut Cursor<&mut [u8]>>,
-//! };
+//! let args = DisassemblerConfig::without_writer(&mut reader);
 //! let mut disassembler = Disassembler::new(args);
 //!
 //! let mut actual_output = String::new();
-//! let first_line = match disassembler.get_next(){
+//! let first_line = match disassembler.get_next().unwrap() {
 //!     Some(line) => line + "\n",
-//!     None => "".to_string(), // This would mean the reader had no valid instructions
+//!     None => "".to_string(), // This would mean the reader had no instructions
 //! };
-//! let the_rest = disassembler.get_to_end();
+//! let the_rest = disassembler.get_to_end().unwrap();
 //! actual_output.push_str(&first_line);
 //! actual_output.push_str(&(the_rest.unwrap()));
 //!
@@ -225,10 +254,7 @@
 //! # ";
 //! let mut output = Cursor::new(Vec::new());
 //!
-//! let args = DisassemblerConfig {
-//!    reader: &mut reader,
-//!   writer: Some(output.borrow_mut()),
-//! };
+//! let args = DisassemblerConfig::new(&mut reader, output.borrow_mut());
 //! 
 //! {
 //! let mut disassembler = Disassembler::new(args);
@@ -267,10 +293,7 @@
 //! // This code block allows us to borrow output without explicitly dropping the disassembler
 //! // You could replace the code block by dropping the disassembler explicitly
 //! { 
-//!     let args = DisassemblerConfig {
-//!        reader: &mut reader,
-//!        writer: Some(output.borrow_mut()),
-//!     };
+//!     let args = DisassemblerConfig::new(&mut reader, output.borrow_mut());
 //! 
 //!     let mut disassembler = Disassembler::new(args);
 //!     
@@ -290,21 +313,91 @@
 //! # License
 //!
 //! This project is licensed under the MIT or Apache-2.0 license, at your option.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 //Define our library structure here
 mod lib {
-    pub mod assembler;
+    // The core of the library, only needing `alloc`
     pub mod encoder;
-    pub mod disassembler;
+    pub mod error;
+    pub mod error_codes;
     pub mod decoder;
+    pub mod diagnostic;
+    pub mod fields;
+    pub mod instruction;
+    pub(crate) mod json;
+    pub mod parser;
+    pub mod symbol_table;
+
+    // Everything reading or writing through `std::io`
+    #[cfg(feature = "std")]
+    pub mod annotations;
+    #[cfg(feature = "std")]
+    pub mod assembler;
+    #[cfg(feature = "std")]
+    pub mod disassembler;
+    #[cfg(feature = "std")]
+    pub mod explainer;
+    #[cfg(feature = "std")]
+    pub mod file_assembler;
+    #[cfg(feature = "std")]
+    pub mod output_format;
+    #[cfg(feature = "std")]
+    pub mod parse_tree;
+    #[cfg(feature = "std")]
+    pub mod rom_image;
+    #[cfg(feature = "std")]
+    pub mod session;
+    #[cfg(feature = "std")]
+    pub mod sink;
+    #[cfg(feature = "std")]
+    pub mod query;
+    #[cfg(feature = "std")]
+    pub mod refactor;
+    #[cfg(feature = "std")]
+    pub mod telemetry;
+    #[cfg(feature = "std")]
+    pub mod test_script;
+    #[cfg(feature = "std")]
+    pub mod warnings;
 }
 
 // Here we declare what parts of the library are exposed to the user
 // Namely the Assembler Struct and the Instruction Enum
 pub use lib::{
-    assembler::{ Assembler, Instruction },
-    decoder::decode_instruction,
-    disassembler::{ Disassembler, DisassemblerConfig },
-    encoder::encode_instruction,
+    decoder::{ decode_instruction, decode_instruction_u16, decode_all_into },
+    diagnostic::{ Diagnostic, Severity, Span },
+    encoder::{ encode_instruction, encode_instruction_u16 },
+    error_codes,
+    error::RhasmError,
+    instruction::{ Instruction, InstructionSpans },
+    diagnostic,
+    encoder,
+    fields,
+    parser::{ self, parse_line },
+    symbol_table::{ self, SymbolKind, SymbolTable },
+};
+
+#[cfg(feature = "std")]
+pub use lib::{
+    assembler::{ Assembler, AssemblerConfig, ConstantOverflow, LintLevel, MachineCodeFormat, Progress, assemble, assemble_single_pass, assemble_str },
+    decoder::decode_all_to_writer,
+    disassembler::{ Disassembler, DisassemblerConfig, MissingOutput, disassemble, disassemble_str },
+    explainer::explain_instruction,
+    file_assembler::FileAssembler,
+    annotations,
     assembler,
     disassembler,
+    output_format::{ self, OutputFormat },
+    parse_tree,
+    rom_image,
+    query,
+    session,
+    sink::{ self, OutputSink },
+    refactor,
+    telemetry,
+    test_script,
+    warnings,
 };
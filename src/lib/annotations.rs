@@ -0,0 +1,131 @@
+//! Module for annotation files, which name and describe regions of ROM so tools can label them in their output.
+//!
+//! Every line of an annotation file names a single address or an inclusive range of addresses, followed by a name and an optional description.
+//! Blank lines and lines starting with `#` or `//` are ignored.
+//!
+//! ```text
+//! # ROM layout of Pong
+//! 0-15     init       Set up the stack and screen pointers
+//! 16-240   main_loop  Poll the keyboard and move the paddle
+//! 1200     ball       Ball sprite table
+//! ```
+//!
+//! The [`crate::Disassembler`] renders each region as a comment header above its first instruction, see [`crate::Disassembler::with_annotations`].
+
+use std::io;
+use crate::RhasmError;
+
+/// A named range of ROM addresses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// The first address of the region.
+    pub start: u16,
+    /// The last address of the region, equal to `start` for a single address.
+    pub end: u16,
+    /// The name of the region, e.g. `main_loop`.
+    pub name: String,
+    /// What the region holds, if described.
+    pub description: Option<String>,
+}
+
+impl Region {
+    /// The comment lines rendered above the first instruction of the region, without trailing newlines.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::annotations::Region;
+    ///
+    /// let region = Region { start: 16, end: 240, name: "main_loop".to_string(), description: Some("Poll the keyboard".to_string()) };
+    /// assert_eq!(region.header(), vec!["// ==== main_loop (ROM[16]-ROM[240]) ====", "// Poll the keyboard"]);
+    /// ```
+    pub fn header(&self) -> Vec<String> {
+        let addresses = match self.start == self.end {
+            true => format!("ROM[{}]", self.start),
+            false => format!("ROM[{}]-ROM[{}]", self.start, self.end),
+        };
+        let mut header = vec![format!("// ==== {} ({}) ====", self.name, addresses)];
+        if let Some(description) = &self.description {
+            header.push(format!("// {}", description));
+        }
+        header
+    }
+}
+
+/// The regions of an annotation file, in the order they were listed.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::annotations::Annotations;
+///
+/// let annotations = Annotations::parse("# Layout\n0-15 init Set up pointers\n16 main_loop\n").unwrap();
+///
+/// assert_eq!(annotations.regions().len(), 2);
+/// assert_eq!(annotations.starting_at(16).next().unwrap().name, "main_loop");
+/// assert_eq!(annotations.containing(3).next().unwrap().description.as_deref(), Some("Set up pointers"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Annotations {
+    regions: Vec<Region>,
+}
+
+impl Annotations {
+    /// Parse the contents of an annotation file in the format described in the [module documentation](self).
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Io`] of kind [`io::ErrorKind::InvalidData`] naming the line if it does not start with an address or range followed by a name,
+    ///   if an address does not fit in 16 bits, or if a range ends before it starts.
+    pub fn parse(source: &str) -> Result<Annotations, RhasmError> {
+        let mut regions = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            let region = parse_region(line).ok_or_else(|| {
+                let message = format!("Malformed annotation on line {}: {}, expected `START[-END] NAME [DESCRIPTION]`", index + 1, line);
+                RhasmError::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+            })?;
+            regions.push(region);
+        }
+        Ok(Annotations { regions })
+    }
+
+    /// Every region, in the order they were listed.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// The regions starting at `address`, in the order they were listed.
+    pub fn starting_at(&self, address: u16) -> impl Iterator<Item = &Region> {
+        self.regions.iter().filter(move |region| region.start == address)
+    }
+
+    /// The regions `address` lies in, in the order they were listed.
+    pub fn containing(&self, address: u16) -> impl Iterator<Item = &Region> {
+        self.regions.iter().filter(move |region| (region.start..=region.end).contains(&address))
+    }
+}
+
+// Parse `START[-END] NAME [DESCRIPTION]`, returning None if the line is malformed
+fn parse_region(line: &str) -> Option<Region> {
+    let (range, rest) = line.split_once(char::is_whitespace)?;
+    let rest = rest.trim_start();
+    let (name, description) = match rest.split_once(char::is_whitespace) {
+        Some((name, description)) => (name, Some(description.trim().to_string())),
+        None => (rest, None),
+    };
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+        None => {
+            let address = range.parse().ok()?;
+            (address, address)
+        }
+    };
+    if name.is_empty() || end < start {
+        return None;
+    }
+    Some(Region { start, end, name: name.to_string(), description })
+}
@@ -1,118 +1,976 @@
-use lazy_static::lazy_static;
-use crate::lib::encoder;
-use regex::Regex;
+use crate::lib::{ encoder, error_codes, json, telemetry::{ self, Counters }, warnings };
+use crate::lib::instruction::locate_error;
+use crate::lib::parser::{ invalid_line, parse_line_spanned, parse_source_line, strip_comment, CommentStripper, ParsedLine };
+use crate::lib::symbol_table::{ resolve_aliases, SymbolKind, SymbolTable };
+use crate::{ Diagnostic, OutputFormat, OutputSink, RhasmError, Severity, Span };
 use std::{
-    collections::HashMap,
-    io::{ BufRead, BufReader, BufWriter, Lines, Read, Write },
+    collections::{ HashMap, HashSet },
+    io::{ self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write },
     iter::Peekable,
+    ops::Range,
 };
 
-lazy_static! {
-    static ref INSTRUCTION_REGEX: Regex = Regex::new({
-        r"(?x) # Ignore whitespace and allow comments
-    ^(?:
-        @(?P<a_symbol>[a-zA-Z_\.\$:][\w\.\$:]*|\d+) # A-instruction (address or symbol)
-      |
-        \((?P<l_label>[a-zA-Z_\.\$:][\w\.\$:]+)\)   # L-instruction (label)
-      |
-        (?:
-            (?P<c_dest>[ADM]{1,3})?  # Optional dest part for C-instruction
-            =?
-            (?P<c_comp>[AMD01!+\-&|]+) # Required comp part for C-instruction
-            ;?
-            (?P<c_jump>[A-Z]{3})?   # Optional jump part for C-instruction
-        )
-    )$"
-    }).unwrap();
+pub use crate::lib::instruction::{ Instruction, InstructionSpans };
+
+// How much of an overlong line is quoted in its diagnostic
+const QUOTED_PREFIX_LEN: usize = 40;
+
+// Splits the source into lines like `BufRead::lines`, but gives up with a diagnostic as soon as a line or the whole input
+// grows past its limit, so neither is ever buffered in full
+pub(crate) struct BoundedLines<B> {
+    reader: B,
+    max_line_length: Option<usize>,
+    max_input_size: Option<u64>,
+    line_number: usize,
+    bytes_read: u64,
+    // Set once a limit is exceeded or reading fails, no more lines are returned after that
+    failed: bool,
+}
+
+impl<B: BufRead> BoundedLines<B> {
+    pub(crate) fn new(reader: B, max_line_length: Option<usize>, max_input_size: Option<u64>) -> BoundedLines<B> {
+        BoundedLines { reader, max_line_length, max_input_size, line_number: 0, bytes_read: 0, failed: false }
+    }
+
+    fn fail(&mut self, err: RhasmError) -> Option<Result<String, RhasmError>> {
+        self.failed = true;
+        Some(Err(err))
+    }
+}
+
+impl<B: BufRead> Iterator for BoundedLines<B> {
+    type Item = Result<String, RhasmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let line_number = self.line_number + 1;
+        let mut line: Vec<u8> = Vec::new();
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    return self.fail(read_error(err, line_number).into());
+                }
+            };
+            if available.is_empty() {
+                break;
+            }
+            let newline = available.iter().position(|byte| *byte == b'\n');
+            let chunk_len = newline.map_or(available.len(), |index| index + 1);
+            let content_len = line.len() + newline.unwrap_or(available.len());
+            line.extend_from_slice(&available[..chunk_len]);
+            self.reader.consume(chunk_len);
+            self.bytes_read += chunk_len as u64;
+            if let Some(max) = self.max_input_size.filter(|max| self.bytes_read > *max) {
+                let message = format!("Input is larger than the limit of {} bytes", max);
+                return self.fail(
+                    RhasmError::Diagnostics(vec![Diagnostic::new(Span::new(line_number, 0, 0), "", &message).with_code(error_codes::INPUT_TOO_LARGE)])
+                );
+            }
+            if let Some(max) = self.max_line_length.filter(|max| content_len > *max) {
+                let quoted = String::from_utf8_lossy(&line[..line.len().min(QUOTED_PREFIX_LEN)]).into_owned();
+                let message = format!("Line is longer than the limit of {} bytes", max);
+                let diagnostic = Diagnostic::new(Span::new(line_number, 0, quoted.len()), &format!("{}...", quoted), &message)
+                    .with_code(error_codes::LINE_TOO_LONG);
+                return self.fail(RhasmError::Diagnostics(vec![diagnostic]));
+            }
+            if newline.is_some() {
+                break;
+            }
+        }
+        if line.is_empty() {
+            return None;
+        }
+        self.line_number = line_number;
+        if line.ends_with(b"\n") {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+        }
+        match String::from_utf8(line) {
+            Ok(line) => Some(Ok(line)),
+            Err(_) => {
+                let err = io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8");
+                self.fail(read_error(err, line_number).into())
+            }
+        }
+    }
+}
+
+// Name the line that was being read when reading the source failed, keeping the kind of the error
+pub(crate) fn read_error(err: io::Error, line: usize) -> io::Error {
+    io::Error::new(err.kind(), format!("Failed to read line {} of the input: {}", line, err))
+}
+
+// The diagnostic made by `locate_error` for an error in the source
+fn first_diagnostic(err: RhasmError) -> Option<Diagnostic> {
+    match err {
+        RhasmError::Diagnostics(mut diagnostics) => diagnostics.pop(),
+        _ => None,
+    }
+}
+
+// The number of instructions the Hack ROM holds
+pub(crate) const ROM_SIZE: usize = 1 << 15;
+
+// Variables are allocated below the screen memory map, which starts at RAM[16384]
+pub(crate) const DATA_SEGMENT_END: u16 = 16384;
+
+// The error for a program with `count` instructions, pointing at the first one that falls outside ROM
+pub(crate) fn rom_overflow(count: usize, span: Span, text: &str) -> Diagnostic {
+    Diagnostic::new(span, text, &format!("Program has {} instructions, but the Hack ROM only holds {}", count, ROM_SIZE))
+        .with_note(None, &format!("this is the first instruction outside ROM, at address {}", ROM_SIZE))
+        .with_code(error_codes::ROM_OVERFLOW)
+}
+
+// The error for a label defined at `span` that was first defined at `first`
+pub(crate) fn duplicate_label_error(label: &str, first: Span, span: Span, text: &str) -> Diagnostic {
+    let message = format!("Label {} is defined twice, on line {} and line {}", label, first.line, span.line);
+    Diagnostic::new(span, text, &message)
+        .with_note(Some(first), &format!("{} is first defined here", label))
+        .with_code(error_codes::DUPLICATE_LABEL)
+}
+
+// The error for an alias declared at `span` whose target is neither a label nor a predefined symbol
+pub(crate) fn unresolved_alias(alias: &str, target: &str, span: Span) -> Diagnostic {
+    Diagnostic::new(
+        span,
+        &format!(".alias {} {}", alias, target),
+        &format!("Alias target {} is not a label or predefined symbol", target)
+    ).with_code(error_codes::UNRESOLVED_ALIAS)
+}
+
+// Find the first variable that will be allocated at or past `ram_end` in the second pass
+// Every symbol not in `symbol_table` after the first pass becomes a variable, in the order of its first use
+pub(crate) fn variable_overflow(
+    instructions: &[Instruction],
+    spans: &[InstructionSpans],
+    symbol_table: &SymbolTable,
+    ram_end: u16,
+    as_warning: bool
+) -> Option<Diagnostic> {
+    let base = symbol_table.next_free_address();
+    let capacity = usize::from(ram_end.checked_sub(base)?);
+    let mut variables: HashSet<&str> = HashSet::new();
+    for (instruction, spans) in instructions.iter().zip(spans) {
+        let Instruction::AInstruction(symbol) = instruction else {
+            continue;
+        };
+        if symbol.chars().all(|char| char.is_ascii_digit()) || symbol_table.contains(symbol) {
+            continue;
+        }
+        if variables.insert(symbol) && variables.len() > capacity {
+            return Some(variable_overflow_at(symbol, spans.instruction, &instruction.to_string(), base, ram_end, as_warning));
+        }
+    }
+    None
+}
+
+// The error, or warning if `as_warning` is set, for the variable `symbol` used at `span` that falls outside RAM[base..ram_end]
+pub(crate) fn variable_overflow_at(symbol: &str, span: Span, text: &str, base: u16, ram_end: u16, as_warning: bool) -> Diagnostic {
+    let capacity = ram_end.saturating_sub(base);
+    let message = format!(
+        "Variable {} is allocated RAM[{}], past the data segment holding {} variable{}",
+        symbol,
+        ram_end,
+        capacity,
+        if capacity == 1 { "" } else { "s" }
+    );
+    let note = match ram_end {
+        DATA_SEGMENT_END => format!("variables are allocated from RAM[{}], RAM[{}] onwards is the screen memory map", base, DATA_SEGMENT_END),
+        ram_end => format!("variables are allocated from RAM[{}] and must stay below RAM[{}]", base, ram_end),
+    };
+    let diagnostic = match as_warning {
+        true => Diagnostic::warning(span, text, &message).with_lint("variable-overflow"),
+        false => Diagnostic::new(span, text, &message).with_code(error_codes::VARIABLE_OVERFLOW),
+    };
+    diagnostic.with_note(None, &note)
+}
+
+/// Enum to represent how the [`Assembler`] treats A-Instruction constants that do not fit in 15 bits, e.g. `@70000`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConstantOverflow {
+    /// Reject the constant with [`RhasmError::AddressOutOfRange`], the default.
+    #[default]
+    Error,
+    /// Keep the lowest 15 bits of the constant and report a warning stating the original and truncated value.
+    Truncate,
+    /// Keep the lowest 15 bits of the constant without reporting anything.
+    Wrap,
+}
+
+/// Enum to represent a milestone reached by the [`Assembler`], reported to the callback set with [`AssemblerConfig::with_progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// The first pass started, with the symbol table holding only the predefined symbols.
+    FirstPassStarted,
+    /// The first pass completed without errors, having parsed `instructions` instructions.
+    FirstPassCompleted { instructions: usize },
+    /// The instruction at ROM address `encoded - 1` was encoded, out of the `total` instructions in the program.
+    Encoded { encoded: usize, total: usize },
+}
+
+/// Enum to represent how the [`Assembler`] writes each encoded instruction to its output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MachineCodeFormat {
+    /// 16 binary digits per line, as read by the Nand2Tetris CPU Emulator, the default.
+    #[default]
+    Binary,
+    /// 4 lower case hexadecimal digits per line, as read by e.g. Logisim ROM components.
+    Hex,
+}
+
+impl MachineCodeFormat {
+    // Format a single machine word
+    pub(crate) fn format_word(self, word: u16) -> String {
+        match self {
+            MachineCodeFormat::Binary => format!("{:016b}", word),
+            MachineCodeFormat::Hex => format!("{:04x}", word),
+        }
+    }
+}
+
+/// Enum to represent how the [`Assembler`] reports the findings of a lint, named in [`crate::warnings::LINTS`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Do not report the findings.
+    Allow,
+    /// Report the findings as warnings, the program is still assembled.
+    Warn,
+    /// Report the findings as errors, the program is not assembled.
+    Deny,
 }
 
-/// Enum to represent the different types of instructions in the Hack Assembly Language.
-/// Contains variants for A-Instructions and C-Instructions.
-/// Each variant contains the necessary data to represent the instruction.
-#[derive(Clone, Debug, PartialEq)]
-pub enum Instruction {
-    /// A-Instruction variant, contains the address or symbol of the instruction.
-    AInstruction(String),
-    /// C-Instruction variant, contains the destination, computation, and jump mnemonics, respectively.
-    CInstruction(String, String, String),
+/// Struct to hold the arguments for the [`Assembler`] constructor.
+///
+/// Only the reader is required, every other option has a default and can be set with the `with_*` methods.
+/// The struct can not be constructed directly outside of this crate so that new options can be added without breaking existing code,
+/// use [`AssemblerConfig::new`] or [`AssemblerConfig::without_writer`] instead.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use std::io::Cursor;
+///
+/// let mut in_file = Cursor::new("@UART\nM=D\n@counter\nM=0\n");
+/// let mut out_file = Cursor::new(Vec::new());
+/// let config = AssemblerConfig::new(&mut in_file, &mut out_file)
+///     .with_predefined_symbol("UART", 24577)
+///     .with_ram_start(1024);
+/// let mut assembler = Assembler::build(config).unwrap();
+/// assembler.advance_to_end().unwrap();
+///
+/// assert_eq!(assembler.symbol_table.get("UART"), Some(24577));
+/// assert_eq!(assembler.symbol_table.get("counter"), Some(1024));
+/// ```
+#[non_exhaustive]
+pub struct AssemblerConfig<'a, R: Read, W: Write> {
+    /// The assembly source to read from.
+    pub reader: &'a mut R,
+    /// The destination the encoded instructions are written to.
+    /// When it is [`None`], the methods that write to the output return an error, see [`AssemblerConfig::without_writer`].
+    pub writer: Option<&'a mut W>,
+    /// If set, every symbol and its address is written to it as `name:address` once the last instruction is encoded.
+    pub symbol_file: Option<&'a mut dyn Write>,
+    /// If set, both assembly passes are narrated to it, see [`AssemblerConfig::with_trace_file`].
+    pub trace_file: Option<&'a mut dyn Write>,
+    /// If set, every machine word written to the output is also sent to it, see [`AssemblerConfig::with_sink`].
+    pub sink: Option<&'a mut dyn OutputSink>,
+    /// If set, called with every milestone of the assembly, see [`AssemblerConfig::with_progress`].
+    pub on_progress: Option<&'a mut dyn FnMut(Progress)>,
+    /// Symbols added to the predefined symbols of the Hack specification, e.g. the addresses of memory mapped devices.
+    pub predefined_symbols: Vec<(String, u16)>,
+    /// The RAM address the first variable is allocated at, `16` by default.
+    pub ram_start: u16,
+    /// The RAM address variables must stay below, `16384` by default, see [`AssemblerConfig::with_ram_end`].
+    pub ram_end: u16,
+    /// Accept syntax from other assembler dialects, see [`AssemblerConfig::with_lenient_syntax`].
+    pub lenient_syntax: bool,
+    /// Report labels defined more than once as warnings rather than errors, see [`AssemblerConfig::with_duplicate_labels_as_warnings`].
+    pub duplicate_labels_as_warnings: bool,
+    /// How A-Instruction constants that do not fit in 15 bits are treated, see [`ConstantOverflow`].
+    pub constant_overflow: ConstantOverflow,
+    /// Report variables allocated past the data segment as warnings rather than errors, see [`AssemblerConfig::with_variable_overflow_as_warning`].
+    pub variable_overflow_as_warning: bool,
+    /// Also check the source against the conventions of the Hack platform, see [`AssemblerConfig::with_style_checks`].
+    pub style_checks: bool,
+    /// The level of every lint whose findings are not reported at their default severity, see [`AssemblerConfig::with_lint_level`].
+    pub lint_levels: HashMap<String, LintLevel>,
+    /// The longest source line accepted in bytes, unlimited by default, see [`AssemblerConfig::with_max_line_length`].
+    pub max_line_length: Option<usize>,
+    /// The largest source accepted in bytes, unlimited by default, see [`AssemblerConfig::with_max_input_size`].
+    pub max_input_size: Option<u64>,
+    /// How encoded instructions are written to the output, see [`MachineCodeFormat`].
+    pub machine_code_format: MachineCodeFormat,
+    /// If set, writes the encoded instructions instead of [`AssemblerConfig::machine_code_format`], see [`AssemblerConfig::with_output_format`].
+    pub output_format: Option<&'a dyn OutputFormat>,
+    /// Run the first pass while building the assembler, `true` by default, see [`AssemblerConfig::with_eager_first_pass`].
+    pub eager_first_pass: bool,
+}
+
+impl<'a, R, W> AssemblerConfig<'a, R, W> where R: Read, W: Write {
+    /// Create a config reading the source from `reader` and writing the encoded instructions to `writer`, with every other option at its default.
+    pub fn new(reader: &'a mut R, writer: &'a mut W) -> AssemblerConfig<'a, R, W> {
+        AssemblerConfig::with_optional_writer(reader, Some(writer))
+    }
+
+    // Every option at its default, shared by `new` and `without_writer`
+    fn with_optional_writer(reader: &'a mut R, writer: Option<&'a mut W>) -> AssemblerConfig<'a, R, W> {
+        AssemblerConfig {
+            reader,
+            writer,
+            symbol_file: None,
+            trace_file: None,
+            sink: None,
+            on_progress: None,
+            predefined_symbols: Vec::new(),
+            ram_start: SymbolTable::new().next_free_address(),
+            ram_end: DATA_SEGMENT_END,
+            lenient_syntax: false,
+            duplicate_labels_as_warnings: false,
+            constant_overflow: ConstantOverflow::Error,
+            variable_overflow_as_warning: false,
+            style_checks: false,
+            lint_levels: HashMap::new(),
+            max_line_length: None,
+            max_input_size: None,
+            machine_code_format: MachineCodeFormat::Binary,
+            output_format: None,
+            eager_first_pass: true,
+        }
+    }
+
+    /// Write every symbol and its address to `symbol_file` as `name:address` once the last instruction is encoded.
+    pub fn with_symbol_file(mut self, symbol_file: &'a mut dyn Write) -> Self {
+        self.symbol_file = Some(symbol_file);
+        self
+    }
+
+    /// Narrate both assembly passes to `trace_file`.
+    ///
+    /// The trace lists every label recorded during the first pass together with its ROM address,
+    /// and every symbolic A-Instruction resolved during the second pass, including when a new variable is allocated.
+    /// It is meant as a readable log for studying how the two-pass process works.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let mut trace = Vec::new();
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_trace_file(&mut trace);
+    /// if let Ok(mut assembler) = Assembler::build(config) {
+    ///     assembler.advance_to_end().unwrap();
+    /// }
+    /// let trace = String::from_utf8(trace).unwrap();
+    /// assert!(trace.contains("(LOOP) recorded at ROM[0]"));
+    /// assert!(trace.contains("@i is a new variable, allocated RAM[16]"));
+    /// ```
+    pub fn with_trace_file(mut self, trace_file: &'a mut dyn Write) -> Self {
+        self.trace_file = Some(trace_file);
+        self
+    }
+
+    /// Also send every machine word written to the output to `sink`, e.g. an attached emulator.
+    /// Combine sinks with [`OutputSink::tee`] to send the words to more than one, see [`OutputSink`] for an example.
+    /// A sink can stand in for the writer, see [`AssemblerConfig::without_writer`].
+    pub fn with_sink(mut self, sink: &'a mut dyn OutputSink) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Call `on_progress` with every milestone of the assembly, e.g. to print them or drive a progress bar.
+    /// Nothing is reported otherwise, the assembler never prints on its own.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@2\nD=A\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let mut milestones = Vec::new();
+    /// let mut record = |progress| milestones.push(progress);
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_progress(&mut record);
+    /// let mut assembler = Assembler::build(config).unwrap();
+    /// assembler.advance_to_end().unwrap();
+    /// drop(assembler);
+    ///
+    /// assert_eq!(milestones, vec![
+    ///     Progress::FirstPassStarted,
+    ///     Progress::FirstPassCompleted { instructions: 2 },
+    ///     Progress::Encoded { encoded: 1, total: 2 },
+    ///     Progress::Encoded { encoded: 2, total: 2 },
+    /// ]);
+    /// ```
+    pub fn with_progress(mut self, on_progress: &'a mut dyn FnMut(Progress)) -> Self {
+        self.on_progress = Some(on_progress);
+        self
+    }
+
+    /// Add a predefined symbol, can be called repeatedly.
+    /// A symbol with the same name as one of the Hack specification's predefined symbols replaces it.
+    pub fn with_predefined_symbol(mut self, name: &str, address: u16) -> Self {
+        self.predefined_symbols.push((name.to_string(), address));
+        self
+    }
+
+    /// Add every symbol of `symbols` as a predefined symbol, e.g. from a map of device addresses or a file read with
+    /// [`crate::symbol_table::parse_symbol_file`], so they resolve to fixed addresses instead of being allocated as variables.
+    /// Symbols are added in order, like repeated calls to [`AssemblerConfig::with_predefined_symbol`].
+    pub fn with_predefined_symbols<S: AsRef<str>>(mut self, symbols: impl IntoIterator<Item = (S, u16)>) -> Self {
+        for (name, address) in symbols {
+            self = self.with_predefined_symbol(name.as_ref(), address);
+        }
+        self
+    }
+
+    /// Allocate variables from `ram_start` onwards instead of from address `16`.
+    pub fn with_ram_start(mut self, ram_start: u16) -> Self {
+        self.ram_start = ram_start;
+        self
+    }
+
+    /// Keep variables below `ram_end` instead of below the screen memory map at address `16384`,
+    /// e.g. to reserve a static region at the top of the data segment.
+    /// A variable that would be allocated at `ram_end` or above is reported like one allocated in the screen memory map,
+    /// see [`AssemblerConfig::with_variable_overflow_as_warning`]. Nothing is checked if the variables start at or above `ram_end`.
+    pub fn with_ram_end(mut self, ram_end: u16) -> Self {
+        self.ram_end = ram_end;
+        self
+    }
+
+    /// Allocate variables within `range` only, see [`AssemblerConfig::with_ram_start`] and [`AssemblerConfig::with_ram_end`].
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@first\nD=M\n@second\nD=D+M\n@third\nD=D+M\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_variable_range(1024..1026);
+    /// let Err(RhasmError::Diagnostics(diagnostics)) = Assembler::build(config) else {
+    ///     panic!("expected the third variable to be rejected");
+    /// };
+    ///
+    /// assert_eq!(diagnostics[0].message, "Variable third is allocated RAM[1026], past the data segment holding 2 variables");
+    /// assert_eq!(diagnostics[0].notes[0].message, "variables are allocated from RAM[1024] and must stay below RAM[1026]");
+    /// ```
+    pub fn with_variable_range(self, range: Range<u16>) -> Self {
+        self.with_ram_start(range.start).with_ram_end(range.end)
+    }
+
+    /// Accept the comment styles of other assembler dialects in addition to `//`,
+    /// namely `;` and `#` line comments and `/* ... */` block comments, which may span several lines.
+    ///
+    /// A `;` directly followed by a jump mnemonic still separates the jump, so `0;JMP ; loop forever` is a jump with a comment.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("/* start\n   of program */\n@2 ; load\n# jump\n0;JMP ; forever\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_lenient_syntax(true);
+    /// let mut assembler = Assembler::build(config).unwrap();
+    ///
+    /// assert_eq!(assembler.get_next_encoded_instruction().unwrap(), Some("0000000000000010".to_string()));
+    /// assert_eq!(assembler.get_next_encoded_instruction().unwrap(), Some("1110101010000111".to_string()));
+    ///
+    /// // Symbols with characters outside ASCII are still reported as invalid instructions
+    /// let mut in_file = Cursor::new("@ébc\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_lenient_syntax(true);
+    /// let Err(RhasmError::Diagnostics(diagnostics)) = Assembler::build(config) else {
+    ///     panic!("expected the symbol to be rejected");
+    /// };
+    /// assert_eq!(diagnostics[0].code.as_deref(), Some("E0005"));
+    /// ```
+    pub fn with_lenient_syntax(mut self, lenient_syntax: bool) -> Self {
+        self.lenient_syntax = lenient_syntax;
+        self
+    }
+
+    /// Report labels defined more than once as warnings rather than errors, the last definition of such a label is used.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let source = "(LOOP)\n@LOOP\n0;JMP\n(LOOP)\n@LOOP\n0;JMP\n";
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let mut in_file = Cursor::new(source);
+    /// let result = Assembler::build(AssemblerConfig::new(&mut in_file, &mut out_file));
+    /// assert!(matches!(result, Err(RhasmError::Diagnostics(_))));
+    ///
+    /// let mut in_file = Cursor::new(source);
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_duplicate_labels_as_warnings(true);
+    /// let assembler = Assembler::build(config).unwrap();
+    /// assert_eq!(assembler.diagnostics()[0].message, "Label LOOP is defined twice, on line 1 and line 4");
+    /// assert_eq!(assembler.symbol_table.get("LOOP"), Some(2));
+    /// ```
+    pub fn with_duplicate_labels_as_warnings(mut self, duplicate_labels_as_warnings: bool) -> Self {
+        self.duplicate_labels_as_warnings = duplicate_labels_as_warnings;
+        self
+    }
+
+    /// Choose how A-Instruction constants that do not fit in 15 bits are treated, rejecting them by default.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@70000\nD=A\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_constant_overflow(ConstantOverflow::Truncate);
+    /// let mut assembler = Assembler::build(config).unwrap();
+    ///
+    /// assert_eq!(
+    ///     assembler.diagnostics()[0].message,
+    ///     "A-Instruction constant 70000 does not fit in 15 bits, truncated to 4464"
+    /// );
+    /// assert_eq!(assembler.get_next_encoded_instruction().unwrap(), Some(format!("{:016b}", 4464)));
+    /// ```
+    pub fn with_constant_overflow(mut self, constant_overflow: ConstantOverflow) -> Self {
+        self.constant_overflow = constant_overflow;
+        self
+    }
+
+    /// Report variables allocated at `ram_end` or above as a warning rather than an error, see [`AssemblerConfig::with_ram_end`].
+    /// By default `ram_end` is `16384`, so these are the variables allocated in the screen memory map.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@first\nD=M\n@second\nD=M\n(END)\n@END\n0;JMP\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file)
+    ///     .with_ram_start(16383)
+    ///     .with_variable_overflow_as_warning(true);
+    /// let assembler = Assembler::build(config).unwrap();
+    ///
+    /// assert_eq!(
+    ///     assembler.diagnostics()[0].message,
+    ///     "Variable second is allocated RAM[16384], past the data segment holding 1 variable"
+    /// );
+    /// ```
+    pub fn with_variable_overflow_as_warning(mut self, variable_overflow_as_warning: bool) -> Self {
+        self.variable_overflow_as_warning = variable_overflow_as_warning;
+        self
+    }
+
+    /// Also check the source against the conventions of the Hack platform, e.g. that labels are written in upper case.
+    /// Departures from the conventions are reported as [`Severity::Note`](crate::Severity::Note) diagnostics.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("(loop)\n@loop\n0;JMP\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_style_checks(true);
+    /// let assembler = Assembler::build(config).unwrap();
+    ///
+    /// assert_eq!(assembler.diagnostics()[0].severity, Severity::Note);
+    /// assert_eq!(assembler.diagnostics()[0].message, "Label loop is not written in upper case");
+    /// ```
+    pub fn with_style_checks(mut self, style_checks: bool) -> Self {
+        self.style_checks = style_checks;
+        self
+    }
+
+    /// Report the findings of `lint` at `level` instead of their default severity, e.g. to fail the build on unused labels.
+    /// Setting the level of a style lint runs it even without [`AssemblerConfig::with_style_checks`].
+    /// The lints are listed in [`crate::warnings::LINTS`], other names have no effect.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("(UNUSED)\n(END)\n@END\n0;JMP\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_lint_level("unused-label", LintLevel::Deny);
+    /// let Err(RhasmError::Diagnostics(diagnostics)) = Assembler::build(config) else {
+    ///     panic!("expected the unused label to be an error");
+    /// };
+    /// assert_eq!(diagnostics[0].to_string(), "Label UNUSED is never used [unused-label] @ line [1]: (UNUSED)");
+    ///
+    /// let mut in_file = Cursor::new("(UNUSED)\n(END)\n@END\n0;JMP\n");
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_lint_level("unused-label", LintLevel::Allow);
+    /// assert!(Assembler::build(config).unwrap().diagnostics().is_empty());
+    /// ```
+    pub fn with_lint_level(mut self, lint: &str, level: LintLevel) -> Self {
+        self.lint_levels.insert(lint.to_string(), level);
+        self
+    }
+
+    /// Reject source lines longer than `max_line_length` bytes, not counting the line ending.
+    /// Reading stops at the first byte past the limit, so an overlong line is never buffered in full,
+    /// which protects services assembling untrusted uploads.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new(format!("@1\n@{}\n", "9".repeat(100_000)));
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_max_line_length(256);
+    /// let Err(RhasmError::Diagnostics(diagnostics)) = Assembler::build(config) else {
+    ///     panic!("expected the second line to be rejected");
+    /// };
+    /// assert_eq!(diagnostics[0].message, "Line is longer than the limit of 256 bytes");
+    /// assert_eq!(diagnostics[0].span.line, 2);
+    /// ```
+    pub fn with_max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = Some(max_line_length);
+        self
+    }
+
+    /// Reject sources larger than `max_input_size` bytes, reading no further than the first byte past the limit.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@1\nD=A\n".repeat(1000));
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_max_input_size(1024);
+    /// let Err(RhasmError::Diagnostics(diagnostics)) = Assembler::build(config) else {
+    ///     panic!("expected the input to be rejected");
+    /// };
+    /// assert_eq!(diagnostics[0].message, "Input is larger than the limit of 1024 bytes");
+    /// ```
+    pub fn with_max_input_size(mut self, max_input_size: u64) -> Self {
+        self.max_input_size = Some(max_input_size);
+        self
+    }
+
+    /// Write the encoded instructions to the output in `machine_code_format` rather than as binary digits.
+    /// [`Assembler::get_next_encoded_instruction`] always returns binary digits.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@256\nD=A\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_machine_code_format(MachineCodeFormat::Hex);
+    /// let mut assembler = Assembler::build(config).unwrap();
+    /// assembler.advance_to_end().unwrap();
+    /// drop(assembler);
+    ///
+    /// assert_eq!(String::from_utf8(out_file.into_inner()).unwrap(), "0100\nec10\n");
+    /// ```
+    pub fn with_machine_code_format(mut self, machine_code_format: MachineCodeFormat) -> Self {
+        self.machine_code_format = machine_code_format;
+        self
+    }
+
+    /// Write the encoded instructions to the output in `output_format`, e.g. [`crate::output_format::RawBytes`] or a format of your own,
+    /// taking precedence over [`AssemblerConfig::with_machine_code_format`]. The sink still receives every word as it is.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use rhasm::output_format::RawBytes;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@256\nD=A\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_output_format(&RawBytes);
+    /// let mut assembler = Assembler::build(config).unwrap();
+    /// assembler.advance_to_end().unwrap();
+    /// drop(assembler);
+    ///
+    /// assert_eq!(out_file.into_inner(), vec![0x01, 0x00, 0xec, 0x10]);
+    /// ```
+    pub fn with_output_format(mut self, output_format: &'a dyn OutputFormat) -> Self {
+        self.output_format = Some(output_format);
+        self
+    }
+
+    /// Choose whether [`Assembler::build`] runs the first pass, reading the whole source, before returning.
+    /// If not, the first pass runs on the first call to [`Assembler::run_first_pass`] or any method encoding an instruction,
+    /// and problems with the source are reported from there instead of from `build`.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@1\nD=Q\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_eager_first_pass(false);
+    /// let mut assembler = Assembler::build(config).unwrap();
+    ///
+    /// assert!(assembler.instructions.is_empty());
+    /// assert!(matches!(assembler.run_first_pass(), Err(RhasmError::Diagnostics(_))));
+    /// ```
+    pub fn with_eager_first_pass(mut self, eager_first_pass: bool) -> Self {
+        self.eager_first_pass = eager_first_pass;
+        self
+    }
+}
+
+impl<'a, R> AssemblerConfig<'a, R, io::Sink> where R: Read {
+    /// Create a config reading the source from `reader` without anywhere to write the encoded instructions,
+    /// for callers that only want the strings returned by [`Assembler::get_next_encoded_instruction`].
+    /// Unless a sink is set with [`AssemblerConfig::with_sink`], [`Assembler::advance_once`] and [`Assembler::advance_to_end`]
+    /// return an error of kind [`io::ErrorKind::NotFound`] instead of encoding anything.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@2\nD=A\n");
+    /// let mut assembler = Assembler::build(AssemblerConfig::without_writer(&mut in_file)).unwrap();
+    ///
+    /// assert!(matches!(assembler.advance_once(), Err(RhasmError::Io(_))));
+    /// assert_eq!(assembler.get_next_encoded_instruction().unwrap(), Some("0000000000000010".to_string()));
+    /// ```
+    pub fn without_writer(reader: &'a mut R) -> AssemblerConfig<'a, R, io::Sink> {
+        AssemblerConfig::with_optional_writer(reader, None)
+    }
 }
 
 /// Struct to represent the Assembler's internal logic.
-/// Contains the file references, symbol table, and other necessary state.
+/// Contains the input and output references, symbol table, and other necessary state.
 /// Can be constructed using the `build` function.
+///
+/// The input can be anything implementing [`Read`] and the output anything implementing [`Write`],
+/// neither has to be seekable, so in-memory buffers, sockets and compressed streams work as well as files.
+///
+/// Besides instructions and labels, the source may declare aliases with `.alias NAME TARGET`,
+/// making `NAME` resolve to the same address as the label or predefined symbol `TARGET`, wherever either is defined.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use std::io::Cursor;
+///
+/// let mut in_file = Cursor::new(".alias BEGIN MAIN_LOOP\n@0\n(MAIN_LOOP)\n@BEGIN\n0;JMP\n");
+/// let mut out_file = Cursor::new(Vec::new());
+/// let assembler = Assembler::build(AssemblerConfig::new(&mut in_file, &mut out_file)).unwrap();
+///
+/// assert_eq!(assembler.symbol_table.get("BEGIN"), Some(1));
+/// ```
+///
+/// Reading from a byte slice and writing to a plain vector:
+///
+/// ```rust
+/// use rhasm::*;
+///
+/// let mut source: &[u8] = b"@2\nD=A\n";
+/// let mut machine_code: Vec<u8> = Vec::new();
+/// let mut assembler = Assembler::build(AssemblerConfig::new(&mut source, &mut machine_code)).unwrap();
+/// assembler.advance_to_end().unwrap();
+/// drop(assembler);
+///
+/// assert_eq!(String::from_utf8(machine_code).unwrap(), "0000000000000010\n1110110000010000\n");
+/// ```
 pub struct Assembler<'a, R, W> where R: Read, W: Write {
-    pub(crate) out_file: BufWriter<&'a mut W>,
-    pub(crate) lines: Peekable<Lines<BufReader<&'a mut R>>>,
-    pub(crate) cur_ram: u16,
+    pub(crate) out_file: Option<BufWriter<&'a mut W>>,
+    pub(crate) lines: Peekable<BoundedLines<BufReader<&'a mut R>>>,
     pub(crate) cur_line: usize,
     pub(crate) cur_instruction: u16,
     /// Symbol table to store the addresses of labels and variables.
-    /// The symbol table is populated during the `build` of the `Assembler`.
-    pub symbol_table: HashMap<String, u16>,
+    /// The symbol table is populated during the `build` of the `Assembler`, variables are added as they are encoded.
+    pub symbol_table: SymbolTable,
     /// Vector of `Instruction`(s) used to store the parsed instructions from the source file.
     /// The vector is populated on `build` and can be used in tandem with the symbol table for custom implementations.
     pub instructions: Vec<Instruction>,
+    /// The location of each instruction in `instructions` within the source file, at the same index.
+    pub spans: Vec<InstructionSpans>,
+    diagnostics: Vec<Diagnostic>,
+    // Aliases declared in the source with their location, resolved at the end of the first pass
+    aliases: Vec<(String, String, Span)>,
+    // Label definitions with their location, for the warnings checks
+    labels: Vec<(String, Span)>,
+    duplicate_labels_as_warnings: bool,
+    constant_overflow: ConstantOverflow,
+    variable_overflow_as_warning: bool,
+    // Variables must be allocated below this RAM address
+    ram_end: u16,
+    style_checks: bool,
+    lint_levels: HashMap<String, LintLevel>,
+    machine_code_format: MachineCodeFormat,
+    output_format: Option<&'a dyn OutputFormat>,
+    comments: CommentStripper,
     pub(crate) fp_flag: bool,
-    pub(crate) instruction_regex: &'static Regex,
-    symbol_file: Option<BufWriter<&'a mut W>>,
+    symbol_file: Option<BufWriter<&'a mut dyn Write>>,
+    trace_file: Option<BufWriter<&'a mut dyn Write>>,
+    sink: Option<&'a mut dyn OutputSink>,
+    on_progress: Option<&'a mut dyn FnMut(Progress)>,
+    counters: Counters,
 }
 
 impl<'a, R, W> Assembler<'a, R, W> where R: Read, W: Write {
     /// Constructor for the [`Assembler`] struct, returns a [`Result`] wrapping either the successfully constructed [`Assembler`] or an [`Err`].
-    /// Takes an input [`File`] and an output [`File`] reference as arguments.
+    /// Takes an [`AssemblerConfig`] holding the input and output references along with any further options.
     /// Returns a [`Result`] wrapping the built [`Assembler`] instance if successful.
-    pub fn build(
-        in_file: &'a mut R,
-        out_file: &'a mut W,
-        symbol_file: Option<&'a mut W>
-    ) -> Result<Assembler<'a, R, W>, Box<dyn std::error::Error>> {
-        // We either accept a file passed in or open the default file
-        // If None is passed in, we open the sample file
-        // Our file reference is then wrapped in a BufReader
-        let in_file: BufReader<&mut R> = BufReader::new(in_file);
-
-        // We either accept a file passed in or create the default file
-        // If None is passed in, we create the sample file
-        // Our file reference is then wrapped in a BufWriter
-        let out_file: BufWriter<&mut W> = BufWriter::new(out_file);
-
-        // We get a peekable iterator of lines from our BufReader
-        let lines: Peekable<Lines<BufReader<&mut R>>> = in_file.lines().peekable();
-
-        // We initialize our symbol table as an empty HashMap
-        // (Maybe we should use &str instead?)
-        let symbol_table: HashMap<String, u16> = HashMap::new();
-
-        let symbol_file = if let Some(writer) = symbol_file {
-            Some(BufWriter::new(writer))
-        } else {
-            None
-        };
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Diagnostics`] listing every line that is neither a valid instruction nor a label,
+    ///   every C-Instruction with an unknown dest, comp or jump mnemonic, every label whose address does not fit in 16 bits, the first instruction that does not fit in the 32K ROM,
+    ///   and the first variable that would be allocated in the screen memory map, along with any warnings found in the same pass.
+    /// * Returns [`RhasmError::Io`] naming the line that was being read if reading the input fails, e.g. midway through a file.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@1\nD=Q\n0;JMP\nfoo bar\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let result = Assembler::build(AssemblerConfig::new(&mut in_file, &mut out_file));
+    /// let Err(RhasmError::Diagnostics(diagnostics)) = result else {
+    ///     panic!("expected both invalid lines to be reported");
+    /// };
+    /// let lines: Vec<usize> = diagnostics.iter().map(|diagnostic| diagnostic.span.line).collect();
+    /// assert_eq!(lines, vec![2, 4]);
+    /// ```
+    pub fn build(config: AssemblerConfig<'a, R, W>) -> Result<Assembler<'a, R, W>, RhasmError> {
+        let AssemblerConfig {
+            reader,
+            writer,
+            symbol_file,
+            trace_file,
+            sink,
+            on_progress,
+            predefined_symbols,
+            ram_start,
+            ram_end,
+            lenient_syntax,
+            duplicate_labels_as_warnings,
+            constant_overflow,
+            variable_overflow_as_warning,
+            style_checks,
+            lint_levels,
+            max_line_length,
+            max_input_size,
+            machine_code_format,
+            output_format,
+            eager_first_pass,
+        } = config;
+
+        // Our file references are wrapped in a BufReader and BufWriter respectively
+        let in_file: BufReader<&mut R> = BufReader::new(reader);
+        let out_file: Option<BufWriter<&mut W>> = writer.map(BufWriter::new);
+
+        // We get a peekable iterator of lines from our BufReader, which stops at the first line or byte past the limits
+        let lines = BoundedLines::new(in_file, max_line_length, max_input_size).peekable();
+
+        // We initialize our symbol table with the predefined symbols, including any added by the config
+        let mut symbol_table = SymbolTable::with_variable_base(ram_start);
+        for (name, address) in &predefined_symbols {
+            symbol_table.define_predefined(name, *address);
+        }
 
         let mut assembler = Assembler {
             out_file,
             lines,
-            cur_ram: 16 /*Starting address for variables*/,
             cur_line: 0,
             cur_instruction: 0,
             symbol_table,
             instructions: Vec::<Instruction>::new(),
+            spans: Vec::<InstructionSpans>::new(),
+            diagnostics: Vec::<Diagnostic>::new(),
+            aliases: Vec::new(),
+            labels: Vec::new(),
+            duplicate_labels_as_warnings,
+            constant_overflow,
+            variable_overflow_as_warning,
+            ram_end,
+            style_checks,
+            lint_levels,
+            machine_code_format,
+            output_format,
+            comments: CommentStripper::new(lenient_syntax),
             fp_flag: false,
-            instruction_regex: &INSTRUCTION_REGEX,
-            symbol_file,
+            symbol_file: symbol_file.map(BufWriter::new),
+            trace_file: trace_file.map(BufWriter::new),
+            sink,
+            on_progress,
+            counters: Counters::new(),
         };
-        assembler.init();
+        if eager_first_pass {
+            assembler.run_first_pass()?;
+        }
         Ok(assembler)
     }
 
+    /// Run the first pass, recording every label and parsing every instruction, unless it has already run.
+    /// Only needed if the first pass was deferred with [`AssemblerConfig::with_eager_first_pass`],
+    /// to read [`Assembler::instructions`] or [`Assembler::diagnostics`] before encoding anything.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns the same errors as [`Assembler::build`] does when it runs the first pass.
+    pub fn run_first_pass(&mut self) -> Result<(), RhasmError> {
+        if !self.fp_flag {
+            self.init().inspect_err(|err| self.counters.record_error(err))?;
+        }
+        Ok(())
+    }
+
+    /// The work done by this assembler so far, see [`crate::telemetry`].
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
     // Function to initialize the assembler and its symbol table
     // Called by constructor to ensure symbol table is populated
-    fn init(&mut self) {
+    fn init(&mut self) -> Result<(), RhasmError> {
         if !self.fp_flag {
-            self.first_pass();
-            println!("First Pass Completed!");
-        } else {
-            println!("First Pass Already Completed!");
+            self.first_pass()?;
+            log::debug!("First pass completed, {} instruction(s) parsed", self.instructions.len());
+            self.report(Progress::FirstPassCompleted { instructions: self.instructions.len() });
+        }
+        Ok(())
+    }
+
+    // Pass a milestone on to the progress callback, if there is one
+    fn report(&mut self, progress: Progress) {
+        if let Some(on_progress) = self.on_progress.as_mut() {
+            on_progress(progress);
         }
     }
 
@@ -126,109 +984,308 @@ impl<'a, R, W> Assembler<'a, R, W> where R: Read, W: Write {
     // Function to run the first pass of the assembler
     // Populates the symbol table with default symbols
     // Additionally parses through the source file and creates a vector of Instructions
-    fn first_pass(&mut self) {
-        self.populate_default_symbols();
-        println!("Generated Default Symbol Table!");
+    // Invalid lines do not stop the pass, they are collected and reported together once every line has been read
+    fn first_pass(&mut self) -> Result<(), RhasmError> {
+        log::debug!("Generated default symbol table");
+        self.report(Progress::FirstPassStarted);
+        self.trace("Pass 1: recording labels".to_string())?;
         while self.can_read_more_instructions() {
-            self.parse_instruction();
+            if let Some(diagnostic) = self.parse_instruction()? {
+                self.diagnostics.push(diagnostic);
+            }
             self.cur_line += 1;
         }
+        if let Some(spans) = self.spans.get(ROM_SIZE) {
+            let text = self.instructions[ROM_SIZE].to_string();
+            self.diagnostics.push(rom_overflow(self.instructions.len(), spans.instruction, &text));
+        }
+        let aliases: Vec<(String, String)> = self.aliases
+            .iter()
+            .map(|(alias, target, _)| (alias.clone(), target.clone()))
+            .collect();
+        for (alias, target, span) in resolve_aliases(&mut self.symbol_table, std::mem::take(&mut self.aliases)) {
+            self.diagnostics.push(unresolved_alias(&alias, &target, span));
+        }
+        let overflow = variable_overflow(
+            &self.instructions,
+            &self.spans,
+            &self.symbol_table,
+            self.ram_end,
+            self.variable_overflow_as_warning
+        );
+        if let Some(overflow) = overflow {
+            self.diagnostics.push(overflow);
+        }
+        let facts = warnings::ProgramFacts {
+            instructions: &self.instructions,
+            spans: &self.spans,
+            labels: &self.labels,
+            aliases: &aliases,
+            symbol_table: &self.symbol_table,
+        };
+        self.diagnostics.extend(warnings::check(&facts));
+        // Style lints only run when asked for, either all at once or by setting their level
+        let style = warnings::check_style(&facts)
+            .into_iter()
+            .filter(|note| self.style_checks || note.lint.as_ref().is_some_and(|lint| self.lint_levels.contains_key(lint)));
+        self.diagnostics.extend(style);
+        self.diagnostics = std::mem::take(&mut self.diagnostics)
+            .into_iter()
+            .filter_map(|diagnostic| self.apply_lint_level(diagnostic))
+            .collect();
+        self.diagnostics.sort_by_key(|diagnostic| (diagnostic.span.line, diagnostic.span.start));
+        if self.diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(RhasmError::Diagnostics(self.diagnostics.clone()));
+        }
         self.fp_flag = true;
+        self.trace(
+            format!(
+                "Pass 1 complete: {} label(s), {} instruction(s)\n\nPass 2: resolving symbols",
+                self.symbol_table.iter_kind(SymbolKind::Label).count(),
+                self.instructions.len()
+            )
+        )?;
+        Ok(())
     }
 
     // Function dedicated to parsing through our source file and creating a vector of Instructions
     // This allows for address labels to be resolved in the second pass
     // As well as us extracting the instructions from the file into enums
-    fn parse_instruction(&mut self) {
+    // Returns a diagnostic instead of an error for invalid lines so the first pass can carry on
+    fn parse_instruction(&mut self) -> Result<Option<Diagnostic>, RhasmError> {
         // We only parse when has_more_commands() is true so we can unwrap safely
-        let line = self.lines.next().unwrap().unwrap();
-        // Remove comments and trim whitespace
-        let line = line.split("//").next().unwrap().trim().to_owned();
+        let raw_line = self.lines.next().unwrap()?;
+        let stripped = self.comments.strip(&raw_line);
+        let line = stripped.trim();
         if line.is_empty() {
-            return;
-        }
-
-        let captures = self.instruction_regex.captures(&line);
-        if let Some(captures) = captures {
-            if let Some(a_symbol) = captures.name("a_symbol") {
-                let addr = a_symbol.as_str();
-                self.instructions.push(Instruction::AInstruction(addr.to_string()));
-            } else if let Some(c_comp) = captures.name("c_comp") {
-                let c_comp = c_comp.as_str();
-                let c_dest = captures.name("c_dest").map_or("", |m| m.as_str());
-                let c_jump = captures.name("c_jump").map_or("", |m| m.as_str());
-                self.instructions.push(
-                    Instruction::CInstruction(
-                        c_dest.to_string(),
-                        c_comp.to_string(),
-                        c_jump.to_string()
-                    )
-                );
-            } else if let Some(l_label) = captures.name("l_label") {
-                let label = l_label.as_str();
-                self.symbol_table.insert(
-                    label.to_string(),
-                    self.instructions.len().try_into().unwrap()
-                );
-            } else {
-                panic!("Invalid Instruction @ line [{}]: {}", self.cur_line, line);
+            return Ok(None);
+        }
+        let indent = stripped.len() - stripped.trim_start().len();
+        let span = Span::new(self.cur_line + 1, indent, indent + line.len());
+
+        match parse_line_spanned(line, span) {
+            Some((ParsedLine::Instruction(mut instruction), spans)) => {
+                // Unknown mnemonics are reported here, so every one of them is found before anything is encoded
+                let invalid = match encoder::validate_mnemonics(&instruction) {
+                    Err(err) => first_diagnostic(locate_error(err, &instruction, &spans)),
+                    Ok(()) => None,
+                };
+                let truncated = self.truncate_constant(&mut instruction, &spans, line);
+                self.instructions.push(instruction);
+                self.spans.push(spans);
+                return Ok(invalid.or(truncated));
+            }
+            Some((ParsedLine::Label(label), _)) => {
+                let Ok(address) = u16::try_from(self.instructions.len()) else {
+                    return Ok(
+                        Some(Diagnostic::new(span, line, "No address left to assign to label").with_code(error_codes::SYMBOL_OVERFLOW))
+                    );
+                };
+                let duplicate = self.duplicate_label(&label, span, line);
+                if duplicate.as_ref().is_some_and(Diagnostic::is_error) {
+                    return Ok(duplicate);
+                }
+                self.symbol_table.define_label(&label, address);
+                if duplicate.is_none() {
+                    self.labels.push((label.clone(), span));
+                }
+                if self.trace_file.is_some() {
+                    self.trace(
+                        format!(
+                            "  line {}: ({}) recorded at ROM[{}]",
+                            self.cur_line + 1,
+                            label,
+                            self.instructions.len()
+                        )
+                    )?;
+                }
+                return Ok(duplicate);
             }
-        } else {
-            panic!("Invalid Instruction @ line [{}]: {}", self.cur_line, line);
-        }
-    }
-
-    // Subroutine to populate the default symbols
-    // Symbol names as per the Hack Assembly Language Specification
-    fn populate_default_symbols(&mut self) {
-        self.symbol_table.insert("SP".to_string(), 0);
-        self.symbol_table.insert("LCL".to_string(), 1);
-        self.symbol_table.insert("ARG".to_string(), 2);
-        self.symbol_table.insert("THIS".to_string(), 3);
-        self.symbol_table.insert("THAT".to_string(), 4);
-        self.symbol_table.insert("R0".to_string(), 0);
-        self.symbol_table.insert("R1".to_string(), 1);
-        self.symbol_table.insert("R2".to_string(), 2);
-        self.symbol_table.insert("R3".to_string(), 3);
-        self.symbol_table.insert("R4".to_string(), 4);
-        self.symbol_table.insert("R5".to_string(), 5);
-        self.symbol_table.insert("R6".to_string(), 6);
-        self.symbol_table.insert("R7".to_string(), 7);
-        self.symbol_table.insert("R8".to_string(), 8);
-        self.symbol_table.insert("R9".to_string(), 9);
-        self.symbol_table.insert("R10".to_string(), 10);
-        self.symbol_table.insert("R11".to_string(), 11);
-        self.symbol_table.insert("R12".to_string(), 12);
-        self.symbol_table.insert("R13".to_string(), 13);
-        self.symbol_table.insert("R14".to_string(), 14);
-        self.symbol_table.insert("R15".to_string(), 15);
-        self.symbol_table.insert("SCREEN".to_string(), 16384);
-        self.symbol_table.insert("KBD".to_string(), 24576);
+            Some((ParsedLine::Alias(alias, target), _)) => {
+                self.aliases.push((alias, target, span));
+            }
+            None => {
+                return Ok(Some(invalid_line(line, span)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The warnings and notes found while building the assembler, in source order.
+    /// Unlike errors, these do not prevent the program from being assembled, see [`crate::warnings`] for the checks that are run.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("(UNUSED)\n@result\nM=0\n(END)\n@END\n0;JMP\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let assembler = Assembler::build(AssemblerConfig::new(&mut in_file, &mut out_file)).unwrap();
+    ///
+    /// let messages: Vec<&str> = assembler.diagnostics().iter().map(|warning| warning.message.as_str()).collect();
+    /// assert_eq!(messages, vec!["Label UNUSED is never used", "Variable result is written but never read"]);
+    /// ```
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Iterate over every instruction of the program together with its ROM address, e.g. for listings and debuggers.
+    /// Labels and aliases take no address, so they are not included. Empty until the first pass has run, see [`Assembler::run_first_pass`].
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@2\n(LOOP)\nD=D-A\n@LOOP\nD;JGT\n");
+    /// let assembler = Assembler::build(AssemblerConfig::without_writer(&mut in_file)).unwrap();
+    ///
+    /// let listing: Vec<String> = assembler.program().map(|(address, instruction)| format!("{}: {}", address, instruction)).collect();
+    /// assert_eq!(listing, vec!["0: @2", "1: D=D-A", "2: @LOOP", "3: D;JGT"]);
+    /// ```
+    pub fn program(&self) -> impl Iterator<Item = (u16, &Instruction)> {
+        // The first pass rejects programs that do not fit in ROM, so every address fits in 15 bits
+        (0..).zip(&self.instructions)
+    }
+
+    /// Iterate over the ROM address of every instruction together with where it was written in the source,
+    /// so debuggers can map machine addresses back to source lines. Empty until the first pass has run.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("// Count down\n@2\n(LOOP)\n  D=D-A\n");
+    /// let assembler = Assembler::build(AssemblerConfig::without_writer(&mut in_file)).unwrap();
+    ///
+    /// let lines: Vec<(u16, usize)> = assembler.source_map().map(|(address, span)| (address, span.line)).collect();
+    /// assert_eq!(lines, vec![(0, 2), (1, 4)]);
+    /// assert_eq!(assembler.source_span(1), Some(Span::new(4, 2, 7)));
+    /// assert_eq!(assembler.source_span(2), None);
+    /// ```
+    pub fn source_map(&self) -> impl Iterator<Item = (u16, Span)> + '_ {
+        (0..).zip(self.spans.iter().map(|spans| spans.instruction))
+    }
+
+    /// Where the instruction at a ROM address was written in the source, or [`None`] if the program is shorter.
+    pub fn source_span(&self, address: u16) -> Option<Span> {
+        self.spans.get(usize::from(address)).map(|spans| spans.instruction)
+    }
+
+    /// Write the [source map](Assembler::source_map) as a single JSON object to `writer`, naming the source file if given.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Io`] if writing fails.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@2\n\n  D=A\n");
+    /// let assembler = Assembler::build(AssemblerConfig::without_writer(&mut in_file)).unwrap();
+    /// let mut map = Vec::new();
+    /// assembler.write_source_map(&mut map, Some("Add.asm")).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(map).unwrap(),
+    ///     "{\"source\":\"Add.asm\",\"mappings\":[{\"address\":0,\"line\":1,\"column\":1},{\"address\":1,\"line\":3,\"column\":3}]}\n"
+    /// );
+    /// ```
+    pub fn write_source_map(&self, writer: &mut dyn Write, source: Option<&str>) -> Result<(), RhasmError> {
+        let mappings: Vec<String> = self
+            .source_map()
+            .map(|(address, span)| format!("{{\"address\":{},\"line\":{},\"column\":{}}}", address, span.line, span.column()))
+            .collect();
+        let source = source.map_or("null".to_string(), json::string);
+        writeln!(writer, "{{\"source\":{},\"mappings\":[{}]}}", source, mappings.join(","))?;
+        Ok(())
+    }
+
+    // Replace a constant too large for 15 bits by its lowest 15 bits, unless such constants are rejected
+    // Returns the warning for the truncation if one should be reported
+    fn truncate_constant(&self, instruction: &mut Instruction, spans: &InstructionSpans, text: &str) -> Option<Diagnostic> {
+        let Instruction::AInstruction(constant) = instruction else {
+            return None;
+        };
+        if self.constant_overflow == ConstantOverflow::Error || !encoder::is_out_of_range(constant) {
+            return None;
+        }
+        let truncated = encoder::wrap_constant(constant);
+        let message = format!("A-Instruction constant {} does not fit in 15 bits, truncated to {}", constant, truncated);
+        *constant = truncated.to_string();
+        match self.constant_overflow {
+            ConstantOverflow::Truncate => Some(Diagnostic::warning(spans.instruction, text, &message).with_lint("truncated-constant")),
+            _ => None,
+        }
+    }
+
+    // Report a diagnostic at the level configured for its lint, or drop it if the lint is allowed
+    fn apply_lint_level(&self, mut diagnostic: Diagnostic) -> Option<Diagnostic> {
+        match diagnostic.lint.as_ref().and_then(|lint| self.lint_levels.get(lint)) {
+            Some(LintLevel::Allow) => {
+                return None;
+            }
+            Some(LintLevel::Warn) => diagnostic.severity = Severity::Warning,
+            Some(LintLevel::Deny) => diagnostic.severity = Severity::Error,
+            None => {}
+        }
+        Some(diagnostic)
+    }
+
+    // Report a label defined at `span` that was already defined earlier, naming both definitions
+    // This is an error unless duplicate labels were downgraded to warnings, in which case the later definition wins
+    fn duplicate_label(&self, label: &str, span: Span, text: &str) -> Option<Diagnostic> {
+        let (_, first) = self.labels.iter().find(|(defined, _)| defined == label)?;
+        let mut diagnostic = duplicate_label_error(label, *first, span, text);
+        if self.duplicate_labels_as_warnings {
+            diagnostic = diagnostic
+                .with_note(None, &format!("the definition on line {} is used", span.line))
+                .with_lint("duplicate-label");
+            diagnostic.severity = Severity::Warning;
+        }
+        Some(diagnostic)
     }
 
     /// Function to advance the assembler by one instruction, this encoded instruction is then immediately written to the output file.
-    pub fn advance_once(&mut self) {
-        let encoded_instruction = self.get_next_encoded_instruction();
-        if let Some(encoded_instruction) = encoded_instruction {
-            self.write_line(encoded_instruction);
+    /// The instruction is flushed before returning, so when a later call fails every instruction written before it is already in the output.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Diagnostics`] pointing at the offending field if the instruction cannot be encoded, see [`encoder::encode_instruction`].
+    /// * Returns [`RhasmError::Io`] if writing to the output fails, or of kind [`io::ErrorKind::NotFound`] without encoding anything
+    ///   if the assembler was built without a writer or sink.
+    pub fn advance_once(&mut self) -> Result<(), RhasmError> {
+        self.require_writer().inspect_err(|err| self.counters.record_error(err))?;
+        if let Some(word) = self.next_word()? {
+            self.write_words(&[word]).inspect_err(|err| self.counters.record_error(err))?;
         }
+        Ok(())
     }
 
     /// Function to advance the assembler to the end of the file, encoding all instructions and writing them all at once to the output file.
-    pub fn advance_to_end(&mut self) {
-        if !self.fp_flag {
-            self.init();
-        }
-        let mut buffer = String::new();
-        while self.cur_instruction < (self.instructions.len() as u16) {
-            let instruction = if let Some(instruction) = self.get_next_encoded_instruction() {
-                instruction
-            } else {
-                break;
-            };
-            buffer.push_str(&format!("{}\n", instruction));
+    /// The output is only written, and then flushed, once every instruction is encoded, so a program that fails to encode leaves no partial output behind.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns the first error encountered while encoding, see [`Assembler::get_next_encoded_instruction`]. Nothing is written in that case.
+    /// * Returns [`RhasmError::Io`] if writing to the output fails, or of kind [`io::ErrorKind::NotFound`] without encoding anything
+    ///   if the assembler was built without a writer or sink.
+    pub fn advance_to_end(&mut self) -> Result<(), RhasmError> {
+        self.require_writer().inspect_err(|err| self.counters.record_error(err))?;
+        self.run_first_pass()?;
+        let mut words = Vec::new();
+        while let Some(word) = self.next_word()? {
+            words.push(word);
         }
-        self.write_line(buffer.trim_end().to_owned());
+        self.write_words(&words).inspect_err(|err| self.counters.record_error(err))
     }
 
     /// Function to get the next encoded instruction from the assembler.
@@ -236,36 +1293,407 @@ impl<'a, R, W> Assembler<'a, R, W> where R: Read, W: Write {
     /// But can also be used to get the encoded instructions as strings rather than being written to a file.
     /// Returns [`None`] if there are no more instructions to encode.
     /// Either use this function, or the [`Assembler::advance_once`] and [`Assembler::advance_to_end`] functions, mixing the two may result in unexpected behavior.
-    pub fn get_next_encoded_instruction(&mut self) -> Option<String> {
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Diagnostics`] pointing at the offending field if the instruction cannot be encoded, see [`encoder::encode_instruction`].
+    /// * Returns [`RhasmError::Io`] if writing the label file or trace fails after the last instruction.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@1\n  @70000\n");
+    /// let mut out_file = Cursor::new(Vec::new());
+    /// let mut assembler = Assembler::build(AssemblerConfig::new(&mut in_file, &mut out_file)).unwrap();
+    /// assembler.get_next_encoded_instruction().unwrap();
+    ///
+    /// let Err(RhasmError::Diagnostics(diagnostics)) = assembler.get_next_encoded_instruction() else {
+    ///     panic!("expected the constant to be rejected");
+    /// };
+    /// assert_eq!(diagnostics[0].span, Span::new(2, 2, 8));
+    /// assert_eq!(diagnostics[0].span.column(), 3);
+    /// ```
+    pub fn get_next_encoded_instruction(&mut self) -> Result<Option<String>, RhasmError> {
+        Ok(self.next_word()?.map(|word| format!("{:016b}", word)))
+    }
+
+    // Encode the next instruction into its machine word, counting and reporting it
+    fn next_word(&mut self) -> Result<Option<u16>, RhasmError> {
+        self.run_first_pass()?;
+        let word = self.encode_next().inspect_err(|err| self.counters.record_error(err))?;
+        if word.is_some() {
+            self.counters.instructions_encoded += 1;
+            self.report(Progress::Encoded { encoded: self.cur_instruction as usize, total: self.instructions.len() });
+        }
+        Ok(word)
+    }
+
+    /// Rewind the second pass to the first instruction, so the program can be encoded again without rebuilding the assembler
+    /// or reading the source a second time. The first pass is not repeated.
+    ///
+    /// The symbol table is kept, so variables keep their addresses. Call [`SymbolTable::clear_variables`] on
+    /// [`Assembler::symbol_table`] as well to allocate them afresh. Nothing already written is taken back:
+    /// the output, sink, symbol file and trace receive the program again when it is encoded again.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut in_file = Cursor::new("@counter\nM=M+1\n");
+    /// let mut assembler = Assembler::build(AssemblerConfig::without_writer(&mut in_file)).unwrap();
+    /// let first: Vec<String> = assembler.by_ref().collect();
+    ///
+    /// assembler.reset();
+    /// assembler.symbol_table.clear_variables();
+    /// let second: Vec<String> = assembler.by_ref().collect();
+    /// assert_eq!(first, second);
+    /// ```
+    pub fn reset(&mut self) {
+        self.cur_instruction = 0;
+    }
+
+    // Encode the instruction at `cur_instruction`, finishing the second pass after the last one
+    fn encode_next(&mut self) -> Result<Option<u16>, RhasmError> {
         // If we have no more instructions to encode, return None
-        let instruction = if
-            let Some(instruction) = self.instructions.get(self.cur_instruction as usize)
-        {
-            instruction
-        } else {
-            return None;
+        let instruction = match self.instructions.get(self.cur_instruction as usize) {
+            Some(instruction) => instruction,
+            None => {
+                return Ok(None);
+            }
         };
-        let out = encoder::encode_instruction(
+        let resolution = match instruction {
+            Instruction::AInstruction(symbol) if
+                self.trace_file.is_some() && !symbol.chars().all(|char| char.is_ascii_digit())
+            => Some(self.describe_resolution(symbol)),
+            _ => None,
+        };
+        let out = encoder::encode_instruction_u16(
             instruction,
-            &mut self.symbol_table,
-            &mut self.cur_ram
-        );
+            &mut self.symbol_table
+        ).map_err(|err| self.locate(err))?;
+        if let Some(resolution) = resolution {
+            self.trace(format!("  ROM[{}]: {}", self.cur_instruction, resolution))?;
+        }
         self.cur_instruction += 1;
         if self.cur_instruction == (self.instructions.len() as u16) {
-            self.write_label_file();
+            self.counters.programs_assembled += 1;
+            self.write_label_file()?;
+            self.trace(format!("Pass 2 complete: {} variable(s) allocated",
+                    self.symbol_table.iter_kind(SymbolKind::Variable).count()
+                )
+            )?;
+            if let Some(writer) = &mut self.trace_file {
+                writer.flush()?;
+            }
+        }
+        Ok(Some(out))
+    }
+
+    // Point an error raised while encoding the current instruction at the offending field
+    // Errors unrelated to the source, i.e. I/O errors, are returned unchanged
+    fn locate(&self, err: RhasmError) -> RhasmError {
+        let index = self.cur_instruction as usize;
+        match (self.instructions.get(index), self.spans.get(index)) {
+            (Some(instruction), Some(spans)) => locate_error(err, instruction, spans),
+            _ => err,
+        }
+    }
+
+    // Fail before encoding anything if there is nowhere to write the output
+    fn require_writer(&self) -> Result<(), RhasmError> {
+        match (&self.out_file, &self.sink) {
+            (None, None) => Err(io::Error::new(io::ErrorKind::NotFound, "No writeable output specified").into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Hand the words last encoded to the sink and the output
+    fn write_words(&mut self, words: &[u16]) -> Result<(), RhasmError> {
+        if let Some(sink) = self.sink.as_mut() {
+            for word in words {
+                sink.write_word(*word)?;
+            }
+            sink.flush()?;
+        }
+        if let Some(out_file) = self.out_file.as_mut() {
+            // The words are the last ones encoded, so the first of them is at the ROM address before theirs
+            let first = self.cur_instruction - words.len() as u16;
+            let format = self.output_format.unwrap_or(&self.machine_code_format);
+            let mut contents = Vec::new();
+            for (address, word) in (first..).zip(words) {
+                format.write_word(&mut contents, address, *word)?;
+            }
+            out_file.write_all(&contents)?;
+            out_file.flush()?;
+            self.counters.bytes_written += contents.len() as u64;
         }
-        Some(out)
+        Ok(())
     }
 
-    fn write_line(&mut self, encoded: String) {
-        write!(self.out_file, "{}\n", encoded.trim()).unwrap();
+    // Describe how a symbolic A-Instruction is about to be resolved, for the pass trace
+    fn describe_resolution(&self, symbol: &str) -> String {
+        let address = self.symbol_table.get(symbol).unwrap_or_default();
+        match self.symbol_table.kind(symbol) {
+            None => {
+                format!(
+                    "@{} is a new variable, allocated RAM[{}]",
+                    symbol,
+                    self.symbol_table.next_free_address()
+                )
+            }
+            Some(SymbolKind::Label) => format!("@{} resolved to label address {}", symbol, address),
+            Some(SymbolKind::Predefined) => format!("@{} resolved to predefined address {}", symbol, address),
+            Some(SymbolKind::Variable) => format!("@{} resolved to variable address {}", symbol, address),
+        }
     }
 
-    fn write_label_file(&mut self) {
+    fn trace(&mut self, message: String) -> Result<(), RhasmError> {
+        if let Some(writer) = &mut self.trace_file {
+            writeln!(writer, "{}", message)?;
+        }
+        Ok(())
+    }
+
+    fn write_label_file(&mut self) -> Result<(), RhasmError> {
         for (label, address) in self.symbol_table.iter() {
             if let Some(writer) = &mut self.symbol_file {
-                write!(writer, "{}:{}\n", label, address).unwrap();
+                writeln!(writer, "{}:{}", label, address)?;
             }
         }
+        Ok(())
+    }
+}
+
+// Add the counters of the assembler to the process-wide total, if enabled
+impl<'a, R, W> Drop for Assembler<'a, R, W> where R: Read, W: Write {
+    fn drop(&mut self) {
+        telemetry::submit(&self.counters);
+    }
+}
+
+/// Implement the [`Iterator`] trait for [`Assembler`]. The assembler will yield each encoded instruction as 16 binary digits,
+/// as returned by [`Assembler::get_next_encoded_instruction`], without writing it to the output.
+/// Iteration ends at the first instruction that can not be encoded, call [`Assembler::get_next_encoded_instruction`] to get the failure instead.
+/// The iterator is not fused, [`Assembler::reset`] rewinds it to the first instruction after it ended.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use std::io::Cursor;
+///
+/// let mut in_file = Cursor::new("@2\nD=A\n@3\nD=D+A\n");
+/// let assembler = Assembler::build(AssemblerConfig::without_writer(&mut in_file)).unwrap();
+///
+/// let words: Vec<(u16, u16)> = (0..)
+///     .zip(assembler.take(2))
+///     .map(|(address, instruction)| (address, u16::from_str_radix(&instruction, 2).unwrap()))
+///     .collect();
+/// assert_eq!(words, vec![(0, 2), (1, 0b1110110000010000)]);
+/// ```
+impl<'a, R, W> Iterator for Assembler<'a, R, W> where R: Read + 'a, W: Write + 'a {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.get_next_encoded_instruction().ok().flatten()
+    }
+}
+
+// Every encoded instruction is written as 16 binary digits followed by a newline
+const ENCODED_LINE_LEN: u64 = 17;
+
+/// Assemble a whole program with the default options and return its machine words.
+///
+/// A shorthand for building an [`Assembler`] without a writer and collecting its output, see [`assemble_str`] for source held in a string.
+///
+/// ### Errors
+///
+/// * Returns the same errors as [`Assembler::build`] and [`Assembler::advance_to_end`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::assemble;
+/// use std::io::Cursor;
+///
+/// let words = assemble(Cursor::new("@2\nD=A\n")).unwrap();
+/// assert_eq!(words, vec![2, 0b1110110000010000]);
+/// ```
+pub fn assemble(mut reader: impl Read) -> Result<Vec<u16>, RhasmError> {
+    let mut words = Vec::new();
+    let config = AssemblerConfig::without_writer(&mut reader).with_sink(&mut words);
+    let mut assembler = Assembler::build(config)?;
+    assembler.advance_to_end()?;
+    drop(assembler);
+    Ok(words)
+}
+
+/// Assemble a whole program held in a string with the default options and return its machine words, see [`assemble`].
+///
+/// ### Errors
+///
+/// * Returns the same errors as [`Assembler::build`] and [`Assembler::advance_to_end`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ assemble_str, RhasmError };
+///
+/// assert_eq!(assemble_str("(END)\n@END\n0;JMP\n").unwrap(), vec![0, 0b1110101010000111]);
+/// assert!(matches!(assemble_str("D=Q\n"), Err(RhasmError::Diagnostics(_))));
+/// ```
+pub fn assemble_str(source: &str) -> Result<Vec<u16>, RhasmError> {
+    assemble(source.as_bytes())
+}
+
+/// Assemble a source file in a single pass, writing each instruction as soon as it is read.
+///
+/// Unlike the [`Assembler`], instructions are not cached until the end of the input.
+/// A-Instructions referencing symbols that are not yet known are written as placeholders,
+/// once the whole input has been read they are backpatched with either the label's address or a newly allocated variable.
+/// Variables are allocated in order of first use, so the output is identical to the two-pass [`Assembler`].
+///
+/// This requires the output to implement [`Seek`], e.g. a [`std::fs::File`] or an in-memory [`std::io::Cursor`].
+///
+/// ### Returns
+///
+/// Returns the final symbol table, including predefined symbols, labels, and variables.
+///
+/// ### Errors
+///
+/// * Returns [`RhasmError::Parse`] if a line is not a valid instruction, naming the offending line.
+/// * Returns [`RhasmError::Diagnostics`] if a label is defined twice, an alias target is never defined,
+///   the program does not fit in the 32K ROM, or a variable would be allocated in the screen memory map.
+/// * Returns an error if an instruction cannot be encoded, see [`encoder::encode_instruction`].
+/// * Returns [`RhasmError::SymbolOverflow`] if a variable or label does not fit in the address space.
+/// * Returns [`RhasmError::Io`] if reading the input or writing/seeking the output fails.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ assemble_single_pass, RhasmError };
+/// use std::io::Cursor;
+///
+/// let mut in_file = Cursor::new("@END\n0;JMP\n(END)\n@END\n0;JMP\n");
+/// let mut out_file = Cursor::new(Vec::new());
+/// let symbol_table = assemble_single_pass(&mut in_file, &mut out_file).unwrap();
+///
+/// assert_eq!(symbol_table.get("END"), Some(2));
+/// assert_eq!(
+///     String::from_utf8(out_file.into_inner()).unwrap(),
+///     "0000000000000010\n1110101010000111\n0000000000000010\n1110101010000111\n"
+/// );
+///
+/// // Errors are reported like the two-pass assembler reports them
+/// let mut in_file = Cursor::new("(LOOP)\n  (LOOP)\n");
+/// let Err(RhasmError::Diagnostics(diagnostics)) = assemble_single_pass(&mut in_file, &mut Cursor::new(Vec::new())) else {
+///     panic!("expected the second definition to be rejected");
+/// };
+/// assert_eq!((diagnostics[0].span.line, diagnostics[0].span.start), (2, 2));
+/// assert_eq!(diagnostics[0].notes[0].message, "LOOP is first defined here");
+/// ```
+pub fn assemble_single_pass<R, W>(
+    in_file: &mut R,
+    out_file: &mut W
+) -> Result<SymbolTable, RhasmError>
+    where R: Read, W: Write + Seek
+{
+    let start = out_file.stream_position()?;
+    let mut writer = BufWriter::new(out_file);
+    let mut symbol_table = SymbolTable::new();
+    // ROM addresses of placeholders, the symbol they are waiting on and where it is used
+    let mut pending: Vec<(u16, String, Span)> = Vec::new();
+    // Aliases are only defined once every label is known, so their uses are deferred like unknown symbols
+    let mut aliases: Vec<(String, String, Span)> = Vec::new();
+    // Where each label is defined, to report duplicate definitions
+    let mut label_spans: HashMap<String, Span> = HashMap::new();
+    let mut rom_address: u16 = 0;
+    // Instructions past the end of ROM are only counted, to report the size of the program
+    let mut outside_rom: Option<(Span, String)> = None;
+    let mut instruction_count: usize = 0;
+
+    for (line_number, line) in BufReader::new(in_file).lines().enumerate() {
+        let raw_line = line?;
+        let line = strip_comment(&raw_line);
+        if line.is_empty() {
+            continue;
+        }
+        let indent = raw_line.len() - raw_line.trim_start().len();
+        let span = Span::new(line_number + 1, indent, indent + line.len());
+        let parsed = parse_source_line(line);
+        if matches!(parsed, Some(ParsedLine::Instruction(_))) {
+            instruction_count += 1;
+            if instruction_count > ROM_SIZE {
+                outside_rom.get_or_insert_with(|| (span, line.to_string()));
+                continue;
+            }
+        }
+        let encoded = match parsed {
+            Some(ParsedLine::Label(label)) => {
+                if let Some(first) = label_spans.insert(label.clone(), span) {
+                    return Err(RhasmError::Diagnostics(vec![duplicate_label_error(&label, first, span, line)]));
+                }
+                symbol_table.define_label(&label, rom_address);
+                continue;
+            }
+            Some(ParsedLine::Alias(alias, target)) => {
+                aliases.push((alias, target, span));
+                continue;
+            }
+            Some(ParsedLine::Instruction(Instruction::AInstruction(symbol))) if
+                !symbol.chars().all(|char| char.is_ascii_digit()) &&
+                !symbol_table.contains(&symbol)
+            => {
+                pending.push((rom_address, symbol, span));
+                format!("{:016b}", 0)
+            }
+            Some(ParsedLine::Instruction(instruction)) => {
+                // Unknown symbols were deferred above, so the encoder never allocates variables here
+                encoder::encode_instruction(&instruction, &mut symbol_table)?
+            }
+            None => {
+                return Err(RhasmError::Parse { line: line_number + 1, text: line.to_string() });
+            }
+        };
+        writeln!(writer, "{}", encoded)?;
+        rom_address = rom_address
+            .checked_add(1)
+            .ok_or_else(|| RhasmError::SymbolOverflow(line.to_string()))?;
+    }
+
+    if let Some((span, text)) = outside_rom {
+        return Err(RhasmError::Diagnostics(vec![rom_overflow(instruction_count, span, &text)]));
+    }
+
+    let unresolved: Vec<Diagnostic> = resolve_aliases(&mut symbol_table, aliases)
+        .into_iter()
+        .map(|(alias, target, span)| unresolved_alias(&alias, &target, span))
+        .collect();
+    if !unresolved.is_empty() {
+        return Err(RhasmError::Diagnostics(unresolved));
+    }
+
+    // Any symbol still unknown after the whole input has been read is a variable
+    let base = symbol_table.next_free_address();
+    for (address, symbol, span) in pending {
+        let value = match symbol_table.get(&symbol) {
+            Some(value) => value,
+            None if symbol_table.next_free_address() == DATA_SEGMENT_END => {
+                let text = format!("@{}", symbol);
+                let diagnostic = variable_overflow_at(&symbol, span, &text, base, DATA_SEGMENT_END, false);
+                return Err(RhasmError::Diagnostics(vec![diagnostic]));
+            }
+            None => symbol_table.allocate_variable(&symbol)?,
+        };
+        writer.seek(SeekFrom::Start(start + (address as u64) * ENCODED_LINE_LEN))?;
+        write!(writer, "{:016b}", value)?;
     }
+    writer.seek(SeekFrom::Start(start + (rom_address as u64) * ENCODED_LINE_LEN))?;
+    writer.flush()?;
+    Ok(symbol_table)
 }
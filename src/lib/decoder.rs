@@ -1,11 +1,14 @@
-use std::error::Error;
+use alloc::{ boxed::Box, format, string::String };
+use core::{ error::Error, fmt };
+#[cfg(feature = "std")]
+use std::io;
+use crate::lib::fields::{ CFields, Comp, Jump };
 
 /// Decode an encoded instruction into a human readable instruction.
 /// Labels and variables are lost in the encoding process.
 /// Thus the decoded instruction will not be the same as the original instruction.
 /// But will still assemble back into the same machine code.
 pub fn decode_instruction(encoded_instruction: &str) -> Result<String, Box<dyn Error>> {
-    let mut decoded_instruction = String::new();
     let char_count = encoded_instruction.len();
     if char_count != 16 {
         return Err(format!("Invalid encoded instruction length - Expected 16 found {}", char_count).into());
@@ -13,89 +16,135 @@ pub fn decode_instruction(encoded_instruction: &str) -> Result<String, Box<dyn E
         return Err("Invalid encoded instruction, please make sure instruction is in binary".into());
     }
 
-    let first_char = encoded_instruction.chars().next().unwrap();
-    if first_char == '0' {
-        let addr = &encoded_instruction[1..];
-        decoded_instruction.push_str(&format!("@{}", u16::from_str_radix(addr, 2).unwrap()));
-    } else {
-        let comp = decode_comp(&encoded_instruction[3..10]);
-        let dest = decode_dest(&encoded_instruction[10..13]);
-        let jump = decode_jump(&encoded_instruction[13..]);
-        if let Some(dest) = dest {
-            decoded_instruction.push_str(dest);
-            decoded_instruction.push('=');
-        }
-        if let None = comp {
-            return Err(format!("Invalid comp mnemonic {}", &encoded_instruction[3..9]).into());
-        } else {
-            decoded_instruction.push_str(comp.unwrap());
-        }
-        if let Some(jump) = jump {
-            decoded_instruction.push(';');
-            decoded_instruction.push_str(jump);
-        }
+    decode_instruction_u16(u16::from_str_radix(encoded_instruction, 2)?)
+}
+
+/// Decode a machine word into a human readable instruction, see [`decode_instruction`] for its 16 character binary form.
+///
+/// Meant for emulators and readers of binary files, which can decode words without formatting them as text first.
+///
+/// ### Errors
+///
+/// * Returns an error naming the bits if a C-Instruction does not contain a valid computation.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::decode_instruction_u16;
+///
+/// assert_eq!(decode_instruction_u16(0x0100).unwrap(), "@256");
+/// assert_eq!(decode_instruction_u16(0b1110001100000001).unwrap(), "D;JGT");
+/// assert!(decode_instruction_u16(0b1111111111000000).is_err());
+/// ```
+pub fn decode_instruction_u16(word: u16) -> Result<String, Box<dyn Error>> {
+    let mut decoded_instruction = String::new();
+    if write_decoded_word(word, &mut decoded_instruction).is_err() {
+        return Err(format!("Invalid comp mnemonic {:07b}", (word >> 6) & 0x7f).into());
     }
+    decoded_instruction.pop();
     Ok(decoded_instruction)
 }
 
-fn decode_dest(encoded_dest: &str) -> Option<&str> {
-    match encoded_dest {
-        "000" => None,
-        "001" => Some("M"),
-        "010" => Some("D"),
-        "011" => Some("MD"),
-        "100" => Some("A"),
-        "101" => Some("AM"),
-        "110" => Some("AD"),
-        "111" => Some("AMD"),
-        _ => None,
+/// Decode a slice of instruction words, appending one instruction per line to `out`.
+///
+/// Decodes the fields with [`CFields::decode`] and writes straight into `out`, so no allocations are made per instruction.
+/// Reusing the same `out` buffer (after clearing it) avoids allocating entirely once it has grown large enough,
+/// which makes this suitable for disassembling an entire ROM on every refresh.
+///
+/// ### Errors
+///
+/// * Returns an error naming the offending index if a word does not contain a valid computation.
+///   Instructions decoded before the invalid word are left in `out`.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::decode_all_into;
+///
+/// let mut out = String::new();
+/// decode_all_into(&[0x0100, 0xEC10, 0x0000, 0xEA87], &mut out).unwrap();
+/// assert_eq!(out, "@256\nD=A\n@0\n0;JMP\n");
+/// ```
+pub fn decode_all_into(words: &[u16], out: &mut String) -> Result<(), Box<dyn Error>> {
+    for (index, word) in words.iter().enumerate() {
+        // Writing to a String never fails, so only invalid words can error here
+        if write_decoded_word(*word, out).is_err() {
+            return Err(invalid_word_message(index, *word).into());
+        }
+    }
+    Ok(())
+}
+
+/// Decode a slice of instruction words, writing one instruction per line to `writer`.
+///
+/// Writer-based variant of [`decode_all_into`], also free of per instruction allocations.
+/// Wrap unbuffered writers in a [`std::io::BufWriter`] as every field is written separately.
+///
+/// ### Errors
+///
+/// * Returns an [`io::ErrorKind::InvalidData`] error naming the offending index if a word does not contain a valid computation.
+/// * Returns any error raised by `writer`.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::decode_all_to_writer;
+///
+/// let mut out = Vec::new();
+/// decode_all_to_writer(&[0x0010, 0xFC88], &mut out).unwrap();
+/// assert_eq!(out, b"@16\nM=M-1\n");
+/// ```
+#[cfg(feature = "std")]
+pub fn decode_all_to_writer<W: io::Write>(words: &[u16], writer: &mut W) -> io::Result<()> {
+    let mut adapter = IoAdapter { writer, error: None };
+    for (index, word) in words.iter().enumerate() {
+        if write_decoded_word(*word, &mut adapter).is_err() {
+            return Err(match adapter.error.take() {
+                Some(error) => error,
+                None => io::Error::new(io::ErrorKind::InvalidData, invalid_word_message(index, *word)),
+            });
+        }
     }
+    Ok(())
 }
 
-fn decode_comp(encoded_comp: &str) -> Option<&str> {
-    match encoded_comp {
-        "0101010" => Some("0"),
-        "0111111" => Some("1"),
-        "0111010" => Some("-1"),
-        "0001100" => Some("D"),
-        "0110000" => Some("A"),
-        "0001101" => Some("!D"),
-        "0110001" => Some("!A"),
-        "0001111" => Some("-D"),
-        "0110011" => Some("-A"),
-        "0011111" => Some("D+1"),
-        "0110111" => Some("A+1"),
-        "0001110" => Some("D-1"),
-        "0110010" => Some("A-1"),
-        "0000010" => Some("D+A"),
-        "0010011" => Some("D-A"),
-        "0000111" => Some("A-D"),
-        "0000000" => Some("D&A"),
-        "0010101" => Some("D|A"),
-        "1110000" => Some("M"),
-        "1110001" => Some("!M"),
-        "1110011" => Some("-M"),
-        "1110111" => Some("M+1"),
-        "1110010" => Some("M-1"),
-        "1000010" => Some("D+M"),
-        "1010011" => Some("D-M"),
-        "1000111" => Some("M-D"),
-        "1000000" => Some("D&M"),
-        "1010101" => Some("D|M"),
-        _ => None,
+fn invalid_word_message(index: usize, word: u16) -> String {
+    format!("Invalid comp mnemonic {:07b} in word {} ({:016b})", (word >> 6) & 0x7f, index, word)
+}
+
+// Write a single decoded word followed by a newline
+// Fails with fmt::Error either when the writer fails or the comp bits are invalid
+fn write_decoded_word<T: fmt::Write>(word: u16, out: &mut T) -> fmt::Result {
+    if word & 0x8000 == 0 {
+        return writeln!(out, "@{}", word);
     }
+    let fields = CFields::decode(word).ok_or(fmt::Error)?;
+    writeln!(out, "{}", fields)
+}
+
+// Lets the fmt::Write based decoding write to an io::Write, keeping the io error around
+#[cfg(feature = "std")]
+struct IoAdapter<'w, W: io::Write> {
+    writer: &'w mut W,
+    error: Option<io::Error>,
 }
 
-fn decode_jump(encoded_jump: &str) -> Option<&str> {
-    match encoded_jump {
-        "000" => None,
-        "001" => Some("JGT"),
-        "010" => Some("JEQ"),
-        "011" => Some("JGE"),
-        "100" => Some("JLT"),
-        "101" => Some("JNE"),
-        "110" => Some("JLE"),
-        "111" => Some("JMP"),
-        _ => None,
+#[cfg(feature = "std")]
+impl<W: io::Write> fmt::Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_all(s.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
     }
 }
+
+// Every valid computation mnemonic, in the canonical operand order
+pub(crate) fn comp_mnemonics() -> impl Iterator<Item = &'static str> {
+    Comp::ALL.into_iter().map(Comp::mnemonic)
+}
+
+// Every valid jump mnemonic
+pub(crate) fn jump_mnemonics() -> impl Iterator<Item = &'static str> {
+    Jump::ALL.into_iter().filter(|jump| *jump != Jump::Null).map(Jump::mnemonic)
+}
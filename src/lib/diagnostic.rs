@@ -0,0 +1,529 @@
+//! Module containing the diagnostics reported for problems found in a source file.
+
+use alloc::{ format, string::{ String, ToString }, vec::Vec };
+use core::{ fmt, iter };
+use crate::lib::{ error_codes, json };
+
+/// A range of bytes within a single source line.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    /// The 1-based source line.
+    pub line: usize,
+    /// The 0-based byte offset within the line the span starts at.
+    pub start: usize,
+    /// The 0-based byte offset within the line right after the end of the span.
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a span covering the bytes `start..end` of the 1-based `line`.
+    pub fn new(line: usize, start: usize, end: usize) -> Span {
+        Span { line, start, end }
+    }
+
+    /// The 1-based column the span starts at.
+    pub fn column(&self) -> usize {
+        self.start + 1
+    }
+}
+
+/// Enum to represent how serious a [`Diagnostic`] is, ordered from least to most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Information that does not point at a problem, e.g. about non-canonical but valid syntax.
+    Note,
+    /// Suspicious but valid code, the program is still assembled.
+    Warning,
+    /// The program can not be assembled.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Enum to represent the kind of message attached to a [`Diagnostic`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoteKind {
+    /// Additional context, e.g. where a duplicated label was first defined.
+    Note,
+    /// A suggestion on how to fix the problem.
+    Help,
+}
+
+/// A message attached to a [`Diagnostic`], optionally pointing at a related location.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    /// Whether the message gives context or a suggestion.
+    pub kind: NoteKind,
+    /// The related location, if any.
+    pub span: Option<Span>,
+    /// The message itself.
+    pub message: String,
+}
+
+/// A single problem found in a source file, pointing at the part of the line it was found in.
+///
+/// Unlike a [`crate::RhasmError`], diagnostics are collected rather than returned one at a time,
+/// so every problem in a file can be reported at once.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ Diagnostic, Severity, Span };
+///
+/// let first = Span::new(1, 0, 6);
+/// let diagnostic = Diagnostic::new(Span::new(9, 0, 6), "(LOOP)", "Duplicate label")
+///     .with_note(Some(first), "LOOP was first defined here")
+///     .with_help("rename one of the labels");
+///
+/// assert_eq!(diagnostic.severity, Severity::Error);
+/// assert_eq!(
+///     diagnostic.to_string(),
+///     "Duplicate label @ line [9]: (LOOP)\n  note: LOOP was first defined here @ line [1]\n  help: rename one of the labels"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// The location of the problem, as narrow as possible, e.g. just the computation of a C-Instruction.
+    pub span: Span,
+    /// The offending line without comments.
+    pub text: String,
+    /// A short description of the problem, e.g. `Invalid Instruction`.
+    pub message: String,
+    /// Notes and help messages giving further context, in the order they were attached.
+    pub notes: Vec<Note>,
+    /// The name of the check that found the problem, e.g. `unused-label`, if its level can be configured.
+    /// See [`crate::AssemblerConfig::with_lint_level`].
+    pub lint: Option<String>,
+    /// The stable code of the problem, e.g. `E0002`, explained in [`crate::error_codes`].
+    pub code: Option<String>,
+}
+
+impl Diagnostic {
+    /// Create an error for the problem at `span`, found in the source line `text`.
+    pub fn new(span: Span, text: &str, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            text: text.to_string(),
+            message: message.to_string(),
+            notes: Vec::new(),
+            lint: None,
+            code: None,
+        }
+    }
+
+    /// Create a warning for suspicious code at `span`, found in the source line `text`.
+    pub fn warning(span: Span, text: &str, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::new(span, text, message)
+        }
+    }
+
+    /// Create a note about the code at `span`, found in the source line `text`.
+    pub fn note(span: Span, text: &str, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Note,
+            ..Diagnostic::new(span, text, message)
+        }
+    }
+
+    /// Attach a note giving further context, optionally pointing at a related location.
+    pub fn with_note(mut self, span: Option<Span>, message: &str) -> Self {
+        self.notes.push(Note { kind: NoteKind::Note, span, message: message.to_string() });
+        self
+    }
+
+    /// Attach a suggestion on how to fix the problem.
+    pub fn with_help(mut self, message: &str) -> Self {
+        self.notes.push(Note { kind: NoteKind::Help, span: None, message: message.to_string() });
+        self
+    }
+
+    /// Name the check that found the problem, so its level can be configured.
+    /// The diagnostic is given the lint's code from the catalog as well.
+    pub fn with_lint(mut self, lint: &str) -> Self {
+        self.lint = Some(lint.to_string());
+        self.code = error_codes::lookup(lint).map(|entry| entry.code.to_string());
+        self
+    }
+
+    /// Give the diagnostic a stable code from [`crate::error_codes`], e.g. `E0002`.
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    // The message followed by the name of the check that found the problem or its code, if it has either
+    // Lints are named rather than numbered, as the name is what their level is configured by
+    fn message_with_lint(&self) -> String {
+        match (&self.lint, &self.code) {
+            (Some(lint), _) => format!("{} [{}]", self.message, lint),
+            (None, Some(code)) => format!("{} [{}]", self.message, code),
+            (None, None) => self.message.clone(),
+        }
+    }
+
+    /// Format the diagnostic the way GCC does, as `file:line:column: severity: message`, for editors' problem matchers.
+    /// Attached notes follow on their own lines, pointing at their own location or at the diagnostic's if they have none.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::{ Diagnostic, Span };
+    ///
+    /// let diagnostic = Diagnostic::new(Span::new(2, 4, 5), "D=Q", "Invalid Computation Mnemonic: Q");
+    /// assert_eq!(diagnostic.to_gcc_string("prog.asm"), "prog.asm:2:5: error: Invalid Computation Mnemonic: Q in `D=Q`");
+    /// ```
+    pub fn to_gcc_string(&self, file: &str) -> String {
+        let mut out = format!(
+            "{}:{}:{}: {}: {} in `{}`",
+            file,
+            self.span.line,
+            self.span.column(),
+            self.severity,
+            self.message_with_lint(),
+            self.text
+        );
+        for note in &self.notes {
+            let span = note.span.unwrap_or(self.span);
+            let kind = match note.kind {
+                NoteKind::Note => "note",
+                NoteKind::Help => "help",
+            };
+            out.push_str(&format!("\n{}:{}:{}: {}: {}", file, span.line, span.column(), kind, note.message));
+        }
+        out
+    }
+
+    /// Format the diagnostic as a single line JSON object, for tools that process diagnostics programmatically.
+    ///
+    /// The object contains the `file`, `severity`, `message`, 1-based `line` and `column`, the exclusive `end_column`,
+    /// the offending source line as `snippet`, an array of `notes`, each with a `kind`, `message`, and optional `line` and `column`,
+    /// the name of the `lint` that found the problem and its `code`, each `null` if it has none.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::{ Diagnostic, Span };
+    ///
+    /// let diagnostic = Diagnostic::new(Span::new(2, 0, 3), "D=Q", "Invalid Instruction");
+    /// assert_eq!(
+    ///     diagnostic.to_json("prog.asm"),
+    ///     r#"{"file":"prog.asm","severity":"error","message":"Invalid Instruction","line":2,"column":1,"end_column":4,"snippet":"D=Q","notes":[],"lint":null,"code":null}"#
+    /// );
+    /// ```
+    pub fn to_json(&self, file: &str) -> String {
+        let notes: Vec<String> = self.notes
+            .iter()
+            .map(|note| {
+                let kind = match note.kind {
+                    NoteKind::Note => "note",
+                    NoteKind::Help => "help",
+                };
+                let location = match note.span {
+                    Some(span) => format!(",\"line\":{},\"column\":{}", span.line, span.column()),
+                    None => String::new(),
+                };
+                format!("{{\"kind\":\"{}\",\"message\":{}{}}}", kind, json::string(&note.message), location)
+            })
+            .collect();
+        format!(
+            "{{\"file\":{},\"severity\":\"{}\",\"message\":{},\"line\":{},\"column\":{},\"end_column\":{},\"snippet\":{},\"notes\":[{}],\"lint\":{},\"code\":{}}}",
+            json::string(file),
+            self.severity,
+            json::string(&self.message),
+            self.span.line,
+            self.span.column(),
+            self.span.end + 1,
+            json::string(&self.text),
+            notes.join(","),
+            self.lint.as_deref().map_or("null".to_string(), json::string),
+            self.code.as_deref().map_or("null".to_string(), json::string)
+        )
+    }
+
+    /// Format the diagnostic for a terminal, quoting the offending line of `source` and underlining the problem.
+    ///
+    /// Notes pointing at a location quote their line as well, every other note and help message follows at the end.
+    /// Diagnostics without a location, i.e. on line 0, or pointing past the end of `source` are printed without a quote.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::{ Diagnostic, Span };
+    ///
+    /// let source = "@1\n  D=D+D // double\n";
+    /// let diagnostic = Diagnostic::new(Span::new(2, 4, 7), "D=D+D", "Invalid Computation Mnemonic: D+D")
+    ///     .with_help("did you mean `D+A`?");
+    /// let expected = [
+    ///     "error: Invalid Computation Mnemonic: D+D",
+    ///     " --> prog.asm:2:5",
+    ///     "  |",
+    ///     "2 |   D=D+D // double",
+    ///     "  |     ^^^",
+    ///     "  = help: did you mean `D+A`?",
+    /// ];
+    /// assert_eq!(diagnostic.to_pretty_string("prog.asm", source), expected.join("\n"));
+    /// ```
+    pub fn to_pretty_string(&self, file: &str, source: &str) -> String {
+        // Like rustc, the code follows the severity, e.g. `error[E0002]`
+        let mut out = match &self.code {
+            Some(code) => format!("{}[{}]: {}", self.severity, code, self.message),
+            None => format!("{}: {}", self.severity, self.message),
+        };
+        if let Some(lint) = &self.lint {
+            out.push_str(&format!(" [{}]", lint));
+        }
+        let mut trailing = Vec::new();
+        // Line numbers of every quoted line are padded to the same width
+        let width = self.notes
+            .iter()
+            .filter_map(|note| note.span)
+            .chain(iter::once(self.span))
+            .map(|span| span.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        out.push_str(&excerpt(file, source, self.span, '^', width));
+        for note in &self.notes {
+            let kind = match note.kind {
+                NoteKind::Note => "note",
+                NoteKind::Help => "help",
+            };
+            match note.span {
+                Some(span) => {
+                    out.push_str(&format!("\n{}: {}", kind, note.message));
+                    out.push_str(&excerpt(file, source, span, '-', width));
+                }
+                None => trailing.push(format!("\n{:width$} = {}: {}", "", kind, note.message, width = width)),
+            }
+        }
+        out.extend(trailing);
+        out
+    }
+
+    /// Check if the diagnostic prevents the program from being assembled.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Errors keep the format used before severities existed, everything else is prefixed
+        if self.severity != Severity::Error {
+            let severity = self.severity.to_string();
+            write!(f, "{}{}: ", severity[..1].to_uppercase(), &severity[1..])?;
+        }
+        write!(f, "{} @ line [{}]: {}", self.message_with_lint(), self.span.line, self.text)?;
+        for note in &self.notes {
+            let kind = match note.kind {
+                NoteKind::Note => "note",
+                NoteKind::Help => "help",
+            };
+            write!(f, "\n  {}: {}", kind, note.message)?;
+            if let Some(span) = note.span {
+                write!(f, " @ line [{}]", span.line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Quote the line `span` points at with its location, underlining the span with `marker`
+// Returns an empty string if the line is not part of `source`
+fn excerpt(file: &str, source: &str, span: Span, marker: char, width: usize) -> String {
+    let Some(line) = span.line.checked_sub(1).and_then(|index| source.lines().nth(index)) else {
+        return String::new();
+    };
+    let start = span.start.min(line.len());
+    let end = span.end.clamp(start + 1, line.len().max(start + 1));
+    // Tabs are kept in the padding so the underline lines up however wide the terminal renders them
+    let padding: String = line
+        .get(..start)
+        .unwrap_or_default()
+        .chars()
+        .map(|char| if char == '\t' { '\t' } else { ' ' })
+        .collect();
+    let underline: String = iter::repeat_n(marker, line.get(start..end).map_or(1, |text| text.chars().count().max(1))).collect();
+    format!(
+        "\n{:width$}--> {}:{}:{}\n{:width$} |\n{:>width$} | {}\n{:width$} | {}{}",
+        "",
+        file,
+        span.line,
+        span.column(),
+        "",
+        span.line,
+        line,
+        "",
+        padding,
+        underline,
+        width = width
+    )
+}
+
+/// Iterate over the diagnostics of a single severity.
+pub fn with_severity(diagnostics: &[Diagnostic], severity: Severity) -> impl Iterator<Item = &Diagnostic> {
+    diagnostics.iter().filter(move |diagnostic| diagnostic.severity == severity)
+}
+
+/// Count the errors and warnings among `diagnostics` in a short summary, e.g. `2 errors, 1 warning`.
+/// Notes are only counted if there are any, e.g. `0 errors, 0 warnings, 1 note`.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ Diagnostic, Span, diagnostic::summarize };
+///
+/// let diagnostics = vec![
+///     Diagnostic::new(Span::new(2, 0, 3), "D=Q", "Invalid Instruction"),
+///     Diagnostic::warning(Span::new(5, 0, 6), "(LOOP)", "Unused label"),
+/// ];
+/// assert_eq!(summarize(&diagnostics), "1 error, 1 warning");
+/// ```
+pub fn summarize(diagnostics: &[Diagnostic]) -> String {
+    let count = |severity: Severity, name: &str| {
+        let count = with_severity(diagnostics, severity).count();
+        format!("{} {}{}", count, name, if count == 1 { "" } else { "s" })
+    };
+    let summary = format!("{}, {}", count(Severity::Error, "error"), count(Severity::Warning, "warning"));
+    // Notes are only mentioned when there are any, as most runs do not ask for them
+    match with_severity(diagnostics, Severity::Note).next() {
+        Some(_) => format!("{}, {}", summary, count(Severity::Note, "note")),
+        None => summary,
+    }
+}
+
+// Where SARIF consumers can find out more about the tool
+const SARIF_INFORMATION_URI: &str = "https://github.com/Muu79/rhasm";
+
+/// Format `diagnostics` found in `file` as a [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html) log,
+/// as read by GitHub code scanning and other CI dashboards to annotate the offending lines.
+///
+/// The log holds a single run of rhasm, with one result per diagnostic.
+/// Results carry the code of their diagnostic as their `ruleId`, described by a rule named after the lint if it has one,
+/// notes pointing at a location become `relatedLocations`,
+/// and every other note and help message is appended to the result's message.
+/// Diagnostics without a location, i.e. on line 0, are reported without one.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ Diagnostic, Span, diagnostic::to_sarif };
+///
+/// let diagnostics = vec![Diagnostic::warning(Span::new(1, 0, 6), "(LOOP)", "Label LOOP is never used").with_lint("unused-label")];
+/// let sarif = to_sarif(&diagnostics, "prog.asm");
+///
+/// assert!(sarif.starts_with(r#"{"version":"2.1.0","#));
+/// assert!(sarif.contains(r#""rules":[{"id":"W0001","name":"unused-label","shortDescription":{"text":"label is never used"}}]"#));
+/// assert!(sarif.contains(r#""ruleId":"W0001","level":"warning","message":{"text":"Label LOOP is never used"}"#));
+/// assert!(sarif.contains(r#""region":{"startLine":1,"startColumn":1,"endColumn":7,"snippet":{"text":"(LOOP)"}}"#));
+/// ```
+pub fn to_sarif(diagnostics: &[Diagnostic], file: &str) -> String {
+    let mut rules: Vec<&str> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| diagnostic.code.as_deref())
+        .collect();
+    rules.sort_unstable();
+    rules.dedup();
+    let rules: Vec<String> = rules
+        .into_iter()
+        .map(|code| {
+            let mut rule = format!("{{\"id\":{}", json::string(code));
+            if let Some(entry) = error_codes::lookup(code) {
+                if let Some(lint) = entry.lint {
+                    rule.push_str(&format!(",\"name\":{}", json::string(lint)));
+                }
+                rule.push_str(&format!(",\"shortDescription\":{{\"text\":{}}}", json::string(entry.summary)));
+            }
+            rule.push('}');
+            rule
+        })
+        .collect();
+    let results: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| sarif_result(diagnostic, file))
+        .collect();
+    format!(
+        "{{\"version\":\"2.1.0\",\"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"rhasm\",\"version\":{},\"informationUri\":{},\"rules\":[{}]}}}},\"results\":[{}]}}]}}",
+        json::string(env!("CARGO_PKG_VERSION")),
+        json::string(SARIF_INFORMATION_URI),
+        rules.join(","),
+        results.join(",")
+    )
+}
+
+// A single SARIF result, the JSON object describing one diagnostic
+fn sarif_result(diagnostic: &Diagnostic, file: &str) -> String {
+    let level = match diagnostic.severity {
+        Severity::Note => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+    let mut message = diagnostic.message.clone();
+    let mut related = Vec::new();
+    for note in &diagnostic.notes {
+        let kind = match note.kind {
+            NoteKind::Note => "note",
+            NoteKind::Help => "help",
+        };
+        match note.span {
+            Some(span) if span.line > 0 => {
+                related.push(
+                    format!(
+                        "{{\"id\":{},\"physicalLocation\":{},\"message\":{{\"text\":{}}}}}",
+                        related.len(),
+                        sarif_location(file, span, None),
+                        json::string(&note.message)
+                    )
+                );
+            }
+            _ => message.push_str(&format!("\n{}: {}", kind, note.message)),
+        }
+    }
+    let mut out = String::from("{");
+    if let Some(code) = &diagnostic.code {
+        out.push_str(&format!("\"ruleId\":{},", json::string(code)));
+    }
+    out.push_str(&format!("\"level\":\"{}\",\"message\":{{\"text\":{}}}", level, json::string(&message)));
+    if diagnostic.span.line > 0 {
+        out.push_str(
+            &format!(
+                ",\"locations\":[{{\"physicalLocation\":{}}}]",
+                sarif_location(file, diagnostic.span, Some(&diagnostic.text))
+            )
+        );
+    }
+    if !related.is_empty() {
+        out.push_str(&format!(",\"relatedLocations\":[{}]", related.join(",")));
+    }
+    out.push('}');
+    out
+}
+
+// A SARIF physical location, columns are 1-based and the end column is exclusive
+fn sarif_location(file: &str, span: Span, snippet: Option<&str>) -> String {
+    let snippet = match snippet {
+        Some(text) => format!(",\"snippet\":{{\"text\":{}}}", json::string(text)),
+        None => String::new(),
+    };
+    format!(
+        "{{\"artifactLocation\":{{\"uri\":{}}},\"region\":{{\"startLine\":{},\"startColumn\":{},\"endColumn\":{}{}}}}}",
+        json::string(file),
+        span.line,
+        span.column(),
+        span.end + 1,
+        snippet
+    )
+}
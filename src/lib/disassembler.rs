@@ -1,27 +1,300 @@
 //! Module for disassembling Hack machine code into human readable instructions.
 
 use std::{
-    io::{ BufRead, BufReader, BufWriter, Error, Lines, Read, Write },
-    iter::{ Filter, FusedIterator, Peekable },
+    collections::{ HashMap, VecDeque },
+    fmt,
+    io::{ BufRead, BufReader, BufWriter, Error, Read, Seek, SeekFrom, Write },
+    iter::FusedIterator,
 };
-use crate::decode_instruction;
+use crate::{ annotations::Annotations, decode_instruction, fields::{ CFields, Jump }, OutputFormat, SymbolKind, SymbolTable };
+use crate::output_format::WordComments;
+use crate::lib::{ assembler::{ read_error, DATA_SEGMENT_END }, telemetry::{ self, Counters } };
+
+/// Enum to represent why the [`Disassembler`] could not produce the next instruction.
+#[derive(Debug)]
+pub enum DisassembleError {
+    /// Reading the input failed.
+    Io(Error),
+    /// A line of the input is not a valid encoded instruction.
+    Decode {
+        /// The 1-based line of the input the word was read from.
+        line: usize,
+        /// The offending line, without surrounding whitespace.
+        word: String,
+        /// Why the word could not be decoded.
+        message: String,
+    },
+}
+
+impl fmt::Display for DisassembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisassembleError::Io(error) => write!(f, "Error reading line: {}", error),
+            DisassembleError::Decode { line, word, message } => {
+                write!(f, "Error decoding instruction @ line [{}]: {}: {}", line, word, message)
+            }
+        }
+    }
+}
+
+impl From<DisassembleError> for Error {
+    fn from(error: DisassembleError) -> Self {
+        match error {
+            DisassembleError::Io(error) => error,
+            error => Error::new(std::io::ErrorKind::InvalidData, error),
+        }
+    }
+}
+
+impl std::error::Error for DisassembleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DisassembleError::Io(error) => Some(error),
+            DisassembleError::Decode { .. } => None,
+        }
+    }
+}
 
 /// Struct to disassemble a binary file into human readable instructions.
-/// The disassembler will not be able to recover labels or variables.
+/// Machine code holds no symbols, so every address is written as a number unless symbols are given with
+/// [`DisassemblerConfig::with_symbols`], e.g. read from a symbol file or guessed by [`reconstruct_labels`] and [`name_variables`].
 /// Uses the Hack instruction set.
+///
+/// Errors reading the input name the line that was being read. The methods writing a single instruction flush it before returning,
+/// so the output holds every instruction written before a failure. [`Disassembler::write_to_end`] and [`Disassembler::get_and_write_to_end`]
+/// only write once every remaining line is decoded, so they write nothing if any of them fails.
 pub struct Disassembler<'a, R: Read, W: Write> {
-    writer: Option<BufWriter<Box<&'a mut W>>>,
-    lines: Peekable<Filter<Lines<BufReader<&'a mut R>>, fn(&Result<String, Error>) -> bool>>,
+    writer: Option<BufWriter<&'a mut W>>,
+    reader: BufReader<&'a mut R>,
+    /// Holds the line currently being read.
+    /// When polling, an unterminated trailing line is kept here until the rest of it arrives.
+    line_buffer: String,
+    // The 1-based line of the last line handed out, lines read ahead by [`Disassembler::peek_next`] are not counted until then
+    line_number: usize,
+    // The number of complete lines read from the input so far, including the ones read ahead
+    lines_read: usize,
+    // Lines read ahead by [`Disassembler::peek_next`] with their line numbers, handed out before reading any further
+    peeked: VecDeque<(usize, Result<String, Error>)>,
+    // Byte offset and line number of every instruction in the input, built on the first random access
+    line_offsets: Option<Vec<(u64, usize)>>,
+    skip_invalid: bool,
+    missing_output: MissingOutput,
+    allow_comments: bool,
+    // Output written while buffering for a missing writer, see [`MissingOutput::Buffer`]
+    output_buffer: String,
+    // The number of instructions decoded so far, i.e. the ROM address of the next one
+    decoded: usize,
+    // Rendered as comment headers in the written output, see [`Disassembler::with_annotations`]
+    annotations: Annotations,
+    // The names of the labels at each ROM address, see [`DisassemblerConfig::with_symbols`]
+    labels: HashMap<u16, Vec<String>>,
+    // The kind and name of the first other symbol listed at each RAM address
+    data_symbols: HashMap<u16, (SymbolKind, String)>,
+    // Reassembling allocates variables in order of first use, so a variable is only named once every variable below it has been
+    next_variable: u16,
+    word_comments: WordComments,
+    output_format: Option<&'a dyn OutputFormat>,
+    // The machine words of the instructions handed out since the last call, kept for the output format until they are written
+    recent_words: Vec<u16>,
+    // Set once iterating over the disassembler ends, so it never resumes, see the [`Iterator`] impl
+    exhausted: bool,
+    counters: Counters,
 }
 
-/// Config used to create a new Disassembler instance.
+/// Struct to hold the arguments for the [`Disassembler`] constructor.
 /// Takes two generics (`R` and `W`) that implement the [`Read`] and [`Write`] traits.
 ///
-/// When the passed [`DisassemblerConfig::writer`] is [`None`]:
-/// * the disassembler will return an error when using functions that attempt to write to the output.
+/// Only the reader is required, every other option has a default and can be set with the `with_*` methods.
+/// The struct can not be constructed directly outside of this crate so that new options can be added without breaking existing code,
+/// use [`DisassemblerConfig::new`] or [`DisassemblerConfig::without_writer`] instead.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use std::io::Cursor;
+///
+/// let mut reader = Cursor::new("0000000100000000 // load\n1234\n// padding\n1110110000010000\n");
+/// let config = DisassemblerConfig::without_writer(&mut reader)
+///     .with_skip_invalid(true)
+///     .with_comments_allowed(true)
+///     .with_missing_output(MissingOutput::Buffer);
+/// let mut disassembler = Disassembler::new(config);
+///
+/// disassembler.write_to_end().unwrap();
+/// assert_eq!(disassembler.take_output(), "@256\nD=A\n");
+/// ```
+#[non_exhaustive]
 pub struct DisassemblerConfig<'a, R: Read, W: Write> {
+    /// The machine code to read from.
     pub reader: &'a mut R,
+    /// The destination the decoded instructions are written to.
+    /// When it is [`None`], the write methods behave as set by [`DisassemblerConfig::missing_output`].
     pub writer: Option<&'a mut W>,
+    /// Skip lines that can not be decoded instead of returning a [`DisassembleError`] for them, logging each one as a warning with the `log` crate.
+    /// [`Disassembler::try_next`] and [`Disassembler::try_iter`] return every failure regardless.
+    pub skip_invalid: bool,
+    /// What the write methods do when [`DisassemblerConfig::writer`] is [`None`].
+    pub missing_output: MissingOutput,
+    /// Ignore `//` comments in the input, see [`DisassemblerConfig::with_comments_allowed`].
+    pub allow_comments: bool,
+    /// The symbols A-Instructions are named after, see [`DisassemblerConfig::with_symbols`].
+    pub symbols: Vec<(SymbolKind, String, u16)>,
+    /// Whether the written output notes the address and machine word of every instruction, see [`WordComments`].
+    pub word_comments: WordComments,
+    /// If set, writes the decoded instructions instead of [`DisassemblerConfig::word_comments`], see [`DisassemblerConfig::with_output_format`].
+    pub output_format: Option<&'a dyn OutputFormat>,
+}
+
+impl<'a, R, W> DisassemblerConfig<'a, R, W> where R: Read, W: Write {
+    /// Create a config reading the machine code from `reader` and writing the decoded instructions to `writer`, with every other option at its default.
+    pub fn new(reader: &'a mut R, writer: &'a mut W) -> DisassemblerConfig<'a, R, W> {
+        DisassemblerConfig::with_optional_writer(reader, Some(writer))
+    }
+
+    /// Create a config reading the machine code from `reader` and writing the decoded instructions to `writer` if it is [`Some`].
+    pub fn with_optional_writer(reader: &'a mut R, writer: Option<&'a mut W>) -> DisassemblerConfig<'a, R, W> {
+        DisassemblerConfig {
+            reader,
+            writer,
+            skip_invalid: false,
+            missing_output: MissingOutput::Error,
+            allow_comments: false,
+            symbols: Vec::new(),
+            word_comments: WordComments::Off,
+            output_format: None,
+        }
+    }
+
+    /// Skip lines that can not be decoded instead of returning a [`DisassembleError`] for them, see [`DisassemblerConfig::skip_invalid`].
+    pub fn with_skip_invalid(mut self, skip_invalid: bool) -> Self {
+        self.skip_invalid = skip_invalid;
+        self
+    }
+
+    /// Set what the write methods do when there is no writer, see [`MissingOutput`].
+    pub fn with_missing_output(mut self, missing_output: MissingOutput) -> Self {
+        self.missing_output = missing_output;
+        self
+    }
+
+    /// Ignore everything from `//` to the end of a line, so hand-annotated machine code can be disassembled.
+    /// Lines holding only a comment are skipped like blank lines, which are always skipped.
+    pub fn with_comments_allowed(mut self, allow_comments: bool) -> Self {
+        self.allow_comments = allow_comments;
+        self
+    }
+
+    /// Name the addresses of A-Instructions after `symbols`, e.g. the ones listed by [`crate::symbol_table::parse_symbol_file`],
+    /// and write every label as `(NAME)` above the instruction at its ROM address.
+    ///
+    /// An address both a label and another symbol are named at refers to RAM if the next instruction accesses `M`, otherwise to ROM.
+    /// Labels are only written to the output, like annotations. Labels at the end of the program are only written by
+    /// [`Disassembler::write_to_end`] and [`Disassembler::get_and_write_to_end`], as the other methods can not tell the end apart.
+    /// With every label and variable of a program listed, the output reassembles to the same machine code.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use rhasm::symbol_table::parse_symbol_file;
+    /// use std::io::Cursor;
+    ///
+    /// let symbols = parse_symbol_file("label LOOP 0\nvariable i 16\nlabel END 4\n").unwrap();
+    /// let mut reader = Cursor::new("0000000000010000\n1111110111001000\n0000000000000000\n1110101010000111\n");
+    /// let config = DisassemblerConfig::without_writer(&mut reader)
+    ///     .with_symbols(symbols)
+    ///     .with_missing_output(MissingOutput::Buffer);
+    /// let mut disassembler = Disassembler::new(config);
+    ///
+    /// disassembler.write_to_end().unwrap();
+    /// assert_eq!(disassembler.take_output(), "(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n(END)\n");
+    /// ```
+    pub fn with_symbols(mut self, symbols: impl IntoIterator<Item = (SymbolKind, String, u16)>) -> Self {
+        self.symbols.extend(symbols);
+        self
+    }
+
+    /// Note the ROM address and machine word of every instruction in the written output, see [`WordComments`].
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use rhasm::output_format::WordComments;
+    ///
+    /// let mut reader = "0000000000000111\n1111110111010001\n".as_bytes();
+    /// let config = DisassemblerConfig::without_writer(&mut reader)
+    ///     .with_word_comments(WordComments::Inline)
+    ///     .with_missing_output(MissingOutput::Buffer);
+    /// let mut disassembler = Disassembler::new(config);
+    ///
+    /// disassembler.write_to_end().unwrap();
+    /// assert_eq!(
+    ///     disassembler.take_output(),
+    ///     "@7           // 0x0000 0000000000000111\nD=M+1;JGT    // 0x0001 1111110111010001\n"
+    /// );
+    /// ```
+    pub fn with_word_comments(mut self, word_comments: WordComments) -> Self {
+        self.word_comments = word_comments;
+        self
+    }
+
+    /// Write every decoded instruction to the output in `output_format`, taking precedence over [`DisassemblerConfig::with_word_comments`].
+    /// Annotation headers and labels are written above the instructions regardless.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::{ self, Write };
+    ///
+    /// // Writes every instruction with the ROM address in front, like a listing
+    /// struct Listing;
+    ///
+    /// impl OutputFormat for Listing {
+    ///     fn write_word(&self, out: &mut dyn Write, address: u16, word: u16) -> io::Result<()> {
+    ///         writeln!(out, "{:5} {:016b}", address, word)
+    ///     }
+    ///
+    ///     fn write_instruction(&self, out: &mut dyn Write, address: u16, _word: u16, instruction: &str) -> io::Result<()> {
+    ///         writeln!(out, "{:5} {}", address, instruction)
+    ///     }
+    /// }
+    ///
+    /// let mut reader = "0000000100000000\n1110110000010000\n".as_bytes();
+    /// let config = DisassemblerConfig::without_writer(&mut reader)
+    ///     .with_output_format(&Listing)
+    ///     .with_missing_output(MissingOutput::Buffer);
+    /// let mut disassembler = Disassembler::new(config);
+    ///
+    /// disassembler.write_to_end().unwrap();
+    /// assert_eq!(disassembler.take_output(), "    0 @256\n    1 D=A\n");
+    /// ```
+    pub fn with_output_format(mut self, output_format: &'a dyn OutputFormat) -> Self {
+        self.output_format = Some(output_format);
+        self
+    }
+}
+
+impl<'a, R> DisassemblerConfig<'a, R, std::io::Sink> where R: Read {
+    /// Create a config reading the machine code from `reader` without anywhere to write the decoded instructions,
+    /// for callers that only want the strings returned by [`Disassembler::get_next`] or the output kept by [`MissingOutput::Buffer`].
+    pub fn without_writer(reader: &'a mut R) -> DisassemblerConfig<'a, R, std::io::Sink> {
+        DisassemblerConfig::with_optional_writer(reader, None)
+    }
+}
+
+/// Enum to represent what the write methods of a [`Disassembler`] do when it has no writer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingOutput {
+    /// Return an error of kind [`std::io::ErrorKind::NotFound`], the default.
+    #[default]
+    Error,
+    /// Discard the output and report success.
+    Skip,
+    /// Keep the output in memory until it is taken with [`Disassembler::take_output`].
+    Buffer,
 }
 
 impl<'a, R, W> Disassembler<'a, R, W> where R: Read, W: Write {
@@ -39,58 +312,408 @@ impl<'a, R, W> Disassembler<'a, R, W> where R: Read, W: Write {
     /// Returns a new [`Disassembler`] instance. Calling any disassemble or write methods will advance the disassembler to the next instruction.
     /// The disassembler's methods will return [`None`] when it reaches the end of the input file.
     pub fn new(args: DisassemblerConfig<'a, R, W>) -> Disassembler<'a, R, W> {
-        let DisassemblerConfig { reader, writer } = args;
+        let DisassemblerConfig { reader, writer, skip_invalid, missing_output, allow_comments, symbols, word_comments, output_format } = args;
+        let mut labels: HashMap<u16, Vec<String>> = HashMap::new();
+        let mut data_symbols = HashMap::new();
+        for (kind, name, address) in symbols {
+            match kind {
+                SymbolKind::Label => labels.entry(address).or_default().push(name),
+                kind => {
+                    data_symbols.entry(address).or_insert((kind, name));
+                }
+            }
+        }
+
+
+        Disassembler {
+            writer: writer.map(BufWriter::new),
+            reader: BufReader::new(reader),
+            line_buffer: String::new(),
+            line_number: 0,
+            lines_read: 0,
+            peeked: VecDeque::new(),
+            line_offsets: None,
+            skip_invalid,
+            missing_output,
+            allow_comments,
+            output_buffer: String::new(),
+            decoded: 0,
+            annotations: Annotations::default(),
+            labels,
+            data_symbols,
+            next_variable: SymbolTable::new().next_free_address(),
+            word_comments,
+            output_format,
+            recent_words: Vec::new(),
+            exhausted: false,
+            counters: Counters::new(),
+        }
+    }
+
+    /// The work done by this disassembler so far, see [`crate::telemetry`].
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
 
-        let filter: fn(&Result<String, Error>) -> bool = |line: &Result<String, Error>| {
-            line.is_ok() && !line.as_ref().unwrap().is_empty()
+    // Count an instruction returned to the caller, or the reason none could be
+    fn count<T>(&mut self, result: &Result<T, DisassembleError>) {
+        match result {
+            Ok(_) => {
+                self.decoded += 1;
+                self.counters.instructions_decoded += 1;
+            }
+            Err(DisassembleError::Io(_)) => self.counters.record_error_kind("io"),
+            Err(DisassembleError::Decode { .. }) => self.counters.record_error_kind("decode"),
+        }
+    }
+
+    /// Write the name and description of every region in `annotations` as comments above its first instruction.
+    /// Only the written output is annotated, the instructions returned by the disassembler are left as they are.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use rhasm::annotations::Annotations;
+    /// use std::io::Cursor;
+    ///
+    /// let annotations = Annotations::parse("1 main_loop Wait forever\n").unwrap();
+    /// let mut reader = Cursor::new("1110110000010000\n0000000000000001\n1110101010000111\n");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader).with_missing_output(MissingOutput::Buffer)).with_annotations(annotations);
+    ///
+    /// disassembler.write_to_end().unwrap();
+    /// assert_eq!(disassembler.take_output(), "D=A\n// ==== main_loop (ROM[1]) ====\n// Wait forever\n@1\n0;JMP\n");
+    /// ```
+    pub fn with_annotations(mut self, annotations: Annotations) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
+    /// Hand out the next non-empty line, trimmed of surrounding whitespace, starting with the lines read ahead by [`Disassembler::peek_next`].
+    ///
+    /// When `complete_only` is `true` a trailing line without a newline is treated as still being written,
+    /// it is kept in [`Disassembler::line_buffer`] and [`None`] is returned until the line is terminated.
+    fn read_next_line(&mut self, complete_only: bool) -> Option<Result<String, Error>> {
+        let (line_number, line) = match self.peeked.pop_front() {
+            Some(peeked) => peeked,
+            None => self.read_input_line(complete_only)?,
         };
+        self.line_number = line_number;
+        Some(line)
+    }
+
+    // Read the next non-empty line from the input along with its line number, see [`Disassembler::read_next_line`]
+    fn read_input_line(&mut self, complete_only: bool) -> Option<(usize, Result<String, Error>)> {
+        loop {
+            match self.reader.read_line(&mut self.line_buffer) {
+                Ok(0) => {
+                    // End of input, anything left in the buffer is an unterminated last line
+                    let line = self.line_content(&self.line_buffer).to_owned();
+                    if complete_only || line.is_empty() {
+                        return None;
+                    }
+                    self.line_buffer.clear();
+                    self.lines_read += 1;
+                    return Some((self.lines_read, Ok(line)));
+                }
+                Ok(_) => {
+                    // read_line only stops short of a newline at the end of the input
+                    if !self.line_buffer.ends_with('\n') {
+                        continue;
+                    }
+                    let line = self.line_content(&self.line_buffer).to_owned();
+                    self.line_buffer.clear();
+                    self.lines_read += 1;
+                    if !line.is_empty() {
+                        return Some((self.lines_read, Ok(line)));
+                    }
+                }
+                Err(err) => {
+                    return Some((self.lines_read + 1, Err(read_error(err, self.lines_read + 1))));
+                }
+            }
+        }
+    }
 
-        let lines: Peekable<
-            Filter<Lines<BufReader<&mut R>>, fn(&Result<String, Error>) -> bool>
-        > = BufReader::new(reader).lines().filter(filter).peekable();
+    // The instruction on a line read from the input, without surrounding whitespace or a comment if they are allowed
+    fn line_content<'l>(&self, line: &'l str) -> &'l str {
+        match line.find("//") {
+            Some(index) if self.allow_comments => line[..index].trim(),
+            _ => line.trim(),
+        }
+    }
 
-        let writer = match writer {
-            Some(file) => Some(BufWriter::new(Box::new(file))),
-            None => None,
+    /// Decode a line read from the input, naming the line if it can not be read or decoded.
+    fn decode_line(&self, line: Result<String, Error>) -> Result<String, DisassembleError> {
+        decode_numbered_line(self.line_number, line)
+    }
+
+    /// Decode the next line, skipping lines that can not be decoded if [`DisassemblerConfig::skip_invalid`] is set.
+    fn next_instruction(&mut self, complete_only: bool) -> Result<Option<String>, DisassembleError> {
+        while let Some(line) = self.read_next_line(complete_only) {
+            let word = line_word(&line);
+            match self.decode_line(line) {
+                Err(err @ DisassembleError::Decode { .. }) if self.skip_invalid => {
+                    log::warn!("Skipped invalid line: {}", err);
+                    continue;
+                }
+                result => {
+                    self.count(&result);
+                    let instruction = result?;
+                    self.recent_words.extend(word);
+                    let next_word = self.needs_next_word(&instruction).then(|| self.peek_word(complete_only)).flatten();
+                    return Ok(Some(self.name_address(instruction, next_word)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // Check if naming the address loaded by `instruction` depends on whether the next instruction uses it as a RAM address
+    fn needs_next_word(&self, instruction: &str) -> bool {
+        a_instruction_address(instruction).is_some_and(|address| self.data_symbols.contains_key(&address))
+    }
+
+    // Name the address loaded by an A-Instruction handed out in order, keeping track of the variables named so far
+    fn name_address(&mut self, instruction: String, next_word: Option<u16>) -> String {
+        let Some(address) = a_instruction_address(&instruction) else {
+            return instruction;
         };
+        let Some((kind, name)) = self.symbol_at(address, next_word, true) else {
+            return instruction;
+        };
+        let named = format!("@{}", name);
+        if kind == SymbolKind::Variable && address == self.next_variable {
+            self.next_variable += 1;
+        }
+        named
+    }
 
-        Disassembler {
-            writer,
-            lines,
+    // Replace the address loaded by an A-Instruction by the symbol named at it, see [`Disassembler::symbol_at`]
+    fn render_address(&self, instruction: String, next_word: Option<u16>, in_order: bool) -> String {
+        match a_instruction_address(&instruction).and_then(|address| self.symbol_at(address, next_word, in_order)) {
+            Some((_, name)) => format!("@{}", name),
+            None => instruction,
+        }
+    }
+
+    // The symbol an A-Instruction loading `address` is named after, the word of the next instruction tells RAM from ROM addresses.
+    // Variables are only named where the next instruction accesses `M`, as plain constants would otherwise turn into new variables,
+    // and if `in_order` only in the order reassembling allocates them.
+    fn symbol_at(&self, address: u16, next_word: Option<u16>, in_order: bool) -> Option<(SymbolKind, &str)> {
+        let accesses_m = next_word
+            .and_then(CFields::decode)
+            .is_some_and(|fields| fields.comp.reads_m() || fields.dest.writes_m());
+        let label = self.labels.get(&address).and_then(|names| names.first()).map(|name| (SymbolKind::Label, name.as_str()));
+        match self.data_symbols.get(&address) {
+            Some((SymbolKind::Variable, name)) if accesses_m => {
+                (!in_order || address <= self.next_variable).then_some((SymbolKind::Variable, name.as_str()))
+            }
+            Some((SymbolKind::Variable, _)) => label,
+            Some((kind, name)) if accesses_m || label.is_none() => Some((*kind, name.as_str())),
+            _ => label,
+        }
+    }
+
+    // The word on the next line of the input without advancing the disassembler, if it is a machine word
+    fn peek_word(&mut self, complete_only: bool) -> Option<u16> {
+        if self.peeked.is_empty() {
+            let line = self.read_input_line(complete_only)?;
+            self.peeked.push_back(line);
+        }
+        match self.peeked.front() {
+            Some((_, Ok(line))) => u16::from_str_radix(line, 2).ok(),
+            _ => None,
         }
     }
 
-    /// Check if [`Disassembler::lines`] has more instructions to disassemble.
-    fn has_next(&mut self) -> bool {
-        self.lines.peek().is_some()
+    /// Disassemble and return the next instruction, advancing the disassembler.
+    /// Unlike [`Disassembler::get_next`], a line that can not be read or decoded is returned as an error rather than ending the output.
+    ///
+    /// ### Returns
+    ///
+    /// * Returns [`None`] once the end of the input is reached.
+    /// * Returns a [`DisassembleError`] naming the line if it can not be read or decoded, the disassembler moves on to the next line.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use rhasm::disassembler::DisassembleError;
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new("0000000100000000\n1234\n1110110000010000\n");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader));
+    ///
+    /// assert_eq!(disassembler.try_next().unwrap().unwrap(), "@256");
+    /// let Some(Err(DisassembleError::Decode { line, word, .. })) = disassembler.try_next() else {
+    ///     panic!("expected the second line to be rejected");
+    /// };
+    /// assert_eq!((line, word.as_str()), (2, "1234"));
+    /// assert_eq!(disassembler.try_next().unwrap().unwrap(), "D=A");
+    /// assert!(disassembler.try_next().is_none());
+    /// ```
+    pub fn try_next(&mut self) -> Option<Result<String, DisassembleError>> {
+        self.recent_words.clear();
+        let line = self.read_next_line(false)?;
+        let word = line_word(&line);
+        let decoded = self.decode_line(line);
+        self.count(&decoded);
+        Some(decoded.map(|instruction| {
+            self.recent_words.extend(word);
+            let next_word = self.needs_next_word(&instruction).then(|| self.peek_word(false)).flatten();
+            self.name_address(instruction, next_word)
+        }))
+    }
+
+    /// Iterate over the remaining instructions as [`Result`]s, see [`Disassembler::try_next`].
+    ///
+    /// Iterating over the [`Disassembler`] itself stops at the first line that can not be decoded,
+    /// this adaptor tells the end of the input apart from a malformed line.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new("0000000100000000\n1234\n1110110000010000\n");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader));
+    ///
+    /// let results: Vec<bool> = disassembler.try_iter().map(|result| result.is_ok()).collect();
+    /// assert_eq!(results, vec![true, false, true]);
+    /// ```
+    pub fn try_iter(&mut self) -> TryIter<'_, 'a, R, W> {
+        TryIter { disassembler: self, exhausted: false }
     }
 
     /// Disassemble and return the next instruction, advancing the disassembler.
     ///
     /// Returns [`None`] if there are no more instructions to disassemble.
-    pub fn get_next(&mut self) -> Option<String> {
-        let out: Option<String> = {
-            if !self.has_next() {
-                return None;
-            }
-            // we can unwrap here because of the peekable check in has_next() i.e. line will always match Some(T)
-            let line = self.lines.next().unwrap();
-            // Check if reading the line is an error
-            if let Err(err) = line {
-                eprintln!("Error reading line: {}", err);
-                None
-            } else {
-                let instruction = match decode_instruction(line.unwrap().trim()) {
-                    Ok(decoded) => decoded,
-                    Err(err) => {
-                        eprintln!("Error decoding instruction: {}", err);
-                        return None;
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`DisassembleError::Decode`] naming the line and word if the next line can not be decoded,
+    ///   unless [`DisassemblerConfig::skip_invalid`] is set, in which case such lines are skipped.
+    /// * Returns [`DisassembleError::Io`] if reading the input fails.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use rhasm::disassembler::DisassembleError;
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new("1234\n0000000100000000\n");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader));
+    /// assert!(matches!(disassembler.get_next(), Err(DisassembleError::Decode { line: 1, .. })));
+    ///
+    /// let mut reader = Cursor::new("1234\n0000000100000000\n");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader).with_skip_invalid(true));
+    /// assert_eq!(disassembler.get_next().unwrap(), Some("@256".to_string()));
+    /// ```
+    pub fn get_next(&mut self) -> Result<Option<String>, DisassembleError> {
+        self.recent_words.clear();
+        self.next_instruction(false)
+    }
+
+    /// Disassemble and return the next instruction without advancing the disassembler,
+    /// the next call to [`Disassembler::get_next`] or any other method advancing it returns the same instruction.
+    ///
+    /// Lines [`Disassembler::get_next`] would skip with [`DisassemblerConfig::skip_invalid`] set are skipped here as well,
+    /// though [`Disassembler::try_next`] still returns them afterwards. Peeking is not counted in [`Disassembler::counters`].
+    ///
+    /// ### Errors
+    ///
+    /// * Returns the same errors as [`Disassembler::get_next`], the failing line stays next in line.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new("0000000100000000\n1110110000010000\n");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader));
+    ///
+    /// assert_eq!(disassembler.peek_next().unwrap(), Some("@256".to_string()));
+    /// assert_eq!(disassembler.peek_next().unwrap(), Some("@256".to_string()));
+    /// assert_eq!(disassembler.get_next().unwrap(), Some("@256".to_string()));
+    /// assert_eq!(disassembler.peek_next().unwrap(), Some("D=A".to_string()));
+    /// assert_eq!(disassembler.counters().instructions_decoded, 1);
+    /// ```
+    pub fn peek_next(&mut self) -> Result<Option<String>, DisassembleError> {
+        let mut index = 0;
+        loop {
+            if index == self.peeked.len() {
+                match self.read_input_line(false) {
+                    Some(peeked) => self.peeked.push_back(peeked),
+                    None => {
+                        return Ok(None);
                     }
-                };
-                Some(instruction)
+                }
             }
-        };
-        out
+            let (line_number, line) = &self.peeked[index];
+            // The line stays queued, so it is decoded from a copy
+            let line = match line {
+                Ok(line) => Ok(line.clone()),
+                Err(err) => Err(Error::new(err.kind(), err.to_string())),
+            };
+            match decode_numbered_line(*line_number, line) {
+                Err(DisassembleError::Decode { .. }) if self.skip_invalid => {
+                    index += 1;
+                }
+                result => {
+                    let instruction = result?;
+                    let next_word = match self.needs_next_word(&instruction) {
+                        true => self.word_after_peeked(index),
+                        false => None,
+                    };
+                    return Ok(Some(self.render_address(instruction, next_word, true)));
+                }
+            }
+        }
+    }
+
+    // The word on the line after the `index`-th line read ahead, reading it ahead as well
+    fn word_after_peeked(&mut self, index: usize) -> Option<u16> {
+        if index + 1 == self.peeked.len() {
+            let line = self.read_input_line(false)?;
+            self.peeked.push_back(line);
+        }
+        match &self.peeked[index + 1] {
+            (_, Ok(line)) => u16::from_str_radix(line, 2).ok(),
+            _ => None,
+        }
+    }
+
+    /// Disassemble and return the next fully written instruction, without treating the end of the input as final.
+    ///
+    /// This is intended for inputs that are still growing, such as a `.hack` file being produced by another process.
+    /// A trailing line that has not been terminated by a newline yet is held back until the rest of it can be read.
+    ///
+    /// Returns [`None`] if no complete instruction is available right now, calling it again later may yield more instructions.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns the same errors as [`Disassembler::get_next`].
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new("0000000100000000\n111011000001");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader));
+    ///
+    /// assert_eq!(disassembler.poll_next().unwrap(), Some("@256".to_string()));
+    /// // The second instruction has not been fully written yet
+    /// assert_eq!(disassembler.poll_next().unwrap(), None);
+    /// ```
+    pub fn poll_next(&mut self) -> Result<Option<String>, DisassembleError> {
+        self.recent_words.clear();
+        self.next_instruction(true)
     }
 
     /// Disassemble and return all remaining instructions, advancing the disassembler to the end.
@@ -99,22 +722,20 @@ impl<'a, R, W> Disassembler<'a, R, W> where R: Read, W: Write {
     ///
     /// * Returns a [`Option`] wrapping all remaining instructions if there are any.
     /// * If there are no instructions to disassemble, will return [`None`].
-    pub fn get_to_end(&mut self) -> Option<String> {
+    ///
+    /// ### Errors
+    ///
+    /// * Returns the first error [`Disassembler::get_next`] would return, the instructions decoded before it are discarded.
+    pub fn get_to_end(&mut self) -> Result<Option<String>, DisassembleError> {
+        self.recent_words.clear();
         let mut buffer = String::new();
-        while let Some(line) = self.lines.next() {
-            let instruction = match decode_instruction(line.unwrap().trim()) {
-                Ok(decoded) => decoded,
-                Err(err) => {
-                    eprintln!("Error decoding instruction: {}", err);
-                    continue;
-                }
-            };
+        while let Some(instruction) = self.next_instruction(false)? {
             buffer.push_str(&instruction);
             buffer.push('\n');
         }
         match buffer.is_empty() {
-            true => None,
-            false => Some(buffer),
+            true => Ok(None),
+            false => Ok(Some(buffer)),
         }
     }
 
@@ -125,13 +746,11 @@ impl<'a, R, W> Disassembler<'a, R, W> where R: Read, W: Write {
     /// * Returns an error if there are issues writing to the output file.
     /// * Returns an error if there are no more instructions to disassemble.
     /// * Returns an error if the writer passed in [`DisassemblerConfig::writer`] is [`None`].
+    /// * Returns an error of kind [`std::io::ErrorKind::InvalidData`] wrapping a [`DisassembleError`] if the next line can not be decoded.
     pub fn write_next(&mut self) -> Result<(), Error> {
-        let out = self.get_next();
-        if out.is_some() {
-            self.write_to_output(out.as_ref().unwrap())?;
-            return Ok(());
-        } else {
-            return Err(Error::new(std::io::ErrorKind::Other, "No more lines to disassemble"));
+        match self.get_next()? {
+            Some(out) => self.write_to_output(&out, false),
+            None => Err(Error::other("No more lines to disassemble")),
         }
     }
 
@@ -143,14 +762,11 @@ impl<'a, R, W> Disassembler<'a, R, W> where R: Read, W: Write {
     ///
     /// * Returns an error if there are issues writing to the output file.
     /// * Returns an error if there are no more instructions to disassemble.
+    /// * Returns an error of kind [`std::io::ErrorKind::InvalidData`] wrapping a [`DisassembleError`] if a line can not be decoded.
     pub fn write_to_end(&mut self) -> Result<(), Error> {
-        let out = self.get_to_end();
-        if out.is_some() {
-            let out = out.unwrap();
-            self.write_to_output(out.as_ref())?;
-            return Ok(());
-        } else {
-            return Err(Error::new(std::io::ErrorKind::Other, "No more lines to disassemble"));
+        match self.get_to_end()? {
+            Some(out) => self.write_to_output(&out, true),
+            None => Err(Error::other("No more lines to disassemble")),
         }
     }
 
@@ -165,14 +781,13 @@ impl<'a, R, W> Disassembler<'a, R, W> where R: Read, W: Write {
     ///
     /// * Returns an error if the reference passed by [`DisassemblerConfig::writer`] is [`None`]
     /// * Returns an error if there are issues writing to the output.
+    /// * Returns an error of kind [`std::io::ErrorKind::InvalidData`] wrapping a [`DisassembleError`] if a line can not be decoded.
     pub fn get_and_write_next(&mut self) -> Result<Option<String>, Error> {
-        let out = self.get_next();
+        let out = self.get_next()?;
         if let Some(instruction) = &out {
-            self.write_to_output(instruction)?;
-            return Ok(out);
-        } else {
-            return Ok(None);
+            self.write_to_output(instruction, false)?;
         }
+        Ok(out)
     }
 
     /// Disassemble, write and return all remaining instructions.
@@ -186,38 +801,463 @@ impl<'a, R, W> Disassembler<'a, R, W> where R: Read, W: Write {
     ///
     /// * Returns an error if the reference passed by [`DisassemblerConfig::writer`] is [`None`] 
     /// * Returns an error if there are issues writing to the output.
+    /// * Returns an error of kind [`std::io::ErrorKind::InvalidData`] wrapping a [`DisassembleError`] if a line can not be decoded.
     pub fn get_and_write_to_end(&mut self) -> Result<Option<String>, Error> {
-        let out = self.get_to_end();
-        if out.is_some() {
-            let out = out.unwrap();
-            self.write_to_output(out.as_ref())?;
-            return Ok(Some(out));
-        } else {
+        let out = self.get_to_end()?;
+        if let Some(instructions) = &out {
+            self.write_to_output(instructions, true)?;
+        }
+        Ok(out)
+    }
+
+    /// Poll, write and return the next fully written instruction.
+    /// * Behaves like [`Disassembler::poll_next`] but also writes to [`DisassemblerConfig::writer`].
+    /// ### Returns
+    ///
+    /// * Returns a [`Result<Option>`] wrapping the next instruction if a complete one is available.
+    /// * If no complete instruction is available yet, the result will wrap a [`None`].
+    ///
+    /// ### Errors
+    ///
+    /// * Returns an error if the reference passed by [`DisassemblerConfig::writer`] is [`None`]
+    /// * Returns an error if there are issues writing to the output.
+    /// * Returns an error of kind [`std::io::ErrorKind::InvalidData`] wrapping a [`DisassembleError`] if a line can not be decoded.
+    pub fn poll_and_write_next(&mut self) -> Result<Option<String>, Error> {
+        let out = self.poll_next()?;
+        if let Some(instruction) = &out {
+            self.write_to_output(instruction, false)?;
+        }
+        Ok(out)
+    }
+
+    /// Take everything written while buffering for a missing writer, leaving the buffer empty.
+    ///
+    /// Only [`MissingOutput::Buffer`] fills the buffer, otherwise an empty string is returned.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new("0000000100000000\n1110110000010000\n");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader).with_missing_output(MissingOutput::Buffer));
+    ///
+    /// disassembler.write_to_end().unwrap();
+    /// assert_eq!(disassembler.take_output(), "@256\nD=A\n");
+    /// assert_eq!(disassembler.take_output(), "");
+    /// ```
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output_buffer)
+    }
+
+    // Write just decoded instructions, `at_end` if they are the last ones so labels after them are written as well
+    fn write_to_output(&mut self, contents: &str, at_end: bool) -> Result<(), Error> {
+        let result = self.write_contents(contents, at_end);
+        self.recent_words.clear();
+        if result.is_err() {
+            self.counters.record_error_kind("io");
+        }
+        result
+    }
+
+    fn write_contents(&mut self, contents: &str, at_end: bool) -> Result<(), Error> {
+        let contents = self.render(contents.trim(), at_end)?;
+        let Some(writer) = self.writer.as_mut() else {
+            return match self.missing_output {
+                MissingOutput::Error => Err(Error::new(std::io::ErrorKind::NotFound, "No writeable output specified")),
+                MissingOutput::Skip => Ok(()),
+                MissingOutput::Buffer => {
+                    self.output_buffer.push_str(&String::from_utf8_lossy(&contents));
+                    Ok(())
+                }
+            };
+        };
+        writer.write_all(&contents)?;
+        writer.flush()?;
+        self.counters.bytes_written += contents.len() as u64;
+        Ok(())
+    }
+
+    // Write the just decoded instructions in `contents` in the output format, with the header of every region and the labels starting at one of them above it
+    fn render(&self, contents: &str, at_end: bool) -> Result<Vec<u8>, Error> {
+        let format = self.output_format.unwrap_or(&self.word_comments);
+        let count = contents.lines().count();
+        let first = self.decoded.saturating_sub(count);
+        let words = &self.recent_words[self.recent_words.len().saturating_sub(count)..];
+        let mut rendered = Vec::new();
+        for (offset, instruction) in contents.lines().enumerate() {
+            // Inputs too long for the ROM are still written, their addresses are capped
+            let address = u16::try_from(first + offset).unwrap_or(u16::MAX);
+            for header in self.annotations.starting_at(address).flat_map(|region| region.header()) {
+                writeln!(rendered, "{}", header)?;
+            }
+            for label in self.label_lines(address) {
+                writeln!(rendered, "{}", label)?;
+            }
+            let word = words.get(offset).copied().unwrap_or_default();
+            format.write_instruction(&mut rendered, address, word, instruction)?;
+        }
+        // Labels right after the last instruction mark the end of the program
+        match u16::try_from(self.decoded) {
+            Ok(address) if at_end => {
+                for label in self.label_lines(address) {
+                    writeln!(rendered, "{}", label)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(rendered)
+    }
+
+    // The labels at ROM `address` as they are written above its instruction
+    fn label_lines(&self, address: u16) -> impl Iterator<Item = String> + '_ {
+        self.labels.get(&address).into_iter().flatten().map(|name| format!("({})", name))
+    }
+}
+
+/// Random access to the instructions of a seekable input, e.g. a [`std::fs::File`] or an in-memory [`std::io::Cursor`].
+///
+/// On first use the whole input is scanned once, from the start of the stream, to find where each instruction starts, later changes to the input are not picked up.
+/// Random access leaves the position of the disassembler untouched, the instructions returned by [`Disassembler::get_next`] carry on as before.
+impl<'a, R, W> Disassembler<'a, R, W> where R: Read + Seek, W: Write {
+    /// Disassemble the instruction at ROM address `index`, i.e. the `index`-th non-empty line of the input counting from 0.
+    ///
+    /// ### Returns
+    ///
+    /// Returns [`None`] if the input holds no more than `index` instructions, see [`Disassembler::len`].
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`DisassembleError::Decode`] naming the line and word if the instruction can not be decoded.
+    /// * Returns [`DisassembleError::Io`] if reading or seeking the input fails.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new("0000000100000000\n\n1110110000010000\n0000000000000001\n");
+    /// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader));
+    ///
+    /// assert_eq!(disassembler.len().unwrap(), 3);
+    /// assert_eq!(disassembler.disassemble_at(2).unwrap(), Some("@1".to_string()));
+    /// assert_eq!(disassembler.disassemble_at(1).unwrap(), Some("D=A".to_string()));
+    /// assert_eq!(disassembler.disassemble_at(3).unwrap(), None);
+    /// // Streaming still starts from the beginning
+    /// assert_eq!(disassembler.get_next().unwrap(), Some("@256".to_string()));
+    /// ```
+    pub fn disassemble_at(&mut self, index: usize) -> Result<Option<String>, DisassembleError> {
+        let Some(&(offset, line_number)) = self.line_offsets()?.get(index) else {
             return Ok(None);
+        };
+        let line = self.read_line_at(offset).map_err(|err| read_error(err, line_number));
+        let result = decode_numbered_line(line_number, line);
+        match &result {
+            Ok(_) => self.counters.instructions_decoded += 1,
+            Err(DisassembleError::Io(_)) => self.counters.record_error_kind("io"),
+            Err(DisassembleError::Decode { .. }) => self.counters.record_error_kind("decode"),
+        }
+        let instruction = result?;
+        let next_word = match self.needs_next_word(&instruction) {
+            true => self.word_at(index + 1),
+            false => None,
+        };
+        // Random access is for viewing rather than reassembling, so variables are named wherever they are accessed
+        Ok(Some(self.render_address(instruction, next_word, false)))
+    }
+
+    // The machine word at ROM address `index`, if there is one and it can be read
+    fn word_at(&mut self, index: usize) -> Option<u16> {
+        let &(offset, _) = self.line_offsets().ok()?.get(index)?;
+        let line = self.read_line_at(offset).ok()?;
+        u16::from_str_radix(&line, 2).ok()
+    }
+
+    /// The number of instructions in the input, i.e. its non-empty lines, whether they have been disassembled yet or not.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`DisassembleError::Io`] if reading or seeking the input fails.
+    pub fn len(&mut self) -> Result<usize, DisassembleError> {
+        Ok(self.line_offsets()?.len())
+    }
+
+    /// Check if the input holds no instructions at all, see [`Disassembler::len`].
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`DisassembleError::Io`] if reading or seeking the input fails.
+    pub fn is_empty(&mut self) -> Result<bool, DisassembleError> {
+        Ok(self.len()? == 0)
+    }
+
+    // Find where each instruction starts on first use, returning to the current position afterwards
+    fn line_offsets(&mut self) -> Result<&[(u64, usize)], DisassembleError> {
+        if self.line_offsets.is_none() {
+            let offsets = self.scan_line_offsets().map_err(DisassembleError::Io)?;
+            self.line_offsets = Some(offsets);
         }
+        Ok(self.line_offsets.as_deref().unwrap_or_default())
     }
 
-    fn write_to_output(&mut self, contents: &str) -> Result<(), Error> {
-        if let Some(writer) = self.writer.as_mut() {
-            if let Err(error) = write!(writer, "{}\n", contents.trim()) {
-                eprintln!("Error writing to output: {}", error);
-                return Err(error);
+    fn scan_line_offsets(&mut self) -> Result<Vec<(u64, usize)>, Error> {
+        let position = self.reader.stream_position()?;
+        self.reader.seek(SeekFrom::Start(0))?;
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+        let mut line = String::new();
+        for line_number in 1.. {
+            line.clear();
+            let len = self.reader.read_line(&mut line).map_err(|err| read_error(err, line_number))?;
+            if len == 0 {
+                break;
+            }
+            if !self.line_content(&line).is_empty() {
+                offsets.push((offset, line_number));
             }
-            writer.flush().unwrap();
-            return Ok(());
-        } else {
-            return Err(Error::new(std::io::ErrorKind::NotFound, "No writeable output specified"));
+            offset += len as u64;
+        }
+        self.reader.seek(SeekFrom::Start(position))?;
+        Ok(offsets)
+    }
+
+    // Read the line starting at byte `offset`, returning to the current position afterwards
+    fn read_line_at(&mut self, offset: u64) -> Result<String, Error> {
+        let position = self.reader.stream_position()?;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut line = String::new();
+        let read = self.reader.read_line(&mut line);
+        self.reader.seek(SeekFrom::Start(position))?;
+        read?;
+        Ok(self.line_content(&line).to_owned())
+    }
+}
+
+// The machine word on a line read from the input, if it is one
+fn line_word(line: &Result<String, Error>) -> Option<u16> {
+    line.as_ref().ok().and_then(|line| u16::from_str_radix(line, 2).ok())
+}
+
+// The address loaded by a decoded A-Instruction
+fn a_instruction_address(instruction: &str) -> Option<u16> {
+    instruction.strip_prefix('@')?.parse().ok()
+}
+
+// Decode a line read from the input, naming its 1-based `line_number` if it can not be read or decoded
+fn decode_numbered_line(line_number: usize, line: Result<String, Error>) -> Result<String, DisassembleError> {
+    let line = line.map_err(DisassembleError::Io)?;
+    decode_instruction(&line).map_err(|err| DisassembleError::Decode {
+        line: line_number,
+        word: line,
+        message: err.to_string(),
+    })
+}
+
+/// Disassemble machine words into their instructions, one per line.
+///
+/// ### Errors
+///
+/// * Returns [`DisassembleError::Decode`] for the first word that is not a valid instruction, its `line` being the 1-based position of the word.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::disassemble;
+///
+/// assert_eq!(disassemble(&[256, 0b1110110000010000]).unwrap(), "@256\nD=A\n");
+/// assert!(disassemble(&[0b1111111111111111]).is_err());
+/// ```
+pub fn disassemble(words: &[u16]) -> Result<String, DisassembleError> {
+    let mut out = String::new();
+    for (index, word) in words.iter().enumerate() {
+        let word = format!("{:016b}", word);
+        let instruction = decode_instruction(&word).map_err(|err| DisassembleError::Decode {
+            line: index + 1,
+            message: err.to_string(),
+            word,
+        })?;
+        out.push_str(&instruction);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Find the jump targets of a program and name them `L0`, `L1`, and so on in order of their ROM address,
+/// to make its disassembly readable when no symbol file is at hand.
+///
+/// A jump target is the address loaded by an A-Instruction right before a C-Instruction that may jump.
+/// Targets outside the program are left out, as their labels could not be defined when reassembling.
+///
+/// ### Returns
+///
+/// Returns the labels as symbols to pass to [`DisassemblerConfig::with_symbols`], whose output reassembles to the same machine code.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use rhasm::disassembler::reconstruct_labels;
+///
+/// // @0, D;JGT, @1, 0;JMP
+/// let words = [0, 0b1110001100000001, 1, 0b1110101010000111];
+/// let labels = reconstruct_labels(&words);
+/// assert_eq!(labels, vec![(SymbolKind::Label, "L0".to_string(), 0), (SymbolKind::Label, "L1".to_string(), 1)]);
+///
+/// let text: String = words.iter().map(|word| format!("{:016b}\n", word)).collect();
+/// let mut reader = text.as_bytes();
+/// let config = DisassemblerConfig::without_writer(&mut reader)
+///     .with_symbols(labels)
+///     .with_missing_output(MissingOutput::Buffer);
+/// let mut disassembler = Disassembler::new(config);
+/// disassembler.write_to_end().unwrap();
+/// assert_eq!(disassembler.take_output(), "(L0)\n@L0\n(L1)\nD;JGT\n@L1\n0;JMP\n");
+/// ```
+pub fn reconstruct_labels(words: &[u16]) -> Vec<(SymbolKind, String, u16)> {
+    let mut targets: Vec<u16> = words
+        .windows(2)
+        .filter(|pair| pair[0] & 0x8000 == 0)
+        .filter(|pair| CFields::decode(pair[1]).is_some_and(|fields| fields.jump != Jump::Null))
+        .map(|pair| pair[0])
+        .filter(|target| usize::from(*target) < words.len())
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    targets
+        .into_iter()
+        .enumerate()
+        .map(|(index, address)| (SymbolKind::Label, format!("L{}", index), address))
+        .collect()
+}
+
+/// Name the variables of a program `var_16`, `var_17`, and so on after their RAM address,
+/// to make its disassembly readable when no symbol file is at hand.
+///
+/// An address between the first variable address, `16`, and the screen memory map is taken for a variable
+/// if every A-Instruction loading it is followed by a C-Instruction reading or writing `M` without jumping.
+/// Addresses also used as plain constants or jump targets are left as they are.
+///
+/// ### Returns
+///
+/// Returns the variables as symbols to pass to [`DisassemblerConfig::with_symbols`], whose output reassembles to the same machine code.
+/// As reassembling allocates variables in order of first use, a variable is only named in the output while that order is kept.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use rhasm::disassembler::name_variables;
+///
+/// // @16, M=1, @17, M=D, @18, D=A
+/// let words = [16, 0b1110111111001000, 17, 0b1110001100001000, 18, 0b1110110000010000];
+/// let variables = name_variables(&words);
+/// assert_eq!(variables, vec![(SymbolKind::Variable, "var_16".to_string(), 16), (SymbolKind::Variable, "var_17".to_string(), 17)]);
+///
+/// let text: String = words.iter().map(|word| format!("{:016b}\n", word)).collect();
+/// let mut reader = text.as_bytes();
+/// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader).with_symbols(variables));
+/// assert_eq!(disassembler.get_to_end().unwrap().unwrap(), "@var_16\nM=1\n@var_17\nM=D\n@18\nD=A\n");
+/// ```
+pub fn name_variables(words: &[u16]) -> Vec<(SymbolKind, String, u16)> {
+    let data_segment = SymbolTable::new().next_free_address()..DATA_SEGMENT_END;
+    // Whether every use of each address so far was as a data operand
+    let mut data_only: HashMap<u16, bool> = HashMap::new();
+    for (index, word) in words.iter().enumerate() {
+        if word & 0x8000 != 0 || !data_segment.contains(word) {
+            continue;
         }
+        let is_data = words.get(index + 1).and_then(|next| CFields::decode(*next)).is_some_and(|fields| {
+            (fields.comp.reads_m() || fields.dest.writes_m()) && fields.jump == Jump::Null
+        });
+        *data_only.entry(*word).or_insert(true) &= is_data;
+    }
+    let mut addresses: Vec<u16> = data_only.into_iter().filter(|(_, is_data)| *is_data).map(|(address, _)| address).collect();
+    addresses.sort_unstable();
+    addresses
+        .into_iter()
+        .map(|address| (SymbolKind::Variable, format!("var_{}", address), address))
+        .collect()
+}
+
+/// Disassemble the text of a `.hack` file, one instruction of 16 binary digits per line, into its instructions, one per line.
+/// A shorthand for a [`Disassembler`] without a writer, blank lines are skipped.
+///
+/// ### Errors
+///
+/// * Returns [`DisassembleError::Decode`] naming the first line that can not be decoded.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::disassemble_str;
+///
+/// assert_eq!(disassemble_str("0000000100000000\n\n1110110000010000\n").unwrap(), "@256\nD=A\n");
+/// assert!(disassemble_str("1234\n").is_err());
+/// ```
+pub fn disassemble_str(text: &str) -> Result<String, DisassembleError> {
+    let mut reader = text.as_bytes();
+    let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader));
+    Ok(disassembler.get_to_end()?.unwrap_or_default())
+}
+
+// Add the counters of the disassembler to the process-wide total, if enabled
+impl<'a, R, W> Drop for Disassembler<'a, R, W> where R: Read, W: Write {
+    fn drop(&mut self) {
+        telemetry::submit(&self.counters);
     }
 }
 
 /// Implement the [`Iterator`] trait for [`Disassembler`]. Disassembler will yield each instruction as an [`Option<String>`].
+/// Iteration ends at the end of the input or the first line that can not be decoded, and never resumes after either,
+/// use [`Disassembler::try_iter`] to get the failure instead.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+///
+/// let mut reader = "0000000100000000\n1234\n1110110000010000\n".as_bytes();
+/// let mut disassembler = Disassembler::new(DisassemblerConfig::without_writer(&mut reader));
+///
+/// assert_eq!(disassembler.next(), Some("@256".to_string()));
+/// assert_eq!(disassembler.next(), None);
+/// assert_eq!(disassembler.next(), None);
+/// ```
 impl<'a, R, W> Iterator for Disassembler<'a, R, W> where R: Read + 'a, W: Write + 'a {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.get_next()
+        if self.exhausted {
+            return None;
+        }
+        let next = self.get_next().ok().flatten();
+        self.exhausted = next.is_none();
+        next
     }
 }
 
 impl<'a, R, W> FusedIterator for Disassembler<'a, R, W> where R: Read + 'a, W: Write + 'a {}
+
+/// Iterator over the remaining instructions of a [`Disassembler`] that yields decode failures instead of stopping at them.
+/// Created by [`Disassembler::try_iter`].
+pub struct TryIter<'d, 'a, R: Read, W: Write> {
+    disassembler: &'d mut Disassembler<'a, R, W>,
+    // Set once the end of the input is reached, so the iterator never resumes if more input arrives
+    exhausted: bool,
+}
+
+impl<'d, 'a, R, W> Iterator for TryIter<'d, 'a, R, W> where R: Read + 'a, W: Write + 'a {
+    type Item = Result<String, DisassembleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+        let next = self.disassembler.try_next();
+        self.exhausted = next.is_none();
+        next
+    }
+}
+
+impl<'d, 'a, R, W> FusedIterator for TryIter<'d, 'a, R, W> where R: Read + 'a, W: Write + 'a {}
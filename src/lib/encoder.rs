@@ -1,108 +1,204 @@
-use std::collections::HashMap;
-use crate::Instruction;
+use alloc::{ format, string::{ String, ToString }, vec, vec::Vec };
+use crate::lib::{
+    decoder,
+    fields::{ CFields, Comp, Dest, Jump },
+    instruction::locate_error,
+    parser::{ invalid_line, parse_line_spanned, strip_comment, ParsedLine },
+};
+use crate::{ Instruction, RhasmError, Span, SymbolTable };
 
+// A-Instructions only have 15 bits for their value
+const MAX_ADDRESS: u16 = 1 << 15;
+
+/// Encode a parsed instruction into its 16 bit binary representation, see [`encode_instruction_u16`] for the machine word itself.
+///
+/// Symbols not yet in `symbol_table` are treated as new variables and allocated the table's next free address.
+///
+/// ### Errors
+///
+/// * Returns the same errors as [`encode_instruction_u16`].
 pub fn encode_instruction(
     instruction: &Instruction,
-    symbol_table: &mut HashMap<String, u16>,
-    cur_ram: &mut u16
-) -> String {
-    let mut encoded_instruction: Vec<char> = vec![];
-    match instruction {
-        Instruction::AInstruction(addr) => {
-            encoded_instruction.push('0');
-            let parsed_addr = 
-            if addr.chars().all(|char| char.is_digit(10)) {
-                let is_num = addr.parse::<u16>();
-                if let Ok(num) = is_num {
-                    num
-                } else {
-                    panic!("Invalid A-Instruction address or label: {}", addr);
-                }
-            } else {
-                *symbol_table.entry(addr.to_string()).or_insert_with(|| {
-                    *cur_ram += 1;
-                    *cur_ram - 1
-                })
-            };
-            let binary_addr = format!("{:015b}", parsed_addr);
-            encoded_instruction.extend(binary_addr.chars());
-        }
-        Instruction::CInstruction(dest_str, comp_str, jump_string) => {
-            encoded_instruction.extend("111".chars());
-            encoded_instruction.extend(get_comp_code(comp_str).chars());
-            encoded_instruction.extend(get_dest_code(dest_str).chars());
-            encoded_instruction.extend(get_jump_code(jump_string).chars());
+    symbol_table: &mut SymbolTable
+) -> Result<String, RhasmError> {
+    Ok(format!("{:016b}", encode_instruction_u16(instruction, symbol_table)?))
+}
+
+/// Encode a parsed instruction into its machine word.
+///
+/// Symbols not yet in `symbol_table` are treated as new variables and allocated the table's next free address.
+///
+/// ### Errors
+///
+/// * Returns [`RhasmError::AddressOutOfRange`] if an A-Instruction constant does not fit in 15 bits.
+/// * Returns [`RhasmError::SymbolOverflow`] if a new variable would not fit in 15 bits.
+/// * Returns [`RhasmError::InvalidDest`], [`RhasmError::InvalidComp`] or [`RhasmError::InvalidJump`] for unknown C-Instruction mnemonics.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ encode_instruction_u16, Instruction, SymbolTable };
+///
+/// let mut symbols = SymbolTable::new();
+/// let load = Instruction::AInstruction("counter".to_string());
+/// let jump = Instruction::CInstruction("".to_string(), "D".to_string(), "JGT".to_string());
+///
+/// assert_eq!(encode_instruction_u16(&load, &mut symbols).unwrap(), 16);
+/// assert_eq!(encode_instruction_u16(&jump, &mut symbols).unwrap(), 0b1110001100000001);
+/// ```
+pub fn encode_instruction_u16(
+    instruction: &Instruction,
+    symbol_table: &mut SymbolTable
+) -> Result<u16, RhasmError> {
+    encode_with(instruction, |symbol| {
+        match symbol_table.get(symbol) {
+            Some(address) => Ok(address),
+            None => symbol_table.allocate_variable(symbol),
         }
-    }
-    return encoded_instruction.iter().collect();
+    })
 }
 
-fn get_dest_code(mnemonic: &str) -> String {
-    let mut dest: [u8; 3] = [0; 3];
-    if mnemonic.contains("A") {
-        dest[0] = 1;
+/// Encode a single source line in the context of an existing symbol table, without changing the table.
+///
+/// Meant for live previews in editors, e.g. inlay hints showing the encoding of each line as it is typed.
+/// Symbols not yet in `symbol_table` are previewed at the address they would be allocated as a new variable.
+///
+/// ### Returns
+///
+/// Returns the encoded instruction, or [`None`] if the line is blank, only a comment, a label, or an alias.
+///
+/// ### Errors
+///
+/// * Returns [`RhasmError::Diagnostics`] with a single diagnostic on line 1 pointing at the offending part of the line,
+///   if it is not a valid instruction or can not be encoded, see [`encode_instruction`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ encoder::encode_line, RhasmError, SymbolTable };
+///
+/// let mut symbols = SymbolTable::new();
+/// symbols.define_label("LOOP", 4);
+///
+/// assert_eq!(encode_line("  @LOOP // jump target", &symbols).unwrap(), Some(4));
+/// assert_eq!(encode_line("@counter", &symbols).unwrap(), Some(16));
+/// assert_eq!(encode_line("D=M;JGT", &symbols).unwrap(), Some(0b1111110000010001));
+/// assert_eq!(encode_line("(LOOP)", &symbols).unwrap(), None);
+/// // The table is left untouched
+/// assert!(!symbols.contains("counter"));
+///
+/// // Likely typos come with a suggestion
+/// let Err(RhasmError::Diagnostics(diagnostics)) = encode_line("0;JMG", &symbols) else {
+///     panic!("expected the jump to be rejected");
+/// };
+/// assert_eq!(diagnostics[0].notes[0].message, "did you mean `JMP`?");
+/// ```
+pub fn encode_line(line: &str, symbol_table: &SymbolTable) -> Result<Option<u16>, RhasmError> {
+    let code = strip_comment(line);
+    if code.is_empty() {
+        return Ok(None);
     }
-    if mnemonic.contains("D") {
-        dest[1] = 1;
-    }
-    if mnemonic.contains("M") {
-        dest[2] = 1;
-    }
-    dest.iter()
-        .map(|x| format!("{}", x))
-        .collect()
+    let indent = line.len() - line.trim_start().len();
+    let span = Span::new(1, indent, indent + code.len());
+    let (instruction, spans) = match parse_line_spanned(code, span) {
+        Some((ParsedLine::Instruction(instruction), spans)) => (instruction, spans),
+        Some((ParsedLine::Label(_), _)) | Some((ParsedLine::Alias(_, _), _)) => {
+            return Ok(None);
+        }
+        None => {
+            return Err(RhasmError::Diagnostics(vec![invalid_line(code, span)]));
+        }
+    };
+    encode_with(&instruction, |symbol| {
+        match symbol_table.get(symbol) {
+            Some(address) => Ok(address),
+            None if symbol_table.next_free_address() < MAX_ADDRESS => Ok(symbol_table.next_free_address()),
+            None => Err(RhasmError::SymbolOverflow(symbol.to_string())),
+        }
+    })
+        .map(Some)
+        .map_err(|err| locate_error(err, &instruction, &spans))
 }
 
-fn get_jump_code(mnemonic: &str) -> String {
-    let out = match mnemonic {
-        "JGT" => "001",
-        "JEQ" => "010",
-        "JGE" => "011",
-        "JLT" => "100",
-        "JNE" => "101",
-        "JLE" => "110",
-        "JMP" => "111",
-        "" => "000",
+// Mnemonics further than this many edits from every valid one get no suggestion, as they are unlikely to be typos
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+// The valid mnemonic closest to the one rejected by `err`, if it is close enough to be a likely typo
+pub(crate) fn suggest_mnemonic(err: &RhasmError) -> Option<&'static str> {
+    let (mnemonic, candidates): (&str, Vec<&'static str>) = match err {
+        RhasmError::InvalidComp(mnemonic) => (mnemonic, decoder::comp_mnemonics().collect()),
+        RhasmError::InvalidJump(mnemonic) => (mnemonic, decoder::jump_mnemonics().collect()),
         _ => {
-            panic!("Invalid Jump Mnemonic: {}", mnemonic);
+            return None;
         }
     };
-    out.to_string()
+    // Commutative operations are only valid in one operand order, e.g. `D+A` but not `A+D`
+    if let Some(index) = mnemonic.find(['+', '&', '|']) {
+        let swapped = format!("{}{}{}", &mnemonic[index + 1..], &mnemonic[index..=index], &mnemonic[..index]);
+        if let Some(candidate) = candidates.iter().find(|candidate| **candidate == swapped) {
+            return Some(candidate);
+        }
+    }
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(mnemonic, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE && *distance < mnemonic.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
 }
 
-fn get_comp_code(mnemonic: &str) -> String {
-    let out = match mnemonic {
-        "0" => "0101010",
-        "1" => "0111111",
-        "-1" => "0111010",
-        "D" => "0001100",
-        "A" => "0110000",
-        "!D" => "0001101",
-        "!A" => "0110001",
-        "-D" => "0001111",
-        "-A" => "0110011",
-        "D+1" => "0011111",
-        "A+1" => "0110111",
-        "D-1" => "0001110",
-        "A-1" => "0110010",
-        "D+A" => "0000010",
-        "D-A" => "0010011",
-        "A-D" => "0000111",
-        "D&A" => "0000000",
-        "D|A" => "0010101",
-        "M" => "1110000",
-        "!M" => "1110001",
-        "-M" => "1110011",
-        "M+1" => "1110111",
-        "M-1" => "1110010",
-        "D+M" => "1000010",
-        "D-M" => "1010011",
-        "M-D" => "1000111",
-        "D&M" => "1000000",
-        "D|M" => "1010101",
-        _ => {
-            panic!("Invalid Computation Mnemonic: {}", mnemonic);
+// The Levenshtein distance between two strings, i.e. the number of single character insertions, deletions and substitutions between them
+fn edit_distance(from: &str, to: &str) -> usize {
+    let to: Vec<char> = to.chars().collect();
+    let mut previous: Vec<usize> = (0..=to.len()).collect();
+    for (i, from_char) in from.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, to_char) in to.iter().enumerate() {
+            let substitution = previous[j] + usize::from(from_char != *to_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
         }
-    };
-    out.to_string()
+        previous = current;
+    }
+    previous[to.len()]
+}
+
+// Reduce a decimal A-Instruction constant of any length to its lowest 15 bits
+#[cfg(feature = "std")]
+pub(crate) fn wrap_constant(digits: &str) -> u16 {
+    digits.bytes().fold(0, |value: u32, digit| (value * 10 + u32::from(digit - b'0')) % u32::from(MAX_ADDRESS)) as u16
+}
+
+// Check if an A-Instruction is a decimal constant too large for its 15 bits
+#[cfg(feature = "std")]
+pub(crate) fn is_out_of_range(addr: &str) -> bool {
+    addr.chars().all(|char| char.is_ascii_digit()) && !matches!(addr.parse::<u16>(), Ok(num) if num < MAX_ADDRESS)
+}
+
+// Encode an instruction into its 16 bit value, `resolve_symbol` provides the address of a symbolic A-Instruction
+fn encode_with<F>(instruction: &Instruction, resolve_symbol: F) -> Result<u16, RhasmError>
+    where F: FnOnce(&str) -> Result<u16, RhasmError>
+{
+    match instruction {
+        Instruction::AInstruction(addr) => {
+            if addr.chars().all(|char| char.is_ascii_digit()) {
+                match addr.parse::<u16>() {
+                    Ok(num) if num < MAX_ADDRESS => Ok(num),
+                    _ => Err(RhasmError::AddressOutOfRange(addr.to_string())),
+                }
+            } else {
+                resolve_symbol(addr)
+            }
+        }
+        Instruction::CInstruction(dest, comp, jump) => Ok(CFields::parse(dest, comp, jump)?.encode()),
+    }
+}
+
+// Check the mnemonics of a C-Instruction without encoding it, A-Instructions are always valid
+pub(crate) fn validate_mnemonics(instruction: &Instruction) -> Result<(), RhasmError> {
+    if let Instruction::CInstruction(dest, comp, jump) = instruction {
+        dest.parse::<Dest>()?;
+        comp.parse::<Comp>()?;
+        jump.parse::<Jump>()?;
+    }
+    Ok(())
 }
@@ -0,0 +1,107 @@
+//! Module containing the error type shared by the parsing and encoding pipeline.
+
+use alloc::{ string::{ String, ToString }, vec::Vec };
+use core::{ error::Error, fmt };
+#[cfg(feature = "std")]
+use std::io;
+use crate::Diagnostic;
+use crate::lib::error_codes;
+
+/// Enum to represent everything that can go wrong while assembling a program.
+#[derive(Debug)]
+pub enum RhasmError {
+    /// A source line is neither a valid instruction nor a label.
+    /// Contains the 1-based line number and the offending line without comments.
+    Parse {
+        line: usize,
+        text: String,
+    },
+    /// One or more lines of the source could not be parsed.
+    /// Contains every problem found in the first pass in source order, so they can all be fixed at once.
+    Diagnostics(Vec<Diagnostic>),
+    /// A C-Instruction uses an unknown destination mnemonic, e.g. one naming a register twice.
+    InvalidDest(String),
+    /// A C-Instruction uses an unknown computation mnemonic.
+    InvalidComp(String),
+    /// A C-Instruction uses an unknown jump mnemonic.
+    InvalidJump(String),
+    /// An A-Instruction constant does not fit in 15 bits.
+    AddressOutOfRange(String),
+    /// A symbol could not be given an address, e.g. because the program or its variables outgrew the address space.
+    SymbolOverflow(String),
+    /// A predefined symbol was about to be redefined, see [`crate::SymbolTable::define`].
+    PredefinedSymbol(String),
+    /// Reading the source or writing the output failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl RhasmError {
+    /// The stable code of the error from [`crate::error_codes`], if it is a problem with the source.
+    /// [`RhasmError::Diagnostics`] has none of its own, each of its diagnostics carries a code instead.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::RhasmError;
+    ///
+    /// assert_eq!(RhasmError::InvalidComp("D+D".to_string()).code(), Some("E0001"));
+    /// ```
+    pub fn code(&self) -> Option<&'static str> {
+        let code = match self {
+            RhasmError::Parse { .. } => error_codes::INVALID_INSTRUCTION,
+            RhasmError::InvalidDest(_) => error_codes::INVALID_DEST,
+            RhasmError::InvalidComp(_) => error_codes::INVALID_COMP,
+            RhasmError::InvalidJump(_) => error_codes::INVALID_JUMP,
+            RhasmError::AddressOutOfRange(_) => error_codes::CONSTANT_OUT_OF_RANGE,
+            RhasmError::SymbolOverflow(_) => error_codes::SYMBOL_OVERFLOW,
+            RhasmError::Diagnostics(_) | RhasmError::PredefinedSymbol(_) => {
+                return None;
+            }
+            #[cfg(feature = "std")]
+            RhasmError::Io(_) => {
+                return None;
+            }
+        };
+        Some(code)
+    }
+}
+
+impl fmt::Display for RhasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RhasmError::Parse { line, text } => write!(f, "Invalid Instruction @ line [{}]: {}", line, text),
+            RhasmError::Diagnostics(diagnostics) => {
+                let lines: Vec<String> = diagnostics.iter().map(Diagnostic::to_string).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            RhasmError::InvalidDest(mnemonic) => write!(f, "Invalid Destination Mnemonic: {}", mnemonic),
+            RhasmError::InvalidComp(mnemonic) => write!(f, "Invalid Computation Mnemonic: {}", mnemonic),
+            RhasmError::InvalidJump(mnemonic) => write!(f, "Invalid Jump Mnemonic: {}", mnemonic),
+            RhasmError::AddressOutOfRange(address) => {
+                write!(f, "A-Instruction constant {} does not fit in 15 bits", address)
+            }
+            RhasmError::SymbolOverflow(symbol) => write!(f, "No address left to assign to symbol {}", symbol),
+            RhasmError::PredefinedSymbol(symbol) => write!(f, "Predefined symbol {} can not be redefined", symbol),
+            #[cfg(feature = "std")]
+            RhasmError::Io(error) => write!(f, "I/O error: {}", error),
+        }
+    }
+}
+
+impl Error for RhasmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            RhasmError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for RhasmError {
+    fn from(error: io::Error) -> Self {
+        RhasmError::Io(error)
+    }
+}
@@ -0,0 +1,458 @@
+//! Module containing the catalog of stable codes given to every kind of diagnostic, with a longer explanation of each.
+//!
+//! Errors are numbered `E0001` onwards and warnings `W0001` onwards, a code is never reused for a different problem.
+//! The explanations are what `rhasm --explain <code>` prints.
+
+/// A single entry of the catalog.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErrorCode {
+    /// The stable code, e.g. `E0002`.
+    pub code: &'static str,
+    /// The name of the lint reporting the problem, for warnings whose level can be configured.
+    pub lint: Option<&'static str>,
+    /// A one line description of the problem.
+    pub summary: &'static str,
+    /// A longer description of the problem and how to fix it, with examples.
+    pub explanation: &'static str,
+}
+
+/// Every code in the catalog, errors first, each in numeric order.
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: "E0001",
+        lint: None,
+        summary: "invalid computation mnemonic",
+        explanation: "\
+The computation of a C-Instruction is not one of the 28 the Hack CPU supports.
+
+Erroneous code example:
+
+    D=D+D
+
+The ALU can only combine D with A or M, and only in the operand order listed by the
+Hack specification, e.g. `D+A` but not `A+D`. Rewrite the computation with a supported
+mnemonic, doubling D for example takes a copy of D in memory:
+
+    @temp
+    M=D
+    D=D+M
+",
+    },
+    ErrorCode {
+        code: "E0002",
+        lint: None,
+        summary: "duplicate label",
+        explanation: "\
+A label is defined more than once, so it is unclear which address it stands for.
+
+Erroneous code example:
+
+    (LOOP)
+    @LOOP
+    0;JMP
+    (LOOP)
+
+Rename one of the labels. Programs written for assemblers where the last definition wins
+can be assembled with `--allow-duplicate-labels`, reporting this as a warning instead.
+",
+    },
+    ErrorCode {
+        code: "E0003",
+        lint: None,
+        summary: "invalid destination mnemonic",
+        explanation: "\
+The destination of a C-Instruction names something other than the A, D and M registers,
+or names one of them twice.
+
+Erroneous code example:
+
+    DD=M
+    X=1
+
+Each register may appear at most once, in any order, e.g. `AM=M-1` or `MD=0`.
+",
+    },
+    ErrorCode {
+        code: "E0004",
+        lint: None,
+        summary: "invalid jump mnemonic",
+        explanation: "\
+The jump of a C-Instruction is not one of JGT, JEQ, JGE, JLT, JNE, JLE or JMP.
+
+Erroneous code example:
+
+    D;JMG
+
+Jump mnemonics are written in upper case, an unconditional jump is written `0;JMP`.
+",
+    },
+    ErrorCode {
+        code: "E0005",
+        lint: None,
+        summary: "invalid instruction",
+        explanation: "\
+A line is neither an A-Instruction, a C-Instruction, a label, nor an `.alias` declaration.
+
+Erroneous code example:
+
+    LOAD 5
+    (LOOP
+
+A-Instructions start with `@`, labels are enclosed in parentheses, and C-Instructions are
+written `dest=comp;jump` with the destination and jump being optional.
+",
+    },
+    ErrorCode {
+        code: "E0006",
+        lint: None,
+        summary: "A-Instruction without an address or symbol",
+        explanation: "\
+An `@` is not followed by anything to load into the A register.
+
+Erroneous code example:
+
+    @
+    D=A
+
+Write the constant or symbol to load right after the `@`, e.g. `@5` or `@counter`.
+",
+    },
+    ErrorCode {
+        code: "E0007",
+        lint: None,
+        summary: "whitespace after the `@` of an A-Instruction",
+        explanation: "\
+The constant or symbol of an A-Instruction is separated from the `@` by whitespace.
+
+Erroneous code example:
+
+    @ 12
+
+Write the operand right after the `@`, as in `@12`.
+",
+    },
+    ErrorCode {
+        code: "E0008",
+        lint: None,
+        summary: "negative A-Instruction constant",
+        explanation: "\
+An A-Instruction loads a negative constant, but A-Instructions only load constants from 0 to 32767.
+
+Erroneous code example:
+
+    @-5
+
+Load the positive value and negate it with the ALU instead:
+
+    @5
+    A=-A
+
+-1 can be produced directly by the ALU, e.g. with `A=-1` or `D=-1`.
+",
+    },
+    ErrorCode {
+        code: "E0009",
+        lint: None,
+        summary: "signed A-Instruction constant",
+        explanation: "\
+An A-Instruction constant is written with a `+` sign.
+
+Erroneous code example:
+
+    @+5
+
+Constants are always written without a sign, as in `@5`.
+",
+    },
+    ErrorCode {
+        code: "E0010",
+        lint: None,
+        summary: "malformed A-Instruction constant",
+        explanation: "\
+An A-Instruction operand starts with a digit but contains other characters, so it is
+neither a constant nor a symbol.
+
+Erroneous code example:
+
+    @007x
+
+Constants may only contain digits, and symbols can not start with a digit. Rename the
+symbol, e.g. to `@x007`.
+",
+    },
+    ErrorCode {
+        code: "E0011",
+        lint: None,
+        summary: "A-Instruction constant out of range",
+        explanation: "\
+An A-Instruction constant does not fit in the 15 bits an A-Instruction can load, i.e. it is larger than 32767.
+
+Erroneous code example:
+
+    @70000
+
+Larger values have to be computed, e.g. by adding smaller constants. Programs relying on the
+constant being cut down to 15 bits can be assembled with `--constant-overflow truncate` or `wrap`.
+",
+    },
+    ErrorCode {
+        code: "E0012",
+        lint: None,
+        summary: "no address left for a symbol",
+        explanation: "\
+A label or variable could not be given an address, because the program outgrew the 15 bit address space.
+
+Split the program up, or reuse variables instead of allocating new ones.
+",
+    },
+    ErrorCode {
+        code: "E0013",
+        lint: None,
+        summary: "program does not fit in ROM",
+        explanation: "\
+The program has more than the 32768 instructions the Hack ROM holds.
+
+The diagnostic points at the first instruction outside ROM. Shorten the program, e.g. by
+moving repeated code into subroutines.
+",
+    },
+    ErrorCode {
+        code: "E0014",
+        lint: None,
+        summary: "variable allocated past the data segment",
+        explanation: "\
+A variable would be allocated at RAM[16384] or above, which is the screen memory map, so
+writing the variable would draw on the screen.
+
+Reuse variables, or start allocating them at a lower address. Programs that expect this can
+be assembled with `--allow-variable-overflow`, reporting this as a warning instead.
+",
+    },
+    ErrorCode {
+        code: "E0015",
+        lint: None,
+        summary: "unresolved alias target",
+        explanation: "\
+An `.alias` declaration refers to something that is neither a label nor a predefined symbol.
+
+Erroneous code example:
+
+    .alias START BEGIN
+    @START
+    0;JMP
+
+Aliases can only stand for labels and predefined symbols, check the spelling of the target
+or define it as a label.
+",
+    },
+    ErrorCode {
+        code: "E0016",
+        lint: None,
+        summary: "source line too long",
+        explanation: "\
+A source line is longer than the limit set with `--max-line-length`, or by
+`AssemblerConfig::with_max_line_length` when rhasm is used as a library.
+
+Reading stops as soon as the limit is exceeded, so the line is never buffered in full.
+Hack assembly lines are short, so a line this long usually means the input is not an
+assembly file at all.
+",
+    },
+    ErrorCode {
+        code: "E0017",
+        lint: None,
+        summary: "source too large",
+        explanation: "\
+The source is larger than the limit set with `--max-input-size`, or by
+`AssemblerConfig::with_max_input_size` when rhasm is used as a library.
+
+Reading stops as soon as the limit is exceeded. A program filling the whole ROM takes up
+well under a megabyte of assembly, so larger inputs are unlikely to be valid programs.
+",
+    },
+    ErrorCode {
+        code: "W0001",
+        lint: Some("unused-label"),
+        summary: "label is never used",
+        explanation: "\
+A label is defined, but no A-Instruction ever refers to it.
+
+Example:
+
+    (UNUSED)
+    (END)
+    @END
+    0;JMP
+
+The label may be misspelled where it is meant to be used, or left over from earlier code.
+",
+    },
+    ErrorCode {
+        code: "W0002",
+        lint: Some("label-used-as-data"),
+        summary: "label is never jumped to",
+        explanation: "\
+A label is referred to, but never right before a jump, so its address is only ever used as data.
+
+Example:
+
+    (COUNT)
+    @COUNT
+    M=M+1
+
+A label marks a ROM address, not a RAM cell, so this increments whatever RAM cell shares
+its number. Use a variable instead, which is a symbol that is never defined as a label.
+
+A label copied into D with `D=A` is not reported, as translated VM code stores return
+addresses that way to jump to them later.
+",
+    },
+    ErrorCode {
+        code: "W0003",
+        lint: Some("unread-variable"),
+        summary: "variable is written but never read",
+        explanation: "\
+Every use of a variable stores a value in it, but the value is never read back.
+
+The variable may be misspelled where it is read, or no longer needed.
+",
+    },
+    ErrorCode {
+        code: "W0004",
+        lint: Some("symbol-case"),
+        summary: "symbol differs from another only by case",
+        explanation: "\
+Two symbols are spelled the same apart from their case, e.g. `LOOP` and `loop`.
+
+Symbols are case sensitive, so these are different symbols, which is rarely intended.
+",
+    },
+    ErrorCode {
+        code: "W0005",
+        lint: Some("missing-end-loop"),
+        summary: "program does not end in an unconditional jump",
+        explanation: "\
+The last instruction of the program is not an unconditional jump, so the CPU runs on into
+whatever follows the program in ROM.
+
+End programs with an infinite loop:
+
+    (END)
+    @END
+    0;JMP
+",
+    },
+    ErrorCode {
+        code: "W0006",
+        lint: Some("unreachable-code"),
+        summary: "unreachable instruction",
+        explanation: "\
+An instruction follows an unconditional jump, but no label points at it and no jump to a
+constant address reaches it, so it never runs.
+
+Example:
+
+    @LOOP
+    0;JMP
+    D=A
+
+Add a label before the instruction if it is meant to be jumped to, or remove it.
+",
+    },
+    ErrorCode {
+        code: "W0007",
+        lint: Some("label-case"),
+        summary: "label is not written in upper case",
+        explanation: "\
+Labels are conventionally written in upper case, e.g. `LOOP`, to tell them apart from
+variables, which are written in lower case. Labels written by the VM translator, like
+`Main.main`, are exempt. This style check only runs with `rhasm lint` or if its level is set.
+",
+    },
+    ErrorCode {
+        code: "W0008",
+        lint: Some("duplicate-label"),
+        summary: "duplicate label, the last definition is used",
+        explanation: "\
+A label is defined more than once while `--allow-duplicate-labels` is set, so every
+reference jumps to the last definition. See E0002.
+",
+    },
+    ErrorCode {
+        code: "W0009",
+        lint: Some("truncated-constant"),
+        summary: "A-Instruction constant truncated to 15 bits",
+        explanation: "\
+An A-Instruction constant larger than 32767 was cut down to its lowest 15 bits, as asked
+for with `--constant-overflow truncate`. See E0011.
+",
+    },
+    ErrorCode {
+        code: "W0010",
+        lint: Some("variable-overflow"),
+        summary: "variable allocated in the screen memory map",
+        explanation: "\
+A variable was allocated at RAM[16384] or above while `--allow-variable-overflow` is set,
+so writing it draws on the screen. See E0014.
+",
+    },
+    ErrorCode {
+        code: "W0011",
+        lint: Some("non-canonical-syntax"),
+        summary: "accepted syntax differs from the Hack specification",
+        explanation: "\
+rhasm accepts some syntax the Hack specification writes differently, and assembles it the
+same way. The note shows the canonical form, so the program also assembles with other tools.
+
+Example:
+
+    DM=M+1
+
+The specification always names destination registers in the order A, M, D, so this is
+written `MD=M+1`. This style check only runs with `rhasm lint` or if its level is set.
+",
+    },
+];
+
+/// Look up a code in the catalog, either by the code itself or by the name of its lint.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::error_codes::lookup;
+///
+/// assert_eq!(lookup("E0002").unwrap().summary, "duplicate label");
+/// assert_eq!(lookup("e0002"), lookup("E0002"));
+/// assert_eq!(lookup("unused-label").unwrap().code, "W0001");
+/// assert_eq!(lookup("E9999"), None);
+/// ```
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES
+        .iter()
+        .find(|entry| entry.code.eq_ignore_ascii_case(code) || entry.lint == Some(code))
+}
+
+// The codes of the errors reported by the assembler, named after the problem
+pub(crate) const INVALID_COMP: &str = "E0001";
+#[cfg(feature = "std")]
+pub(crate) const DUPLICATE_LABEL: &str = "E0002";
+pub(crate) const INVALID_DEST: &str = "E0003";
+pub(crate) const INVALID_JUMP: &str = "E0004";
+pub(crate) const INVALID_INSTRUCTION: &str = "E0005";
+pub(crate) const MISSING_OPERAND: &str = "E0006";
+pub(crate) const OPERAND_WHITESPACE: &str = "E0007";
+pub(crate) const NEGATIVE_CONSTANT: &str = "E0008";
+pub(crate) const SIGNED_CONSTANT: &str = "E0009";
+pub(crate) const MALFORMED_CONSTANT: &str = "E0010";
+pub(crate) const CONSTANT_OUT_OF_RANGE: &str = "E0011";
+pub(crate) const SYMBOL_OVERFLOW: &str = "E0012";
+#[cfg(feature = "std")]
+pub(crate) const ROM_OVERFLOW: &str = "E0013";
+#[cfg(feature = "std")]
+pub(crate) const VARIABLE_OVERFLOW: &str = "E0014";
+#[cfg(feature = "std")]
+pub(crate) const UNRESOLVED_ALIAS: &str = "E0015";
+#[cfg(feature = "std")]
+pub(crate) const LINE_TOO_LONG: &str = "E0016";
+#[cfg(feature = "std")]
+pub(crate) const INPUT_TOO_LARGE: &str = "E0017";
@@ -0,0 +1,142 @@
+//! Module for explaining single Hack instructions bit by bit, intended as a teaching aid.
+
+use std::{ error::Error, fmt::Write as _ };
+use crate::lib::{ fields::{ Comp, Dest, Jump }, parser::INSTRUCTION_REGEX };
+use crate::{ decode_instruction, encode_instruction, Instruction, SymbolTable };
+
+/// Explain a single instruction by breaking it into its fields.
+///
+/// The instruction can either be given in assembly (e.g. `D=M+1;JGT`) or as its 16 bit binary encoding (e.g. `1111110111010001`).
+/// Only exactly 16 binary digits are taken as an encoding, so the computations `0` and `1` are explained as assembly.
+/// A-Instructions may only use numeric addresses or predefined symbols, as other symbols have no address outside of a program.
+///
+/// ### Returns
+///
+/// Returns a [`String`] containing the instruction in both forms followed by a table of its fields.
+/// Each row of the table lists the bit range, the field name, the bits and a short description.
+///
+/// ### Errors
+///
+/// * Returns an error if the instruction is neither valid assembly nor a valid encoded instruction.
+/// * Returns an error if the instruction is a label or uses a symbol that is not predefined.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::explain_instruction;
+///
+/// let explanation = explain_instruction("D=M+1;JGT").unwrap();
+/// assert!(explanation.contains("1111110111010001"));
+/// assert!(explanation.contains("dest = D"));
+///
+/// let explanation = explain_instruction("0000000100000000").unwrap();
+/// assert!(explanation.contains("@256"));
+///
+/// let explanation = explain_instruction("1").unwrap();
+/// assert!(explanation.contains("1110111111000000"));
+/// ```
+pub fn explain_instruction(instruction: &str) -> Result<String, Box<dyn Error>> {
+    let instruction = instruction.trim();
+    let is_binary = instruction.len() == 16 && instruction.chars().all(|char| char == '0' || char == '1');
+    let (assembly, encoded) = if is_binary {
+        (decode_instruction(instruction)?, instruction.to_string())
+    } else {
+        (instruction.to_string(), encode_for_explanation(instruction)?)
+    };
+
+    let mut out = String::new();
+    writeln!(out, "{}", assembly)?;
+    writeln!(out, "{}", encoded)?;
+    writeln!(out)?;
+
+    let mut row = |bits: &str, field: &str, value: &str, meaning: String| {
+        writeln!(out, "{:<7}{:<7}{:<17}{}", bits, field, value, meaning)
+    };
+    row("bits", "field", "value", "meaning".to_string())?;
+    if let Some(value) = encoded.strip_prefix('0') {
+        row("15", "type", "0", "A-instruction".to_string())?;
+        row(
+            "14-0",
+            "value",
+            value,
+            format!("load {} into the A register", u16::from_str_radix(value, 2)?)
+        )?;
+    } else {
+        let word = u16::from_str_radix(&encoded, 2)?;
+        let a_bit = &encoded[3..4];
+        let comp = Comp::from_bits(word >> 6).ok_or("Invalid comp bits")?;
+        row("15", "type", "1", "C-instruction".to_string())?;
+        row("14-13", "-", &encoded[1..3], "unused, conventionally set to 1".to_string())?;
+        row("12", "a", a_bit, match a_bit {
+            "1" => "comp reads M (RAM[A]) instead of A".to_string(),
+            _ => "comp reads the A register (if at all)".to_string(),
+        })?;
+        row("11-6", "c", &encoded[4..10], format!("comp = {}", comp))?;
+        row("5-3", "d", &encoded[10..13], match Dest::from_bits(word >> 3) {
+            Dest::Null => "no dest, the result is not stored".to_string(),
+            dest => format!("dest = {} (store the result in {})", dest, describe_dest(dest.mnemonic())),
+        })?;
+        row("2-0", "j", &encoded[13..], match Jump::from_bits(word) {
+            Jump::Null => "no jump".to_string(),
+            jump => format!("jump = {} ({})", jump, describe_jump(jump.mnemonic())),
+        })?;
+    }
+    Ok(out)
+}
+
+// Encode an assembly instruction outside of any program
+// Only numeric addresses and predefined symbols can be resolved
+fn encode_for_explanation(instruction: &str) -> Result<String, Box<dyn Error>> {
+    let captures = INSTRUCTION_REGEX.captures(instruction).ok_or_else(||
+        format!("Invalid instruction: {}", instruction)
+    )?;
+    let parsed = if let Some(a_symbol) = captures.name("a_symbol") {
+        let symbol = a_symbol.as_str();
+        let is_known = symbol.chars().all(|char| char.is_ascii_digit()) ||
+            SymbolTable::new().is_predefined(symbol);
+        if !is_known {
+            return Err(
+                format!("Cannot explain @{}, only numeric addresses and predefined symbols have a fixed value", symbol).into()
+            );
+        }
+        Instruction::AInstruction(symbol.to_string())
+    } else if let Some(c_comp) = captures.name("c_comp") {
+        Instruction::CInstruction(
+            captures.name("c_dest").map_or("", |m| m.as_str()).to_string(),
+            c_comp.as_str().to_string(),
+            captures.name("c_jump").map_or("", |m| m.as_str()).to_string()
+        )
+    } else {
+        return Err("Labels are not instructions and have no encoding".into());
+    };
+
+    // Only predefined symbols get here, so no variable is ever allocated
+    let mut symbol_table = SymbolTable::new();
+    Ok(encode_instruction(&parsed, &mut symbol_table)?)
+}
+
+fn describe_dest(dest: &str) -> String {
+    let registers: Vec<&str> = dest
+        .chars()
+        .map(|register| {
+            match register {
+                'A' => "A",
+                'D' => "D",
+                _ => "RAM[A]",
+            }
+        })
+        .collect();
+    registers.join(" and ")
+}
+
+fn describe_jump(jump: &str) -> &'static str {
+    match jump {
+        "JGT" => "jump to ROM[A] if comp > 0",
+        "JEQ" => "jump to ROM[A] if comp = 0",
+        "JGE" => "jump to ROM[A] if comp >= 0",
+        "JLT" => "jump to ROM[A] if comp < 0",
+        "JNE" => "jump to ROM[A] if comp != 0",
+        "JLE" => "jump to ROM[A] if comp <= 0",
+        _ => "always jump to ROM[A]",
+    }
+}
@@ -0,0 +1,471 @@
+//! Module for the typed fields of C-Instructions, for tools that analyse programs without matching on mnemonic strings.
+//!
+//! [`Dest`], [`Comp`] and [`Jump`] have a variant for every valid mnemonic, so invalid fields can not be represented.
+//! [`CFields`] combines them into a whole C-Instruction, which can be read from an [`Instruction`] with [`Instruction::c_fields`]
+//! or decoded from a machine word with [`CFields::decode`].
+
+use alloc::string::ToString;
+use core::{ fmt, str::FromStr };
+use crate::{ Instruction, RhasmError };
+
+/// The registers a C-Instruction stores its result in.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::fields::Dest;
+///
+/// // Registers may be given in any order, the variant is named in the canonical one
+/// assert_eq!("DM".parse::<Dest>().unwrap(), Dest::MD);
+/// assert_eq!(Dest::MD.bits(), 0b011);
+/// assert_eq!(Dest::from_bits(0b101), Dest::AM);
+/// assert_eq!("".parse::<Dest>().unwrap(), Dest::Null);
+/// assert!("AA".parse::<Dest>().is_err());
+/// ```
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Dest {
+    /// The result is not stored.
+    #[default]
+    Null,
+    M,
+    D,
+    MD,
+    A,
+    AM,
+    AD,
+    AMD,
+}
+
+impl Dest {
+    /// Every destination, ordered by their bits.
+    pub const ALL: [Dest; 8] = [Dest::Null, Dest::M, Dest::D, Dest::MD, Dest::A, Dest::AM, Dest::AD, Dest::AMD];
+
+    /// The 3 destination bits, `d1`-`d3`.
+    pub fn bits(self) -> u16 {
+        self as u16
+    }
+
+    /// The destination with the lowest 3 bits of `bits`.
+    pub fn from_bits(bits: u16) -> Dest {
+        Dest::ALL[usize::from(bits & 0b111)]
+    }
+
+    /// The canonical mnemonic, empty for [`Dest::Null`].
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Dest::Null => "",
+            Dest::M => "M",
+            Dest::D => "D",
+            Dest::MD => "MD",
+            Dest::A => "A",
+            Dest::AM => "AM",
+            Dest::AD => "AD",
+            Dest::AMD => "AMD",
+        }
+    }
+
+    /// Whether the result is stored in the `A` register.
+    pub fn writes_a(self) -> bool {
+        self.bits() & 0b100 != 0
+    }
+
+    /// Whether the result is stored in the `D` register.
+    pub fn writes_d(self) -> bool {
+        self.bits() & 0b010 != 0
+    }
+
+    /// Whether the result is stored in memory, at the address in the `A` register.
+    pub fn writes_m(self) -> bool {
+        self.bits() & 0b001 != 0
+    }
+}
+
+impl FromStr for Dest {
+    type Err = RhasmError;
+
+    // Registers may be given in any order, but only once each
+    fn from_str(mnemonic: &str) -> Result<Dest, RhasmError> {
+        let mut bits = 0;
+        for char in mnemonic.chars() {
+            let bit = match char {
+                'A' => 0b100,
+                'D' => 0b010,
+                'M' => 0b001,
+                _ => {
+                    return Err(RhasmError::InvalidDest(mnemonic.to_string()));
+                }
+            };
+            if bits & bit != 0 {
+                return Err(RhasmError::InvalidDest(mnemonic.to_string()));
+            }
+            bits |= bit;
+        }
+        Ok(Dest::from_bits(bits))
+    }
+}
+
+impl fmt::Display for Dest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+/// The computation of a C-Instruction.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::fields::Comp;
+///
+/// assert_eq!("D+M".parse::<Comp>().unwrap(), Comp::DPlusM);
+/// assert_eq!(Comp::DPlusM.bits(), 0b1000010);
+/// assert!(Comp::DPlusM.reads_m());
+/// assert_eq!(Comp::from_bits(0b0101010), Some(Comp::Zero));
+/// assert_eq!(Comp::NotA.to_string(), "!A");
+/// // Only one operand order is valid
+/// assert!("M+D".parse::<Comp>().is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Comp {
+    /// `0`
+    Zero,
+    /// `1`
+    One,
+    /// `-1`
+    MinusOne,
+    /// `D`
+    D,
+    /// `A`
+    A,
+    /// `!D`
+    NotD,
+    /// `!A`
+    NotA,
+    /// `-D`
+    NegD,
+    /// `-A`
+    NegA,
+    /// `D+1`
+    DPlusOne,
+    /// `A+1`
+    APlusOne,
+    /// `D-1`
+    DMinusOne,
+    /// `A-1`
+    AMinusOne,
+    /// `D+A`
+    DPlusA,
+    /// `D-A`
+    DMinusA,
+    /// `A-D`
+    AMinusD,
+    /// `D&A`
+    DAndA,
+    /// `D|A`
+    DOrA,
+    /// `M`
+    M,
+    /// `!M`
+    NotM,
+    /// `-M`
+    NegM,
+    /// `M+1`
+    MPlusOne,
+    /// `M-1`
+    MMinusOne,
+    /// `D+M`
+    DPlusM,
+    /// `D-M`
+    DMinusM,
+    /// `M-D`
+    MMinusD,
+    /// `D&M`
+    DAndM,
+    /// `D|M`
+    DOrM,
+}
+
+impl Comp {
+    /// Every computation, in the order of the Hack specification.
+    pub const ALL: [Comp; 28] = [
+        Comp::Zero, Comp::One, Comp::MinusOne, Comp::D, Comp::A, Comp::NotD, Comp::NotA, Comp::NegD, Comp::NegA,
+        Comp::DPlusOne, Comp::APlusOne, Comp::DMinusOne, Comp::AMinusOne, Comp::DPlusA, Comp::DMinusA, Comp::AMinusD,
+        Comp::DAndA, Comp::DOrA, Comp::M, Comp::NotM, Comp::NegM, Comp::MPlusOne, Comp::MMinusOne, Comp::DPlusM,
+        Comp::DMinusM, Comp::MMinusD, Comp::DAndM, Comp::DOrM,
+    ];
+
+    /// The 7 computation bits, `a` followed by `c1`-`c6`.
+    pub fn bits(self) -> u16 {
+        match self {
+            Comp::Zero => 0b0101010,
+            Comp::One => 0b0111111,
+            Comp::MinusOne => 0b0111010,
+            Comp::D => 0b0001100,
+            Comp::A => 0b0110000,
+            Comp::NotD => 0b0001101,
+            Comp::NotA => 0b0110001,
+            Comp::NegD => 0b0001111,
+            Comp::NegA => 0b0110011,
+            Comp::DPlusOne => 0b0011111,
+            Comp::APlusOne => 0b0110111,
+            Comp::DMinusOne => 0b0001110,
+            Comp::AMinusOne => 0b0110010,
+            Comp::DPlusA => 0b0000010,
+            Comp::DMinusA => 0b0010011,
+            Comp::AMinusD => 0b0000111,
+            Comp::DAndA => 0b0000000,
+            Comp::DOrA => 0b0010101,
+            Comp::M => 0b1110000,
+            Comp::NotM => 0b1110001,
+            Comp::NegM => 0b1110011,
+            Comp::MPlusOne => 0b1110111,
+            Comp::MMinusOne => 0b1110010,
+            Comp::DPlusM => 0b1000010,
+            Comp::DMinusM => 0b1010011,
+            Comp::MMinusD => 0b1000111,
+            Comp::DAndM => 0b1000000,
+            Comp::DOrM => 0b1010101,
+        }
+    }
+
+    /// The computation with the lowest 7 bits of `bits`, or [`None`] if they do not name one.
+    pub fn from_bits(bits: u16) -> Option<Comp> {
+        Comp::ALL.into_iter().find(|comp| comp.bits() == bits & 0b1111111)
+    }
+
+    /// The mnemonic, e.g. `D+M`.
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Comp::Zero => "0",
+            Comp::One => "1",
+            Comp::MinusOne => "-1",
+            Comp::D => "D",
+            Comp::A => "A",
+            Comp::NotD => "!D",
+            Comp::NotA => "!A",
+            Comp::NegD => "-D",
+            Comp::NegA => "-A",
+            Comp::DPlusOne => "D+1",
+            Comp::APlusOne => "A+1",
+            Comp::DMinusOne => "D-1",
+            Comp::AMinusOne => "A-1",
+            Comp::DPlusA => "D+A",
+            Comp::DMinusA => "D-A",
+            Comp::AMinusD => "A-D",
+            Comp::DAndA => "D&A",
+            Comp::DOrA => "D|A",
+            Comp::M => "M",
+            Comp::NotM => "!M",
+            Comp::NegM => "-M",
+            Comp::MPlusOne => "M+1",
+            Comp::MMinusOne => "M-1",
+            Comp::DPlusM => "D+M",
+            Comp::DMinusM => "D-M",
+            Comp::MMinusD => "M-D",
+            Comp::DAndM => "D&M",
+            Comp::DOrM => "D|M",
+        }
+    }
+
+    /// Whether the computation reads memory at the address in the `A` register, i.e. its `a` bit is set.
+    pub fn reads_m(self) -> bool {
+        self.bits() & 0b1000000 != 0
+    }
+}
+
+impl FromStr for Comp {
+    type Err = RhasmError;
+
+    fn from_str(mnemonic: &str) -> Result<Comp, RhasmError> {
+        Comp::ALL
+            .into_iter()
+            .find(|comp| comp.mnemonic() == mnemonic)
+            .ok_or_else(|| RhasmError::InvalidComp(mnemonic.to_string()))
+    }
+}
+
+impl fmt::Display for Comp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+/// The condition under which a C-Instruction jumps to the address in the `A` register.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::fields::Jump;
+///
+/// assert_eq!("JGE".parse::<Jump>().unwrap(), Jump::JGE);
+/// assert_eq!(Jump::JGE.bits(), 0b011);
+/// assert_eq!(Jump::from_bits(0b111), Jump::JMP);
+/// assert_eq!("".parse::<Jump>().unwrap(), Jump::Null);
+/// assert!("JMG".parse::<Jump>().is_err());
+/// ```
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Jump {
+    /// Never jump.
+    #[default]
+    Null,
+    JGT,
+    JEQ,
+    JGE,
+    JLT,
+    JNE,
+    JLE,
+    JMP,
+}
+
+impl Jump {
+    /// Every jump, ordered by their bits.
+    pub const ALL: [Jump; 8] = [Jump::Null, Jump::JGT, Jump::JEQ, Jump::JGE, Jump::JLT, Jump::JNE, Jump::JLE, Jump::JMP];
+
+    /// The 3 jump bits, `j1`-`j3`.
+    pub fn bits(self) -> u16 {
+        self as u16
+    }
+
+    /// The jump with the lowest 3 bits of `bits`.
+    pub fn from_bits(bits: u16) -> Jump {
+        Jump::ALL[usize::from(bits & 0b111)]
+    }
+
+    /// The mnemonic, empty for [`Jump::Null`].
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Jump::Null => "",
+            Jump::JGT => "JGT",
+            Jump::JEQ => "JEQ",
+            Jump::JGE => "JGE",
+            Jump::JLT => "JLT",
+            Jump::JNE => "JNE",
+            Jump::JLE => "JLE",
+            Jump::JMP => "JMP",
+        }
+    }
+}
+
+impl FromStr for Jump {
+    type Err = RhasmError;
+
+    fn from_str(mnemonic: &str) -> Result<Jump, RhasmError> {
+        Jump::ALL
+            .into_iter()
+            .find(|jump| jump.mnemonic() == mnemonic)
+            .ok_or_else(|| RhasmError::InvalidJump(mnemonic.to_string()))
+    }
+}
+
+impl fmt::Display for Jump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+/// The typed fields of a C-Instruction.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::fields::{ CFields, Comp, Dest, Jump };
+///
+/// let fields = CFields::parse("DM", "M+1", "").unwrap();
+/// assert_eq!(fields, CFields { dest: Dest::MD, comp: Comp::MPlusOne, jump: Jump::Null });
+/// assert_eq!(fields.encode(), 0b1111110111011000);
+/// assert_eq!(CFields::decode(fields.encode()), Some(fields));
+/// assert_eq!(fields.to_string(), "MD=M+1");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CFields {
+    /// Where the result is stored.
+    pub dest: Dest,
+    /// What is computed.
+    pub comp: Comp,
+    /// When to jump.
+    pub jump: Jump,
+}
+
+impl CFields {
+    /// Parse the destination, computation and jump mnemonics of a C-Instruction, empty mnemonics meaning no destination or jump.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::InvalidComp`], [`RhasmError::InvalidDest`] or [`RhasmError::InvalidJump`] for the first unknown mnemonic,
+    ///   checked in that order like [`crate::encode_instruction`].
+    pub fn parse(dest: &str, comp: &str, jump: &str) -> Result<CFields, RhasmError> {
+        let comp = comp.parse()?;
+        let dest = dest.parse()?;
+        let jump = jump.parse()?;
+        Ok(CFields { dest, comp, jump })
+    }
+
+    /// The machine word of the C-Instruction.
+    pub fn encode(self) -> u16 {
+        0b111 << 13 | self.comp.bits() << 6 | self.dest.bits() << 3 | self.jump.bits()
+    }
+
+    /// Decode the fields of a C-Instruction word.
+    ///
+    /// ### Returns
+    ///
+    /// Returns [`None`] if `word` is an A-Instruction, or its computation bits do not name a computation.
+    pub fn decode(word: u16) -> Option<CFields> {
+        if word & 0x8000 == 0 {
+            return None;
+        }
+        Some(CFields { dest: Dest::from_bits(word >> 3), comp: Comp::from_bits(word >> 6)?, jump: Jump::from_bits(word) })
+    }
+}
+
+impl fmt::Display for CFields {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.dest != Dest::Null {
+            write!(f, "{}=", self.dest)?;
+        }
+        write!(f, "{}", self.comp)?;
+        if self.jump != Jump::Null {
+            write!(f, ";{}", self.jump)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<CFields> for Instruction {
+    fn from(fields: CFields) -> Instruction {
+        Instruction::CInstruction(fields.dest.to_string(), fields.comp.to_string(), fields.jump.to_string())
+    }
+}
+
+impl Instruction {
+    /// The typed fields of a C-Instruction.
+    ///
+    /// ### Returns
+    ///
+    /// Returns [`None`] for an A-Instruction.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns the same errors as [`CFields::parse`] if a mnemonic is unknown.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::Instruction;
+    /// use rhasm::fields::{ Comp, Jump };
+    ///
+    /// let instruction = Instruction::CInstruction("".to_string(), "D".to_string(), "JGT".to_string());
+    /// let fields = instruction.c_fields().unwrap().unwrap();
+    /// assert_eq!((fields.comp, fields.jump), (Comp::D, Jump::JGT));
+    ///
+    /// assert_eq!(Instruction::AInstruction("LOOP".to_string()).c_fields().unwrap(), None);
+    /// ```
+    pub fn c_fields(&self) -> Result<Option<CFields>, RhasmError> {
+        match self {
+            Instruction::AInstruction(_) => Ok(None),
+            Instruction::CInstruction(dest, comp, jump) => CFields::parse(dest, comp, jump).map(Some),
+        }
+    }
+}
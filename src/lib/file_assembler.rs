@@ -0,0 +1,108 @@
+//! Module containing the [`FileAssembler`], which assembles a source file on disk into a `.hack` file.
+
+use std::{ fs::{ self, File }, io, path::{ Path, PathBuf } };
+use crate::{ Assembler, AssemblerConfig, Diagnostic, RhasmError };
+
+/// Assembles a source file on disk into a file of machine code, for tools that work with paths rather than streams.
+///
+/// The output defaults to the input with the extension `.hack`, next to it. It is only written once the whole program is assembled,
+/// so a source with errors never leaves a partial output behind. Every I/O error names the file it happened on.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::FileAssembler;
+///
+/// let dir = std::env::temp_dir().join("rhasm_file_assembler_doc");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("Add.asm"), "@2\nD=A\n(END)\n@END\n0;JMP\n").unwrap();
+///
+/// let assembler = FileAssembler::new(dir.join("Add.asm"));
+/// assert_eq!(assembler.output(), dir.join("Add.hack"));
+///
+/// let warnings = assembler.assemble().unwrap();
+/// assert!(warnings.is_empty());
+/// assert!(std::fs::read_to_string(dir.join("Add.hack")).unwrap().starts_with("0000000000000010\n"));
+///
+/// // Missing inputs are reported, never replaced by a default
+/// let err = FileAssembler::new(dir.join("Missing.asm")).assemble().unwrap_err();
+/// assert!(err.to_string().contains("Missing.asm"));
+///
+/// // A source that already ends in `.hack` would be overwritten by its own machine code, so it is rejected
+/// std::fs::write(dir.join("Prog.hack"), "@2\n").unwrap();
+/// let err = FileAssembler::new(dir.join("Prog.hack")).assemble().unwrap_err();
+/// assert!(matches!(err, rhasm::RhasmError::Io(err) if err.kind() == std::io::ErrorKind::InvalidInput));
+/// assert_eq!(std::fs::read_to_string(dir.join("Prog.hack")).unwrap(), "@2\n");
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileAssembler {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+impl FileAssembler {
+    /// Create an assembler for the source file at `input`, writing to the same path with the extension `.hack`.
+    pub fn new(input: impl AsRef<Path>) -> FileAssembler {
+        let input = input.as_ref().to_path_buf();
+        let output = input.with_extension("hack");
+        FileAssembler { input, output }
+    }
+
+    /// Write the machine code to `output` instead.
+    pub fn with_output(mut self, output: impl AsRef<Path>) -> Self {
+        self.output = output.as_ref().to_path_buf();
+        self
+    }
+
+    /// The source file that is assembled.
+    pub fn input(&self) -> &Path {
+        &self.input
+    }
+
+    /// The file the machine code is written to.
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Assemble the input and write the machine code to the output, replacing it if it exists.
+    ///
+    /// ### Returns
+    ///
+    /// Returns the warnings found in the source, see [`Assembler::diagnostics`].
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Io`] of kind [`io::ErrorKind::InvalidInput`] if the output is the input itself.
+    /// * Returns [`RhasmError::Io`] naming the input if it can not be opened or read, or the output if it can not be written.
+    /// * Returns the same errors as [`Assembler::build`] and [`Assembler::advance_to_end`] if the source can not be assembled,
+    ///   in which case the output is left untouched.
+    pub fn assemble(&self) -> Result<Vec<Diagnostic>, RhasmError> {
+        if self.output == self.input {
+            return Err(
+                with_path(&self.input, io::Error::new(io::ErrorKind::InvalidInput, "the output would overwrite the source file"))
+            );
+        }
+        let mut in_file = File::open(&self.input).map_err(|err| with_path(&self.input, err))?;
+        let mut machine_code: Vec<u8> = Vec::new();
+        let mut assembler = Assembler::build(AssemblerConfig::new(&mut in_file, &mut machine_code)).map_err(|err| self.name_input(err))?;
+        assembler.advance_to_end().map_err(|err| self.name_input(err))?;
+        let warnings = assembler.diagnostics().to_vec();
+        drop(assembler);
+        fs::write(&self.output, machine_code).map_err(|err| with_path(&self.output, err))?;
+        Ok(warnings)
+    }
+
+    // Errors writing to memory can not happen, so every I/O error while assembling came from reading the input
+    fn name_input(&self, err: RhasmError) -> RhasmError {
+        match err {
+            RhasmError::Io(err) => with_path(&self.input, err),
+            err => err,
+        }
+    }
+}
+
+// Prefix an I/O error with the file it happened on, keeping its kind
+fn with_path(path: &Path, err: io::Error) -> RhasmError {
+    RhasmError::Io(io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+}
@@ -0,0 +1,130 @@
+//! Module containing the [`Instruction`] enum produced by the parser and consumed by the encoder,
+//! along with the location of each instruction within the source.
+
+use alloc::{ format, string::{ String, ToString }, vec };
+use core::{ fmt, str::FromStr };
+use crate::lib::{ encoder, parser::{ invalid_line, parse_line_spanned, ParsedLine } };
+use crate::{ Diagnostic, RhasmError, Span };
+
+/// Enum to represent the different types of instructions in the Hack Assembly Language.
+/// Contains variants for A-Instructions and C-Instructions.
+/// Each variant contains the necessary data to represent the instruction.
+/// The mnemonics of a C-Instruction are kept as written, see [`Instruction::c_fields`] for their typed form.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    /// A-Instruction variant, contains the address or symbol of the instruction.
+    AInstruction(String),
+    /// C-Instruction variant, contains the destination, computation, and jump mnemonics, respectively.
+    CInstruction(String, String, String),
+}
+
+/// Parse a single instruction, without comments or surrounding whitespace, see [`crate::parse_line`] for whole source lines.
+///
+/// ### Errors
+///
+/// * Returns [`RhasmError::Diagnostics`] with a single diagnostic on line 1, like [`crate::parse_line`],
+///   if the text is not exactly one instruction, e.g. if it is blank or a label.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::Instruction;
+///
+/// assert_eq!("@LOOP".parse::<Instruction>().unwrap(), Instruction::AInstruction("LOOP".to_string()));
+/// assert_eq!(
+///     "D=M;JGT".parse::<Instruction>().unwrap(),
+///     Instruction::CInstruction("D".to_string(), "M".to_string(), "JGT".to_string())
+/// );
+/// assert!("(LOOP)".parse::<Instruction>().is_err());
+/// ```
+impl FromStr for Instruction {
+    type Err = RhasmError;
+
+    fn from_str(text: &str) -> Result<Instruction, RhasmError> {
+        match parse_line_spanned(text, Span::new(1, 0, text.len())) {
+            Some((ParsedLine::Instruction(instruction), spans)) => {
+                encoder::validate_mnemonics(&instruction).map_err(|err| locate_error(err, &instruction, &spans))?;
+                Ok(instruction)
+            }
+            _ => Err(RhasmError::Diagnostics(vec![invalid_line(text, Span::new(1, 0, text.len()))])),
+        }
+    }
+}
+
+/// Prints the instruction as Hack assembly, without comments or whitespace, e.g. `@256` or `D=M+1;JGT`.
+///
+/// Mnemonics are printed as they were parsed, so parsing the printed text gives back the same instruction.
+/// See [`crate::fields::CFields`] to print a C-Instruction with its destination registers in the canonical order.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::Instruction;
+///
+/// let instruction = Instruction::CInstruction("D".to_string(), "M+1".to_string(), "JGT".to_string());
+/// assert_eq!(instruction.to_string(), "D=M+1;JGT");
+/// assert_eq!(Instruction::AInstruction("256".to_string()).to_string(), "@256");
+///
+/// for text in ["@LOOP", "0;JMP", "AM=M-1"] {
+///     assert_eq!(text.parse::<Instruction>().unwrap().to_string(), text);
+/// }
+/// ```
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::AInstruction(symbol) => write!(f, "@{}", symbol),
+            Instruction::CInstruction(dest, comp, jump) => {
+                if !dest.is_empty() {
+                    write!(f, "{}=", dest)?;
+                }
+                f.write_str(comp)?;
+                if !jump.is_empty() {
+                    write!(f, ";{}", jump)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The location of an instruction and each of its fields within the source.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InstructionSpans {
+    /// The whole instruction without surrounding whitespace and comments.
+    pub instruction: Span,
+    /// The destination of a C-Instruction, if it has one.
+    pub dest: Option<Span>,
+    /// The computation of a C-Instruction.
+    pub comp: Option<Span>,
+    /// The jump of a C-Instruction, if it has one.
+    pub jump: Option<Span>,
+}
+
+// Turn an error raised while encoding `instruction` into a diagnostic pointing at the offending field
+// Errors unrelated to the source, i.e. I/O errors, are returned unchanged
+pub(crate) fn locate_error(err: RhasmError, instruction: &Instruction, spans: &InstructionSpans) -> RhasmError {
+    let span = match err {
+        #[cfg(feature = "std")]
+        RhasmError::Io(_) => {
+            return err;
+        }
+        RhasmError::InvalidDest(_) => spans.dest,
+        RhasmError::InvalidComp(_) => spans.comp,
+        RhasmError::InvalidJump(_) => spans.jump,
+        _ => None,
+    };
+    let mut diagnostic = Diagnostic::new(
+        span.unwrap_or(spans.instruction),
+        &instruction.to_string(),
+        &err.to_string()
+    );
+    if let Some(code) = err.code() {
+        diagnostic = diagnostic.with_code(code);
+    }
+    if let Some(suggestion) = encoder::suggest_mnemonic(&err) {
+        diagnostic = diagnostic.with_help(&format!("did you mean `{}`?", suggestion));
+    }
+    RhasmError::Diagnostics(vec![diagnostic])
+}
@@ -0,0 +1,117 @@
+//! Module for the [`OutputFormat`] trait, deciding how the [`crate::Assembler`] and [`crate::Disassembler`] write each line of their output.
+//!
+//! The built in formats are [`MachineCodeFormat`] for binary or hexadecimal text, [`RawBytes`] for binary files,
+//! and [`WordComments`] for output annotated with the ROM address and machine word of every line.
+
+use std::io::{ self, Write };
+use crate::MachineCodeFormat;
+
+/// A way of writing machine words and the instructions decoded from them,
+/// see [`crate::AssemblerConfig::with_output_format`] and [`crate::DisassemblerConfig::with_output_format`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use rhasm::output_format::OutputFormat;
+/// use std::io::{ self, Cursor, Write };
+///
+/// // Writes a program as the body of a Verilog ROM initialisation file
+/// struct Verilog;
+///
+/// impl OutputFormat for Verilog {
+///     fn write_word(&self, out: &mut dyn Write, address: u16, word: u16) -> io::Result<()> {
+///         writeln!(out, "rom[{}] = 16'h{:04x};", address, word)
+///     }
+/// }
+///
+/// let mut in_file = Cursor::new("@256\nD=A\n");
+/// let mut out_file = Cursor::new(Vec::new());
+/// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_output_format(&Verilog);
+/// let mut assembler = Assembler::build(config).unwrap();
+/// assembler.advance_to_end().unwrap();
+/// drop(assembler);
+///
+/// assert_eq!(String::from_utf8(out_file.into_inner()).unwrap(), "rom[0] = 16'h0100;\nrom[1] = 16'hec10;\n");
+/// ```
+pub trait OutputFormat {
+    /// Write the machine word at ROM `address` the way the assembler writes it, including any line ending.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns an error if writing to `out` fails.
+    fn write_word(&self, out: &mut dyn Write, address: u16, word: u16) -> io::Result<()>;
+
+    /// Write `instruction`, decoded from the machine word at ROM `address`, the way the disassembler writes it.
+    /// Writes the instruction alone on its own line by default.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns an error if writing to `out` fails.
+    fn write_instruction(&self, out: &mut dyn Write, _address: u16, _word: u16, instruction: &str) -> io::Result<()> {
+        writeln!(out, "{}", instruction)
+    }
+}
+
+impl<T: OutputFormat + ?Sized> OutputFormat for &T {
+    fn write_word(&self, out: &mut dyn Write, address: u16, word: u16) -> io::Result<()> {
+        (**self).write_word(out, address, word)
+    }
+
+    fn write_instruction(&self, out: &mut dyn Write, address: u16, word: u16, instruction: &str) -> io::Result<()> {
+        (**self).write_instruction(out, address, word, instruction)
+    }
+}
+
+/// Writes every word on its own line as binary or hexadecimal digits.
+impl OutputFormat for MachineCodeFormat {
+    fn write_word(&self, out: &mut dyn Write, _address: u16, word: u16) -> io::Result<()> {
+        writeln!(out, "{}", self.format_word(word))
+    }
+}
+
+/// Writes every word as two bytes, the most significant first, e.g. to flash a program to a hardware ROM.
+/// Instructions are written as text like by every other format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RawBytes;
+
+impl OutputFormat for RawBytes {
+    fn write_word(&self, out: &mut dyn Write, _address: u16, word: u16) -> io::Result<()> {
+        out.write_all(&word.to_be_bytes())
+    }
+}
+
+/// Enum to represent whether every line of the output notes its ROM address, and for instructions also their machine word,
+/// e.g. `D=M+1;JGT    // 0x0007 1111110111010001`, for reading it side by side with the machine code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WordComments {
+    /// Write the words or instructions alone, the default.
+    #[default]
+    Off,
+    /// Write the comment on its own line above each word or instruction.
+    Above,
+    /// Write the comment after each word or instruction on the same line.
+    Inline,
+}
+
+impl WordComments {
+    // Write `line` with `comment` placed as asked for
+    fn write_line(self, out: &mut dyn Write, line: &str, comment: &str) -> io::Result<()> {
+        match self {
+            WordComments::Off => writeln!(out, "{}", line),
+            WordComments::Above => writeln!(out, "{}\n{}", comment, line),
+            WordComments::Inline => writeln!(out, "{:<11}  {}", line, comment),
+        }
+    }
+}
+
+/// Writes words as binary digits and instructions as text, each with its address and word as a comment.
+impl OutputFormat for WordComments {
+    fn write_word(&self, out: &mut dyn Write, address: u16, word: u16) -> io::Result<()> {
+        self.write_line(out, &format!("{:016b}", word), &format!("// 0x{:04x}", address))
+    }
+
+    fn write_instruction(&self, out: &mut dyn Write, address: u16, word: u16, instruction: &str) -> io::Result<()> {
+        self.write_line(out, instruction, &format!("// 0x{:04x} {:016b}", address, word))
+    }
+}
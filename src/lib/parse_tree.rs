@@ -0,0 +1,138 @@
+//! Module for exporting the parsed form of a source file, for tools that consume rhasm's parse rather than reimplementing the grammar.
+
+use crate::lib::{ parser::{ parse_line_spanned, CommentStripper, ParsedLine }, json };
+use crate::{ Instruction, InstructionSpans, Span };
+
+/// Dump every instruction, label, alias, comment and invalid line of `source` as a JSON array, in source order.
+///
+/// Each element is an object with a `kind`, the 1-based `line`, and the 0-based `start` and exclusive `end` byte offsets within that line.
+/// Depending on the kind it has further fields:
+///
+/// * `a_instruction`: the ROM `address` of the instruction and the `symbol` or constant it loads.
+/// * `c_instruction`: the ROM `address` of the instruction and its `dest`, `comp` and `jump` fields,
+///   each an object with the `text` and its `start` and `end`, or `null` if the field is missing.
+/// * `label`: the `name` of the label and the ROM `address` it refers to.
+/// * `alias`: the `name` of the alias and its `target`.
+/// * `comment`: the `text` of the comment, including its delimiters.
+/// * `invalid`: the `text` of a line that could not be parsed.
+///
+/// With `lenient_syntax` the `;`, `#` and `/* */` comments of [`crate::AssemblerConfig::with_lenient_syntax`] are recognised too.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::parse_tree;
+///
+/// let json = parse_tree::to_json("(LOOP) // top\n0;JMP\n", false);
+/// assert_eq!(
+///     json,
+///     r#"[
+///   {"kind":"label","line":1,"start":0,"end":6,"name":"LOOP","address":0},
+///   {"kind":"comment","line":1,"start":7,"end":13,"text":"// top"},
+///   {"kind":"c_instruction","line":2,"start":0,"end":5,"address":0,"dest":null,"comp":{"text":"0","start":0,"end":1},"jump":{"text":"JMP","start":2,"end":5}}
+/// ]
+/// "#
+/// );
+/// ```
+pub fn to_json(source: &str, lenient_syntax: bool) -> String {
+    let mut comments = CommentStripper::new(lenient_syntax);
+    let mut items: Vec<String> = Vec::new();
+    let mut address: usize = 0;
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let stripped = comments.strip(raw_line);
+        let code = stripped.trim();
+        let comments_on_line = comment_spans(raw_line, &stripped, line_number);
+        // Comments in front of the code, e.g. a block comment closed on this line, are listed before it
+        let code_start = stripped.len() - stripped.trim_start().len();
+        let (before, after): (Vec<Span>, Vec<Span>) = comments_on_line
+            .into_iter()
+            .partition(|comment| !code.is_empty() && comment.end <= code_start);
+        items.extend(before.into_iter().map(|comment| comment_json(raw_line, comment)));
+        if !code.is_empty() {
+            let span = Span::new(line_number, code_start, code_start + code.len());
+            items.push(match parse_line_spanned(code, span) {
+                Some((ParsedLine::Instruction(instruction), spans)) => {
+                    address += 1;
+                    instruction_json(&instruction, &spans, address - 1)
+                }
+                Some((ParsedLine::Label(label), _)) => {
+                    format!("{},\"name\":{},\"address\":{}}}", item_json("label", span), json::string(&label), address)
+                }
+                Some((ParsedLine::Alias(alias, target), _)) => {
+                    format!(
+                        "{},\"name\":{},\"target\":{}}}",
+                        item_json("alias", span),
+                        json::string(&alias),
+                        json::string(&target)
+                    )
+                }
+                None => format!("{},\"text\":{}}}", item_json("invalid", span), json::string(code)),
+            });
+        }
+        items.extend(after.into_iter().map(|comment| comment_json(raw_line, comment)));
+    }
+    match items.is_empty() {
+        true => "[]\n".to_string(),
+        false => format!("[\n  {}\n]\n", items.join(",\n  ")),
+    }
+}
+
+// The location of each comment on a line
+// Comments are blanked with spaces or cut off by the stripper, so they are wherever the stripped line differs,
+// runs of differing bytes only separated by whitespace belong to the same comment
+fn comment_spans(raw_line: &str, stripped: &str, line_number: usize) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for (index, byte) in raw_line.bytes().enumerate() {
+        if stripped.as_bytes().get(index) == Some(&byte) {
+            continue;
+        }
+        match spans.last_mut() {
+            Some(last) if raw_line.as_bytes()[last.end..index].iter().all(u8::is_ascii_whitespace) => {
+                last.end = index + 1;
+            }
+            _ => spans.push(Span::new(line_number, index, index + 1)),
+        }
+    }
+    spans
+}
+
+// The opening fields shared by every item, the object is left open for the item's own fields
+fn item_json(kind: &str, span: Span) -> String {
+    format!("{{\"kind\":\"{}\",\"line\":{},\"start\":{},\"end\":{}", kind, span.line, span.start, span.end)
+}
+
+fn comment_json(raw_line: &str, span: Span) -> String {
+    format!("{},\"text\":{}}}", item_json("comment", span), json::string(&raw_line[span.start..span.end]))
+}
+
+fn instruction_json(instruction: &Instruction, spans: &InstructionSpans, address: usize) -> String {
+    match instruction {
+        Instruction::AInstruction(symbol) => {
+            format!(
+                "{},\"address\":{},\"symbol\":{}}}",
+                item_json("a_instruction", spans.instruction),
+                address,
+                json::string(symbol)
+            )
+        }
+        Instruction::CInstruction(dest, comp, jump) => {
+            let field = |text: &str, span: Option<Span>| {
+                match span {
+                    Some(span) => {
+                        format!("{{\"text\":{},\"start\":{},\"end\":{}}}", json::string(text), span.start, span.end)
+                    }
+                    None => "null".to_string(),
+                }
+            };
+            format!(
+                "{},\"address\":{},\"dest\":{},\"comp\":{},\"jump\":{}}}",
+                item_json("c_instruction", spans.instruction),
+                address,
+                field(dest, spans.dest),
+                field(comp, spans.comp),
+                field(jump, spans.jump)
+            )
+        }
+    }
+}
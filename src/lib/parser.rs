@@ -0,0 +1,302 @@
+//! Module containing the parser for Hack assembly source, usable without an [`crate::Assembler`] or any file I/O,
+//! e.g. by formatters and linters.
+//!
+//! [`parse_source`] parses a whole program and [`parse_line`] a single line, both skipping blank lines, comments, labels and aliases.
+
+use alloc::{ borrow::Cow, format, string::{ String, ToString }, vec, vec::Vec };
+#[cfg(feature = "std")]
+use core::iter;
+#[cfg(feature = "std")]
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::lib::{ encoder, error_codes, instruction::locate_error };
+use crate::{ Diagnostic, Instruction, InstructionSpans, RhasmError, Span };
+
+#[cfg(feature = "std")]
+lazy_static! {
+    pub(crate) static ref INSTRUCTION_REGEX: Regex = instruction_regex();
+}
+
+// The pattern matching every kind of source line, borrowed from the lazy static with std
+// Without std there is no lazy static to keep it in, so callers parsing many lines compile it once and pass it down
+fn compiled_regex() -> Cow<'static, Regex> {
+    #[cfg(feature = "std")]
+    return Cow::Borrowed(&INSTRUCTION_REGEX);
+    #[cfg(not(feature = "std"))]
+    return Cow::Owned(instruction_regex());
+}
+
+// Compile the pattern matching every kind of source line, with a named group for each field
+fn instruction_regex() -> Regex {
+    Regex::new({
+        r"(?x) # Ignore whitespace and allow comments
+    ^(?:
+        @(?P<a_symbol>[a-zA-Z_\.\$:][\w\.\$:]*|\d+) # A-instruction (address or symbol)
+      |
+        \((?P<l_label>[a-zA-Z_\.\$:][\w\.\$:]+)\)   # L-instruction (label)
+      |
+        \.alias\s+(?P<alias_name>[a-zA-Z_\.\$:][\w\.\$:]*)\s+(?P<alias_target>[a-zA-Z_\.\$:][\w\.\$:]*) # Alias directive
+      |
+        (?:
+            (?P<c_dest>[ADM]{1,3})?  # Optional dest part for C-instruction
+            =?
+            (?P<c_comp>[AMD01!+\-&|]+) # Required comp part for C-instruction
+            ;?
+            (?P<c_jump>[A-Z]{3})?   # Optional jump part for C-instruction
+        )
+    )$"
+    }).unwrap()
+}
+
+/// Parse every instruction of a program, without building an [`crate::Assembler`].
+///
+/// ### Returns
+///
+/// Returns every instruction in source order, each with its location without surrounding whitespace and comments.
+/// Blank lines, comments, labels and aliases produce no instruction.
+///
+/// ### Errors
+///
+/// * Returns [`RhasmError::Diagnostics`] with a diagnostic for every line that is not a valid instruction, label or alias,
+///   or uses an unknown mnemonic, in source order.
+///   Labels and A-Instruction constants are not checked, as that is part of assembling the program.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ Instruction, RhasmError, Span };
+/// use rhasm::parser::parse_source;
+///
+/// let program = parse_source("// Add two numbers\n@2\n(LOOP)\n  D=A // load\n").unwrap();
+/// assert_eq!(program, vec![
+///     (Span::new(2, 0, 2), Instruction::AInstruction("2".to_string())),
+///     (Span::new(4, 2, 5), Instruction::CInstruction("D".to_string(), "A".to_string(), "".to_string())),
+/// ]);
+///
+/// let Err(RhasmError::Diagnostics(diagnostics)) = parse_source("@\nD=M\nD=Q\n") else {
+///     panic!("expected both invalid lines to be reported");
+/// };
+/// let lines: Vec<usize> = diagnostics.iter().map(|diagnostic| diagnostic.span.line).collect();
+/// assert_eq!(lines, vec![1, 3]);
+/// ```
+pub fn parse_source(source: &str) -> Result<Vec<(Span, Instruction)>, RhasmError> {
+    let mut instructions = Vec::new();
+    let mut diagnostics = Vec::new();
+    let regex = &compiled_regex();
+    for (index, line) in source.lines().enumerate() {
+        match parse_numbered_line(regex, line, index + 1) {
+            Ok(Some(instruction)) => instructions.push(instruction),
+            Ok(None) => {}
+            Err(RhasmError::Diagnostics(found)) => diagnostics.extend(found),
+            Err(err) => {
+                return Err(err);
+            }
+        }
+    }
+    match diagnostics.is_empty() {
+        true => Ok(instructions),
+        false => Err(RhasmError::Diagnostics(diagnostics)),
+    }
+}
+
+/// Parse a single source line, which may be indented and end in a comment, without building an [`crate::Assembler`].
+///
+/// ### Returns
+///
+/// Returns the instruction on the line, or [`None`] if it is blank, only a comment, a label, or an alias.
+///
+/// ### Errors
+///
+/// * Returns [`RhasmError::Diagnostics`] with a single diagnostic on line 1 pointing at the offending part of the line,
+///   if it is not a valid instruction or uses an unknown mnemonic.
+///   A-Instruction constants are not range checked, as that depends on [`crate::AssemblerConfig::with_constant_overflow`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ parse_line, Instruction, RhasmError };
+///
+/// assert_eq!(parse_line("  @i // counter").unwrap(), Some(Instruction::AInstruction("i".to_string())));
+/// assert_eq!(parse_line("// just a comment").unwrap(), None);
+/// assert_eq!(parse_line("(LOOP)").unwrap(), None);
+///
+/// let Err(RhasmError::Diagnostics(diagnostics)) = parse_line("D=M;JMG") else {
+///     panic!("expected the jump to be rejected");
+/// };
+/// assert_eq!((diagnostics[0].span.start, diagnostics[0].span.end), (4, 7));
+/// ```
+pub fn parse_line(line: &str) -> Result<Option<Instruction>, RhasmError> {
+    parse_numbered_line(&compiled_regex(), line, 1).map(|parsed| parsed.map(|(_, instruction)| instruction))
+}
+
+// Parse the source line numbered `line_number` with the compiled `regex`, returning its instruction along with where it was written
+fn parse_numbered_line(regex: &Regex, line: &str, line_number: usize) -> Result<Option<(Span, Instruction)>, RhasmError> {
+    let code = strip_comment(line);
+    if code.is_empty() {
+        return Ok(None);
+    }
+    let indent = line.len() - line.trim_start().len();
+    let span = Span::new(line_number, indent, indent + code.len());
+    match parse_line_matching(regex, code, span) {
+        Some((ParsedLine::Instruction(instruction), spans)) => {
+            encoder::validate_mnemonics(&instruction).map_err(|err| locate_error(err, &instruction, &spans))?;
+            Ok(Some((span, instruction)))
+        }
+        Some(_) => Ok(None),
+        None => Err(RhasmError::Diagnostics(vec![invalid_line(code, span)])),
+    }
+}
+
+// A single parsed source line, labels and aliases are kept apart as they do not produce an instruction
+// Only the std modules read the names of labels and aliases, the core parser just skips them
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) enum ParsedLine {
+    Instruction(Instruction),
+    Label(String),
+    // `.alias NAME TARGET` declares NAME as another name for the address of TARGET
+    Alias(String, String),
+}
+
+// Remove comments and surrounding whitespace from a source line
+pub(crate) fn strip_comment(line: &str) -> &str {
+    line.split("//").next().unwrap().trim()
+}
+
+// Blanks out the comments of consecutive source lines with spaces, so the columns of the remaining code are unchanged
+// `//` comments are always recognised, with lenient syntax so are `;` and `#` line comments and `/* */` block comments
+// A `;` directly followed by a jump mnemonic, e.g. `0;JMP`, is a jump rather than a comment
+#[cfg(feature = "std")]
+pub(crate) struct CommentStripper {
+    lenient: bool,
+    in_block_comment: bool,
+}
+
+#[cfg(feature = "std")]
+impl CommentStripper {
+    pub(crate) fn new(lenient: bool) -> CommentStripper {
+        CommentStripper { lenient, in_block_comment: false }
+    }
+
+    pub(crate) fn strip(&mut self, line: &str) -> String {
+        let mut out = String::with_capacity(line.len());
+        let mut chars = line.char_indices();
+        while let Some((index, char)) = chars.next() {
+            let rest = &line[index..];
+            if self.in_block_comment {
+                if rest.starts_with("*/") {
+                    chars.next();
+                    out.push_str("  ");
+                    self.in_block_comment = false;
+                } else {
+                    out.extend(iter::repeat_n(' ', char.len_utf8()));
+                }
+                continue;
+            }
+            if rest.starts_with("//") {
+                break;
+            }
+            if self.lenient {
+                if rest.starts_with("/*") {
+                    chars.next();
+                    out.push_str("  ");
+                    self.in_block_comment = true;
+                    continue;
+                }
+                let is_jump = || matches!(
+                    rest.as_bytes().get(1..4),
+                    Some([b'J', first, second]) if first.is_ascii_uppercase() && second.is_ascii_uppercase()
+                );
+                if char == '#' || (char == ';' && !is_jump()) {
+                    break;
+                }
+            }
+            out.push(char);
+        }
+        out
+    }
+}
+
+// Parse a single source line that has already been stripped of comments
+// Returns None if the line is neither a valid instruction nor a label
+#[cfg(feature = "std")]
+pub(crate) fn parse_source_line(line: &str) -> Option<ParsedLine> {
+    parse_line_spanned(line, Span::default()).map(|(parsed, _)| parsed)
+}
+
+// Parse a single source line that has already been stripped of comments, `span` being its location in the source
+// Along with the parsed line, returns the location of each of its fields
+pub(crate) fn parse_line_spanned(line: &str, span: Span) -> Option<(ParsedLine, InstructionSpans)> {
+    parse_line_matching(&compiled_regex(), line, span)
+}
+
+// Parse a single source line like `parse_line_spanned`, matching it with an already compiled `regex`
+fn parse_line_matching(regex: &Regex, line: &str, span: Span) -> Option<(ParsedLine, InstructionSpans)> {
+    let captures = regex.captures(line)?;
+    let field_span = |name: &str| {
+        captures.name(name).map(|field| Span::new(span.line, span.start + field.start(), span.start + field.end()))
+    };
+    let spans = InstructionSpans {
+        instruction: span,
+        dest: field_span("c_dest"),
+        comp: field_span("c_comp"),
+        jump: field_span("c_jump"),
+    };
+    let parsed = if let Some(a_symbol) = captures.name("a_symbol") {
+        ParsedLine::Instruction(Instruction::AInstruction(a_symbol.as_str().to_string()))
+    } else if let Some(c_comp) = captures.name("c_comp") {
+        let c_dest = captures.name("c_dest").map_or("", |m| m.as_str());
+        let c_jump = captures.name("c_jump").map_or("", |m| m.as_str());
+        ParsedLine::Instruction(
+            Instruction::CInstruction(
+                c_dest.to_string(),
+                c_comp.as_str().to_string(),
+                c_jump.to_string()
+            )
+        )
+    } else if let (Some(alias), Some(target)) = (captures.name("alias_name"), captures.name("alias_target")) {
+        ParsedLine::Alias(alias.as_str().to_string(), target.as_str().to_string())
+    } else {
+        ParsedLine::Label(captures.name("l_label")?.as_str().to_string())
+    };
+    Some((parsed, spans))
+}
+
+// Describe why a line that does not parse was rejected, as precisely as possible
+// Malformed A-Instruction constants get a targeted message, anything else is an invalid instruction
+pub(crate) fn invalid_line(line: &str, span: Span) -> Diagnostic {
+    let Some(operand) = line.strip_prefix('@') else {
+        return Diagnostic::new(span, line, "Invalid Instruction").with_code(error_codes::INVALID_INSTRUCTION);
+    };
+    let is_number = |text: &str| !text.is_empty() && text.chars().all(|char| char.is_ascii_digit());
+    let operand_span = Span::new(span.line, span.start + 1, span.end);
+    if operand.is_empty() {
+        Diagnostic::new(span, line, "A-Instruction is missing an address or symbol").with_code(error_codes::MISSING_OPERAND)
+    } else if operand.starts_with(char::is_whitespace) {
+        Diagnostic::new(operand_span, line, "A-Instruction has whitespace after the `@`")
+            .with_code(error_codes::OPERAND_WHITESPACE)
+            .with_help(&format!("write `@{}`", operand.trim_start()))
+    } else if let Some(value) = operand.strip_prefix('-').filter(|value| is_number(value)) {
+        let help = match value.trim_start_matches('0') {
+            "1" => "use `A=-1` to load -1 into A, or `D=-1` and `M=-1` for the other registers".to_string(),
+            _ => format!("load {} and negate it with `A=-A`", value),
+        };
+        Diagnostic::new(operand_span, line, &format!("Negative constant {} can not be loaded by an A-Instruction", operand))
+            .with_note(None, "A-Instructions only load constants from 0 to 32767")
+            .with_help(&help)
+            .with_code(error_codes::NEGATIVE_CONSTANT)
+    } else if let Some(value) = operand.strip_prefix('+').filter(|value| is_number(value)) {
+        Diagnostic::new(operand_span, line, &format!("Constant {} has a sign, A-Instruction constants are written without one", operand))
+            .with_help(&format!("write `@{}`", value))
+            .with_code(error_codes::SIGNED_CONSTANT)
+    } else if operand.starts_with(|char: char| char.is_ascii_digit()) {
+        let digits = operand.len() - operand.trim_start_matches(|char: char| char.is_ascii_digit()).len();
+        Diagnostic::new(operand_span, line, &format!("Malformed constant {}, constants may only contain digits", operand))
+            .with_note(
+                Some(Span::new(span.line, operand_span.start + digits, operand_span.start + digits + 1)),
+                "symbols can not start with a digit"
+            )
+            .with_code(error_codes::MALFORMED_CONSTANT)
+    } else {
+        Diagnostic::new(span, line, "Invalid Instruction").with_code(error_codes::INVALID_INSTRUCTION)
+    }
+}
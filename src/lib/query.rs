@@ -0,0 +1,204 @@
+//! Module for querying a parsed Hack assembly program.
+//!
+//! The [`Program`] struct answers questions such as where a symbol is referenced, where the program jumps to,
+//! and which instructions write to a register. It is meant as the foundation for cross reference reports and refactoring tools.
+
+use crate::lib::{ parser::{ parse_source_line, strip_comment, ParsedLine }, symbol_table::resolve_aliases };
+use crate::{ Instruction, RhasmError, SymbolTable };
+
+/// Enum to represent the contents of a single [`ProgramItem`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItemKind {
+    /// An A- or C-Instruction.
+    Instruction(Instruction),
+    /// A label definition, contains the label name without parentheses.
+    Label(String),
+}
+
+/// A single instruction or label of a [`Program`] together with its location.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramItem {
+    /// The 1-based source line the item was parsed from.
+    pub line: usize,
+    /// The ROM address of the instruction, or the address a label refers to.
+    pub address: u16,
+    /// The instruction or label itself.
+    pub kind: ItemKind,
+}
+
+/// A jumping C-Instruction and where it jumps to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Jump {
+    /// The 1-based source line of the jumping instruction.
+    pub line: usize,
+    /// The ROM address of the jumping instruction.
+    pub address: u16,
+    /// The jump mnemonic, e.g. `JMP` or `JGT`.
+    pub condition: String,
+    /// The symbol loaded into A right before the jump, if the target was loaded symbolically.
+    pub target_symbol: Option<String>,
+    /// The resolved target ROM address.
+    /// [`None`] if the target is computed at runtime or loaded through a variable.
+    pub target: Option<u16>,
+}
+
+/// The registers an instruction can write to, `M` being the memory location addressed by A.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    A,
+    D,
+    M,
+}
+
+/// A parsed program that can be queried for references, jumps and register writes.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::query::{ Program, Register };
+///
+/// let program = Program::parse("(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n").unwrap();
+///
+/// assert_eq!(program.references("LOOP").len(), 2);
+/// assert_eq!(program.jumps()[0].target, Some(0));
+/// assert_eq!(program.writes_to(Register::M)[0].line, 3);
+/// ```
+pub struct Program {
+    items: Vec<ProgramItem>,
+    // Predefined symbols and labels, variables are never allocated while querying
+    symbols: SymbolTable,
+}
+
+impl Program {
+    /// Parse a program from its source text.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Parse`] if a line is neither a valid instruction nor a label.
+    /// * Returns [`RhasmError::SymbolOverflow`] if the program has more instructions than fit in the 16 bit address space.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::RhasmError;
+    /// use rhasm::query::Program;
+    ///
+    /// let source = "D=0\n".repeat(70000);
+    /// assert!(matches!(Program::parse(&source), Err(RhasmError::SymbolOverflow(_))));
+    /// ```
+    pub fn parse(source: &str) -> Result<Program, RhasmError> {
+        let mut items = Vec::new();
+        let mut symbols = SymbolTable::new();
+        let mut aliases = Vec::new();
+        let mut address: u16 = 0;
+        for (index, line) in source.lines().enumerate() {
+            let line = strip_comment(line);
+            if line.is_empty() {
+                continue;
+            }
+            let kind = match parse_source_line(line) {
+                Some(ParsedLine::Instruction(instruction)) => ItemKind::Instruction(instruction),
+                Some(ParsedLine::Label(label)) => {
+                    symbols.define_label(&label, address);
+                    ItemKind::Label(label)
+                }
+                Some(ParsedLine::Alias(alias, target)) => {
+                    aliases.push((alias, target, ()));
+                    continue;
+                }
+                None => {
+                    return Err(RhasmError::Parse { line: index + 1, text: line.to_string() });
+                }
+            };
+            let is_instruction = matches!(kind, ItemKind::Instruction(_));
+            items.push(ProgramItem { line: index + 1, address, kind });
+            if is_instruction {
+                address = address
+                    .checked_add(1)
+                    .ok_or_else(|| RhasmError::SymbolOverflow(line.to_string()))?;
+            }
+        }
+        // Aliases of variables can not be resolved without allocating them, those are left unresolved
+        resolve_aliases(&mut symbols, aliases);
+        Ok(Program { items, symbols })
+    }
+
+    /// All instructions and labels of the program in source order.
+    pub fn items(&self) -> &[ProgramItem] {
+        &self.items
+    }
+
+    /// All label definitions and A-Instructions referring to `symbol`.
+    pub fn references(&self, symbol: &str) -> Vec<&ProgramItem> {
+        self.items
+            .iter()
+            .filter(|item| {
+                match &item.kind {
+                    ItemKind::Label(label) => label == symbol,
+                    ItemKind::Instruction(Instruction::AInstruction(a_symbol)) => a_symbol == symbol,
+                    _ => false,
+                }
+            })
+            .collect()
+    }
+
+    /// All jumping instructions with their targets.
+    ///
+    /// A target is resolved when the instruction right before the jump loads a constant, a label, or a predefined symbol into A.
+    pub fn jumps(&self) -> Vec<Jump> {
+        let mut jumps = Vec::new();
+        let mut previous: Option<&Instruction> = None;
+        for item in &self.items {
+            let ItemKind::Instruction(instruction) = &item.kind else {
+                continue;
+            };
+            if let Instruction::CInstruction(_, _, jump) = instruction {
+                if !jump.is_empty() {
+                    let target_symbol = match previous {
+                        Some(Instruction::AInstruction(symbol)) => Some(symbol.clone()),
+                        _ => None,
+                    };
+                    let target = target_symbol.as_deref().and_then(|symbol| self.resolve(symbol));
+                    jumps.push(Jump {
+                        line: item.line,
+                        address: item.address,
+                        condition: jump.clone(),
+                        target_symbol,
+                        target,
+                    });
+                }
+            }
+            previous = Some(instruction);
+        }
+        jumps
+    }
+
+    /// All instructions writing to `register`.
+    ///
+    /// A-Instructions are included when querying [`Register::A`], as they load their value into A.
+    pub fn writes_to(&self, register: Register) -> Vec<&ProgramItem> {
+        let name = match register {
+            Register::A => 'A',
+            Register::D => 'D',
+            Register::M => 'M',
+        };
+        self.items
+            .iter()
+            .filter(|item| {
+                match &item.kind {
+                    ItemKind::Instruction(Instruction::AInstruction(_)) => register == Register::A,
+                    ItemKind::Instruction(Instruction::CInstruction(dest, _, _)) => dest.contains(name),
+                    ItemKind::Label(_) => false,
+                }
+            })
+            .collect()
+    }
+
+    // Resolve a symbol or constant to an address without allocating variables
+    fn resolve(&self, symbol: &str) -> Option<u16> {
+        if symbol.chars().all(|char| char.is_ascii_digit()) {
+            return symbol.parse().ok();
+        }
+        self.symbols.get(symbol)
+    }
+}
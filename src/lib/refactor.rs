@@ -0,0 +1,227 @@
+//! Module for source level refactorings of Hack assembly programs.
+//!
+//! Refactorings operate on the source text directly, so formatting, blank lines and comments are preserved.
+
+use std::error::Error;
+use crate::lib::{ parser::{ parse_source_line, ParsedLine }, symbol_table::DEFAULT_SYMBOLS };
+use crate::Instruction;
+
+// The parts of a source line that a refactoring may touch
+// `code` is the instruction or label with surrounding whitespace and any comment removed
+struct SourceLine<'s> {
+    indent: &'s str,
+    code: &'s str,
+    rest: &'s str,
+}
+
+// Split a single line (including its line ending) into its indentation, code, and trailing part
+fn split_line(line: &str) -> SourceLine<'_> {
+    let code_end = line.find("//").unwrap_or(line.len());
+    let code_part = &line[..code_end];
+    let indent_len = code_part.len() - code_part.trim_start_matches([' ', '\t']).len();
+    let code = code_part.trim();
+    SourceLine {
+        indent: &line[..indent_len],
+        code,
+        rest: &line[indent_len + code.len()..],
+    }
+}
+
+fn is_valid_symbol(name: &str) -> bool {
+    matches!(
+        parse_source_line(&format!("@{}", name)),
+        Some(ParsedLine::Instruction(Instruction::AInstruction(_)))
+    ) && !name.chars().all(|char| char.is_ascii_digit())
+}
+
+/// Rename a label or variable everywhere it is defined and referenced.
+///
+/// Label definitions (`(old_name)`), A-Instructions (`@old_name`) and both sides of `.alias` directives are renamed,
+/// everything else in the source, including whitespace and comments, is left untouched.
+///
+/// ### Errors
+///
+/// * Returns an error if either name is not a valid symbol, or is a predefined symbol.
+/// * Returns an error if `old_name` does not occur in the source.
+/// * Returns an error if `new_name` is already used in the source, as the two symbols would be merged.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::refactor::rename_symbol;
+///
+/// let source = "(LOOP)\n  @LOOP // back to the start\n  0;JMP\n";
+/// let renamed = rename_symbol(source, "LOOP", "MAIN_LOOP").unwrap();
+/// assert_eq!(renamed, "(MAIN_LOOP)\n  @MAIN_LOOP // back to the start\n  0;JMP\n");
+/// ```
+pub fn rename_symbol(source: &str, old_name: &str, new_name: &str) -> Result<String, Box<dyn Error>> {
+    for name in [old_name, new_name] {
+        if !is_valid_symbol(name) {
+            return Err(format!("{} is not a valid symbol name", name).into());
+        }
+        if DEFAULT_SYMBOLS.iter().any(|(symbol, _)| *symbol == name) {
+            return Err(format!("{} is a predefined symbol", name).into());
+        }
+    }
+
+    let mut out = String::with_capacity(source.len());
+    let mut renamed = 0;
+    for line in source.split_inclusive('\n') {
+        let SourceLine { indent, code, rest } = split_line(line);
+        let new_code = match parse_source_line(code) {
+            Some(ParsedLine::Label(label)) if label == old_name => format!("({})", new_name),
+            Some(ParsedLine::Instruction(Instruction::AInstruction(symbol))) if symbol == old_name => {
+                format!("@{}", new_name)
+            }
+            Some(ParsedLine::Label(label)) if label == new_name => {
+                return Err(format!("{} is already defined as a label", new_name).into());
+            }
+            Some(ParsedLine::Instruction(Instruction::AInstruction(symbol))) if symbol == new_name => {
+                return Err(format!("{} is already used as a symbol", new_name).into());
+            }
+            Some(ParsedLine::Alias(alias, target)) if alias == new_name || target == new_name => {
+                return Err(format!("{} is already used in an alias", new_name).into());
+            }
+            Some(ParsedLine::Alias(alias, target)) if alias == old_name || target == old_name => {
+                let rename = |name: String| if name == old_name { new_name.to_string() } else { name };
+                format!(".alias {} {}", rename(alias), rename(target))
+            }
+            _ => {
+                out.push_str(line);
+                continue;
+            }
+        };
+        renamed += 1;
+        out.push_str(indent);
+        out.push_str(&new_code);
+        out.push_str(rest);
+    }
+
+    if renamed == 0 {
+        return Err(format!("{} is not used in the source", old_name).into());
+    }
+    Ok(out)
+}
+
+/// Insert a label at a ROM address and use it for every load of that address.
+///
+/// The label is inserted on its own line directly before the instruction at `address`,
+/// or at the end of the source if `address` is one past the last instruction.
+/// Every numeric A-Instruction loading `address`, e.g. `@2` or `@002`, is rewritten to `@label`,
+/// use [`insert_jump_label`] to leave the loads that are not followed by a jump alone when some of them are data.
+/// This is useful for gradually re-symbolizing disassembled code.
+///
+/// ### Errors
+///
+/// * Returns an error if `label` is not a valid symbol, is a predefined symbol, or is already used in the source.
+/// * Returns an error if `address` lies beyond the end of the program.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::refactor::insert_label;
+///
+/// let source = "@2\n0;JMP\nD=0\n@2\nD=A\n";
+/// let labelled = insert_label(source, 2, "START").unwrap();
+/// assert_eq!(labelled, "@START\n0;JMP\n(START)\nD=0\n@START\nD=A\n");
+/// ```
+pub fn insert_label(source: &str, address: u16, label: &str) -> Result<String, Box<dyn Error>> {
+    label_address(source, address, label, false)
+}
+
+/// Insert a label at a ROM address and use it only for the jumps to that address.
+///
+/// Works like [`insert_label`], except that a numeric A-Instruction loading `address` is only rewritten
+/// when the following instruction jumps, other loads of the same number are left alone as data rather than jump targets.
+///
+/// ### Errors
+///
+/// * Returns an error if `label` is not a valid symbol, is a predefined symbol, or is already used in the source.
+/// * Returns an error if `address` lies beyond the end of the program.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::refactor::insert_jump_label;
+///
+/// let source = "@2\n0;JMP\nD=0\n@2\nD=A\n";
+/// let labelled = insert_jump_label(source, 2, "START").unwrap();
+/// assert_eq!(labelled, "@START\n0;JMP\n(START)\nD=0\n@2\nD=A\n");
+/// ```
+pub fn insert_jump_label(source: &str, address: u16, label: &str) -> Result<String, Box<dyn Error>> {
+    label_address(source, address, label, true)
+}
+
+// Insert `label` before the instruction at `address` and rewrite the numeric loads of it, only those followed by a jump
+// if `jumps_only` is set
+fn label_address(source: &str, address: u16, label: &str, jumps_only: bool) -> Result<String, Box<dyn Error>> {
+    if !is_valid_symbol(label) || DEFAULT_SYMBOLS.iter().any(|(symbol, _)| *symbol == label) {
+        return Err(format!("{} is not a valid label name", label).into());
+    }
+
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let parsed: Vec<Option<ParsedLine>> = lines
+        .iter()
+        .map(|line| parse_source_line(split_line(line).code))
+        .collect();
+    let mut instruction_lines: Vec<usize> = Vec::new();
+    for (index, line) in parsed.iter().enumerate() {
+        match line {
+            Some(ParsedLine::Instruction(Instruction::AInstruction(symbol))) |
+            Some(ParsedLine::Label(symbol)) if symbol == label => {
+                return Err(format!("{} is already used in the source", label).into());
+            }
+            Some(ParsedLine::Alias(alias, target)) if alias == label || target == label => {
+                return Err(format!("{} is already used in the source", label).into());
+            }
+            Some(ParsedLine::Instruction(_)) => instruction_lines.push(index),
+            _ => {}
+        }
+    }
+    if (address as usize) > instruction_lines.len() {
+        return Err(
+            format!("Address {} is beyond the end of the program ({} instructions)", address, instruction_lines.len()).into()
+        );
+    }
+
+    let loads_address = |symbol: &str| {
+        symbol.chars().all(|char| char.is_ascii_digit()) && symbol.parse::<u16>().ok() == Some(address)
+    };
+    let mut loads: Vec<usize> = Vec::new();
+    for (position, &index) in instruction_lines.iter().enumerate() {
+        let Some(ParsedLine::Instruction(Instruction::AInstruction(symbol))) = &parsed[index] else {
+            continue;
+        };
+        let jumps = || matches!(
+            instruction_lines.get(position + 1).map(|&next| &parsed[next]),
+            Some(Some(ParsedLine::Instruction(Instruction::CInstruction(_, _, jump)))) if !jump.is_empty()
+        );
+        if loads_address(symbol) && (!jumps_only || jumps()) {
+            loads.push(index);
+        }
+    }
+
+    let insert_at = instruction_lines.get(address as usize).copied();
+    let mut out = String::with_capacity(source.len() + label.len() * (loads.len() + 1));
+    for (index, line) in lines.iter().enumerate() {
+        if Some(index) == insert_at {
+            out.push_str(&format!("({})", label));
+            out.push_str(if line.ends_with("\r\n") { "\r\n" } else { "\n" });
+        }
+        if loads.contains(&index) {
+            let SourceLine { indent, rest, .. } = split_line(line);
+            out.push_str(indent);
+            out.push_str(&format!("@{}", label));
+            out.push_str(rest);
+        } else {
+            out.push_str(line);
+        }
+    }
+    if insert_at.is_none() {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&format!("({})\n", label));
+    }
+    Ok(out)
+}
@@ -0,0 +1,223 @@
+//! Module for packing several assembled programs into a single ROM image file, and loading them back.
+//!
+//! An image starts with a header indexing its programs, followed by the machine words of every program:
+//!
+//! * The magic bytes `RHPK` and a format version byte, currently `1`.
+//! * The number of programs as a little endian `u16`.
+//! * For each program, in order: the length of its name as a `u8`, the UTF-8 name,
+//!   and the offset and length of its words within the data section as little endian `u32`s.
+//! * The data section, every word stored as a little endian `u16`.
+
+use std::io;
+use crate::RhasmError;
+
+// Identifies a file as a rhasm ROM image
+const MAGIC: &[u8; 4] = b"RHPK";
+
+// Bumped whenever the layout changes, images of other versions are rejected
+const VERSION: u8 = 1;
+
+// The most words a program can have, the size of the Hack ROM
+const ROM_SIZE: usize = 1 << 15;
+
+/// A single program stored in a [`RomImage`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackedProgram {
+    /// The name the program is looked up by, e.g. the stem of its source file.
+    pub name: String,
+    /// The machine words of the program, in ROM order.
+    pub words: Vec<u16>,
+}
+
+/// A collection of named programs that can be written to and loaded from a single file.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::rom_image::RomImage;
+///
+/// let mut image = RomImage::new();
+/// image.add("Add", vec![2, 0b1110110000010000]).unwrap();
+/// image.add("Loop", vec![0, 0b1110101010000111]).unwrap();
+///
+/// let bytes = image.to_bytes();
+/// let loaded = RomImage::from_bytes(&bytes).unwrap();
+///
+/// assert_eq!(loaded.names().collect::<Vec<_>>(), vec!["Add", "Loop"]);
+/// assert_eq!(loaded.get("Loop"), Some(&[0, 0b1110101010000111][..]));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomImage {
+    programs: Vec<PackedProgram>,
+}
+
+impl RomImage {
+    /// Create an empty image.
+    pub fn new() -> RomImage {
+        RomImage::default()
+    }
+
+    /// Add a program to the end of the image.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Io`] of kind [`io::ErrorKind::InvalidInput`] if the name is empty, longer than 255 bytes,
+    ///   or already used by another program, if the program does not fit in the 32K ROM,
+    ///   or if the image already holds the most programs its index can list, 65535.
+    pub fn add(&mut self, name: &str, words: Vec<u16>) -> Result<(), RhasmError> {
+        self.check_program(name, words.len()).map_err(invalid_input)?;
+        if self.programs.len() == usize::from(u16::MAX) {
+            return Err(invalid_input(format!("Image can not hold more than {} programs", u16::MAX)));
+        }
+        self.programs.push(PackedProgram { name: name.to_string(), words });
+        Ok(())
+    }
+
+    // Check that a program of `len` words called `name` can be added, both when adding and when loading an image
+    fn check_program(&self, name: &str, len: usize) -> Result<(), String> {
+        if name.is_empty() || name.len() > usize::from(u8::MAX) {
+            return Err(format!("Program name {:?} must be between 1 and 255 bytes long", name));
+        }
+        if self.get(name).is_some() {
+            return Err(format!("Image already contains a program named {}", name));
+        }
+        if len > ROM_SIZE {
+            return Err(format!("Program {} has {} words, but the Hack ROM only holds {}", name, len, ROM_SIZE));
+        }
+        Ok(())
+    }
+
+    /// The programs of the image, in the order they were added.
+    pub fn programs(&self) -> &[PackedProgram] {
+        &self.programs
+    }
+
+    /// The names of the programs in the image, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.programs.iter().map(|program| program.name.as_str())
+    }
+
+    /// The words of the program called `name`, if the image contains one.
+    pub fn get(&self, name: &str) -> Option<&[u16]> {
+        self.programs
+            .iter()
+            .find(|program| program.name == name)
+            .map(|program| program.words.as_slice())
+    }
+
+    /// Encode the image in the format described in the [module documentation](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        // `add` caps the number of programs and the lengths of their names and words, so the casts can not truncate
+        bytes.extend_from_slice(&(self.programs.len() as u16).to_le_bytes());
+        let mut offset: u32 = 0;
+        for program in &self.programs {
+            bytes.push(program.name.len() as u8);
+            bytes.extend_from_slice(program.name.as_bytes());
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&(program.words.len() as u32).to_le_bytes());
+            offset += program.words.len() as u32;
+        }
+        for word in self.programs.iter().flat_map(|program| &program.words) {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Load an image written by [`RomImage::to_bytes`].
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Io`] of kind [`io::ErrorKind::InvalidData`] if `bytes` is not an image of this version,
+    ///   or an index entry points outside the data section.
+    /// * Returns [`RhasmError::Io`] of kind [`io::ErrorKind::InvalidData`] if a program breaks the rules of [`RomImage::add`],
+    ///   i.e. its name is empty or used twice, or it does not fit in the 32K ROM.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::{ rom_image::RomImage, RhasmError };
+    ///
+    /// let mut image = RomImage::new();
+    /// image.add("Add", vec![2, 0b1110110000010000]).unwrap();
+    /// let mut bytes = image.to_bytes();
+    ///
+    /// // An index entry claiming more words than the ROM holds is rejected rather than read
+    /// let len_at = bytes.len() - 2 * 2 - 4;
+    /// bytes[len_at..len_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    /// let Err(RhasmError::Io(err)) = RomImage::from_bytes(&bytes) else {
+    ///     panic!("expected the oversized program to be rejected");
+    /// };
+    /// assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<RomImage, RhasmError> {
+        let mut reader = ByteReader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid_data("Not a rhasm ROM image".to_string()));
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(invalid_data(format!("Unsupported ROM image version {}, expected {}", version, VERSION)));
+        }
+        let count = u16::from_le_bytes(reader.array()?);
+        let mut index = Vec::with_capacity(usize::from(count));
+        for _ in 0..count {
+            let name_len = usize::from(reader.take(1)?[0]);
+            let name = String::from_utf8(reader.take(name_len)?.to_vec())
+                .map_err(|_| invalid_data("Program name is not valid UTF-8".to_string()))?;
+            let offset = u32::from_le_bytes(reader.array()?);
+            let len = u32::from_le_bytes(reader.array()?);
+            index.push((name, offset, len));
+        }
+        let data = &bytes[reader.position..];
+        let mut image = RomImage::new();
+        for (name, offset, len) in index {
+            let len = usize::try_from(len).unwrap_or(usize::MAX);
+            image.check_program(&name, len).map_err(invalid_data)?;
+            // The offset comes from untrusted bytes, so the range is computed with checked arithmetic
+            let range = usize::try_from(offset)
+                .ok()
+                .and_then(|offset| Some(offset.checked_mul(2)?..offset.checked_add(len)?.checked_mul(2)?));
+            let Some(program) = range.and_then(|range| data.get(range)) else {
+                return Err(invalid_data(format!("Program {} lies outside the image's data", name)));
+            };
+            let words = program
+                .chunks_exact(2)
+                .map(|word| u16::from_le_bytes([word[0], word[1]]))
+                .collect();
+            image.programs.push(PackedProgram { name, words });
+        }
+        Ok(image)
+    }
+}
+
+// Reads the header of an image, failing on truncated input
+struct ByteReader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+}
+
+impl<'b> ByteReader<'b> {
+    fn take(&mut self, len: usize) -> Result<&'b [u8], RhasmError> {
+        let taken = self.bytes
+            .get(self.position..self.position + len)
+            .ok_or_else(|| invalid_data("ROM image header is truncated".to_string()))?;
+        self.position += len;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RhasmError> {
+        // `take` returns exactly N bytes, so the conversion can not fail
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
+
+fn invalid_input(message: String) -> RhasmError {
+    RhasmError::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+fn invalid_data(message: String) -> RhasmError {
+    RhasmError::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
@@ -0,0 +1,363 @@
+//! Module for keeping a program assembled while it is being edited, e.g. by a language server reporting problems as the user types.
+//!
+//! An [`AssemblySession`] parses every source line once and keeps the result along with the program's symbol table.
+//! Replacing a range of lines only parses the new lines: the instructions below the edit are moved to their new addresses,
+//! and the symbol table is only rebuilt if the edit added, removed or moved a label.
+//! The diagnostics are found once per edit, however often they are asked for.
+
+use std::{ collections::HashMap, ops::Range, sync::OnceLock };
+use crate::lib::{
+    assembler::{ duplicate_label_error, rom_overflow, unresolved_alias, variable_overflow, DATA_SEGMENT_END, ROM_SIZE },
+    encoder,
+    instruction::locate_error,
+    parser::{ invalid_line, parse_line_spanned, strip_comment, ParsedLine },
+    symbol_table::resolve_aliases,
+    warnings,
+};
+use crate::{ Diagnostic, Instruction, InstructionSpans, RhasmError, Span, SymbolTable };
+
+// A single source line as parsed when it was added
+// Every span is parsed on line 1 and moved to the current line number of the line when reported
+// Instructions are kept by the session in address order, the line only holds the problem found in the instruction itself,
+// e.g. an unknown mnemonic
+enum LineKind {
+    Blank,
+    Instruction(Option<Diagnostic>),
+    Label(String, Span),
+    Alias(String, String, Span),
+    // Neither an instruction nor a label, holding why it was rejected
+    Invalid(Diagnostic),
+}
+
+struct SessionLine {
+    text: String,
+    kind: LineKind,
+    // The number of instructions above the line, i.e. the ROM address of its instruction or the address of its label
+    address: usize,
+}
+
+impl SessionLine {
+    // Parse the 1-based source `line`, returning the instruction on it along with where its fields are
+    fn parse(text: &str, line: usize, address: usize) -> (SessionLine, Option<(Instruction, InstructionSpans)>) {
+        let code = strip_comment(text);
+        let indent = text.len() - text.trim_start().len();
+        let span = Span::new(1, indent, indent + code.len());
+        if code.is_empty() {
+            return (SessionLine { text: text.to_string(), kind: LineKind::Blank, address }, None);
+        }
+        let mut instruction = None;
+        let kind = match parse_line_spanned(code, span) {
+            Some((ParsedLine::Instruction(parsed), spans)) => {
+                let problem = instruction_problem(&parsed, &spans);
+                instruction = Some((parsed, spans_on_line(&spans, line)));
+                LineKind::Instruction(problem)
+            }
+            Some((ParsedLine::Label(label), _)) => LineKind::Label(label, span),
+            Some((ParsedLine::Alias(alias, target), _)) => LineKind::Alias(alias, target, span),
+            None => LineKind::Invalid(invalid_line(code, span)),
+        };
+        (SessionLine { text: text.to_string(), kind, address }, instruction)
+    }
+
+    fn is_instruction(&self) -> bool {
+        matches!(self.kind, LineKind::Instruction(..))
+    }
+
+    // Whether the line affects the symbol table
+    fn defines_symbol(&self) -> bool {
+        matches!(self.kind, LineKind::Label(..) | LineKind::Alias(..))
+    }
+}
+
+// The problem the second pass would find in an instruction regardless of the rest of the program, if any
+fn instruction_problem(instruction: &Instruction, spans: &InstructionSpans) -> Option<Diagnostic> {
+    let err = match instruction {
+        Instruction::AInstruction(constant) if encoder::is_out_of_range(constant) => RhasmError::AddressOutOfRange(constant.clone()),
+        Instruction::AInstruction(_) => {
+            return None;
+        }
+        Instruction::CInstruction(..) => encoder::validate_mnemonics(instruction).err()?,
+    };
+    match locate_error(err, instruction, spans) {
+        RhasmError::Diagnostics(mut diagnostics) => diagnostics.pop(),
+        _ => None,
+    }
+}
+
+// Move a span parsed on line 1 to `line`
+fn on_line(span: Span, line: usize) -> Span {
+    Span { line, ..span }
+}
+
+fn spans_on_line(spans: &InstructionSpans, line: usize) -> InstructionSpans {
+    InstructionSpans {
+        instruction: on_line(spans.instruction, line),
+        dest: spans.dest.map(|span| on_line(span, line)),
+        comp: spans.comp.map(|span| on_line(span, line)),
+        jump: spans.jump.map(|span| on_line(span, line)),
+    }
+}
+
+// Problems found in a single line only point within that line
+fn diagnostic_on_line(diagnostic: &Diagnostic, line: usize) -> Diagnostic {
+    let mut diagnostic = diagnostic.clone();
+    diagnostic.span.line = line;
+    for note in &mut diagnostic.notes {
+        if let Some(span) = note.span.as_mut() {
+            span.line = line;
+        }
+    }
+    diagnostic
+}
+
+/// A program kept parsed between edits, so its diagnostics stay cheap to recompute on large files.
+///
+/// Unlike the [`crate::Assembler`], a session never fails to build: problems in the source are reported by [`AssemblySession::diagnostics`],
+/// and the source can be edited with [`AssemblySession::replace_lines`] until they are fixed.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::session::AssemblySession;
+///
+/// let mut session = AssemblySession::new("@R0\nM=1\n(END)\n@END\n0;JMP\n");
+/// assert_eq!(session.symbol_table().get("END"), Some(2));
+///
+/// // Insert two instructions above the label, moving it
+/// session.replace_lines(3..3, "D=0\nD;JMG\n");
+/// assert_eq!(session.symbol_table().get("END"), Some(4));
+///
+/// let diagnostics = session.diagnostics();
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].span.line, 4);
+///
+/// // Fix the typo, which leaves the addresses as they are
+/// session.replace_lines(4..5, "D;JGT");
+/// assert!(session.diagnostics().is_empty());
+/// assert_eq!(session.machine_code().unwrap()[3], "1110001100000001");
+/// ```
+pub struct AssemblySession {
+    lines: Vec<SessionLine>,
+    // The instructions of the program and their locations in the current source, indexed by ROM address
+    instructions: Vec<Instruction>,
+    spans: Vec<InstructionSpans>,
+    // Predefined symbols, labels and aliases, variables are only allocated by `machine_code`
+    symbol_table: SymbolTable,
+    // Found on first use after an edit, and cleared by every edit
+    diagnostics: OnceLock<Vec<Diagnostic>>,
+}
+
+impl AssemblySession {
+    /// Parse `source` into a new session.
+    pub fn new(source: &str) -> AssemblySession {
+        let mut session = AssemblySession {
+            lines: Vec::new(),
+            instructions: Vec::new(),
+            spans: Vec::new(),
+            symbol_table: SymbolTable::new(),
+            diagnostics: OnceLock::new(),
+        };
+        session.replace_lines(1..1, source);
+        session
+    }
+
+    /// Replace the 1-based source lines `lines.start` up to, but not including, `lines.end` with the lines of `text`.
+    ///
+    /// An empty range inserts `text` before `lines.start`, and an empty `text` deletes the lines.
+    /// Ranges reaching past the last line are cut short at the end of the source, so `n + 1..n + 1` appends to a source of `n` lines.
+    pub fn replace_lines(&mut self, lines: Range<usize>, text: &str) {
+        let start = lines.start.saturating_sub(1).min(self.lines.len());
+        let end = lines.end.saturating_sub(1).clamp(start, self.lines.len());
+
+        self.diagnostics = OnceLock::new();
+
+        let first_address = self.lines.get(start).map_or(self.instructions.len(), |line| line.address);
+        let mut address = first_address;
+        let mut added = Vec::new();
+        let mut added_instructions = Vec::new();
+        let mut added_spans = Vec::new();
+        for (offset, text) in text.lines().enumerate() {
+            let (line, instruction) = SessionLine::parse(text, start + offset + 1, address);
+            if let Some((instruction, spans)) = instruction {
+                added_instructions.push(instruction);
+                added_spans.push(spans);
+                address += 1;
+            }
+            added.push(line);
+        }
+        let added_count = added.len();
+        let removed: Vec<SessionLine> = self.lines.splice(start..end, added).collect();
+
+        let removed_instructions = removed.iter().filter(|line| line.is_instruction()).count();
+        let added_instructions_count = added_instructions.len();
+        let replaced = first_address..first_address + removed_instructions;
+        self.instructions.splice(replaced.clone(), added_instructions);
+        self.spans.splice(replaced, added_spans);
+        // The instructions below the edit keep their spans, only moved to their new line numbers
+        if added_count != removed.len() {
+            for spans in &mut self.spans[first_address + added_instructions_count..] {
+                *spans = spans_on_line(spans, spans.instruction.line + added_count - removed.len());
+            }
+        }
+
+        // Only the addresses below the edit change, and only if it changed the number of instructions
+        let mut symbols_changed = removed.iter().chain(&self.lines[start..start + added_count]).any(SessionLine::defines_symbol);
+        if added_instructions_count != removed_instructions {
+            for line in &mut self.lines[start + added_count..] {
+                line.address = line.address + added_instructions_count - removed_instructions;
+                symbols_changed |= matches!(line.kind, LineKind::Label(..));
+            }
+        }
+        if symbols_changed {
+            self.rebuild_symbol_table();
+        }
+    }
+
+    // Define every label at its current address and resolve the aliases again
+    fn rebuild_symbol_table(&mut self) {
+        let mut symbol_table = SymbolTable::new();
+        let mut aliases = Vec::new();
+        for line in &self.lines {
+            match &line.kind {
+                LineKind::Label(label, _) => {
+                    if let Ok(address) = u16::try_from(line.address) {
+                        symbol_table.define_label(label, address);
+                    }
+                }
+                LineKind::Alias(alias, target, _) => aliases.push((alias.clone(), target.clone(), ())),
+                _ => {}
+            }
+        }
+        resolve_aliases(&mut symbol_table, aliases);
+        self.symbol_table = symbol_table;
+    }
+
+    /// The current source, every line followed by a newline.
+    pub fn source(&self) -> String {
+        self.lines.iter().map(|line| format!("{}\n", line.text)).collect()
+    }
+
+    /// The number of source lines.
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The ROM address of the instruction on the 1-based `line`, or the address the label defined on it refers to.
+    /// [`None`] if the line holds neither, or is past the end of the source.
+    pub fn address_of_line(&self, line: usize) -> Option<u16> {
+        let line = self.lines.get(line.checked_sub(1)?)?;
+        match line.kind {
+            LineKind::Instruction(..) | LineKind::Label(..) => u16::try_from(line.address).ok(),
+            _ => None,
+        }
+    }
+
+    /// The predefined symbols, labels and aliases of the program, without its variables.
+    pub fn symbol_table(&self) -> &SymbolTable {
+        &self.symbol_table
+    }
+
+    /// Every problem in the current source, in source order.
+    ///
+    /// Reports the same errors as the first pass of the [`crate::Assembler`], along with invalid constants,
+    /// and the warnings from [`crate::warnings`]. Style checks are not run.
+    /// The problems are only found again after the source is edited, further calls return the same ones.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::session::AssemblySession;
+    ///
+    /// // One variable more than fits between RAM[16] and the screen memory map
+    /// let source: String = (0..16369).map(|index| format!("@var{}\nM=0\n", index)).collect();
+    /// let session = AssemblySession::new(&source);
+    ///
+    /// let overflow = session.diagnostics().iter().find(|diagnostic| diagnostic.is_error()).unwrap();
+    /// assert_eq!(overflow.code.as_deref(), Some("E0014"));
+    /// assert_eq!(overflow.span.line, 16369 * 2 - 1);
+    /// assert!(session.machine_code().is_err());
+    /// ```
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.diagnostics.get_or_init(|| self.find_diagnostics())
+    }
+
+    // Check the whole program, borrowing the instructions kept by the session
+    fn find_diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut labels: Vec<(String, Span)> = Vec::new();
+        let mut first_definitions: HashMap<&str, Span> = HashMap::new();
+        let mut aliases = Vec::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            let number = index + 1;
+            match &line.kind {
+                LineKind::Blank => {}
+                LineKind::Instruction(problem) => {
+                    if let Some(problem) = problem {
+                        diagnostics.push(diagnostic_on_line(problem, number));
+                    }
+                }
+                LineKind::Label(label, span) => {
+                    let span = on_line(*span, number);
+                    match first_definitions.get(label.as_str()) {
+                        Some(first) => diagnostics.push(duplicate_label_error(label, *first, span, strip_comment(&line.text))),
+                        None => {
+                            first_definitions.insert(label, span);
+                            labels.push((label.clone(), span));
+                        }
+                    }
+                }
+                LineKind::Alias(alias, target, span) => aliases.push((alias.clone(), target.clone(), on_line(*span, number))),
+                LineKind::Invalid(problem) => diagnostics.push(diagnostic_on_line(problem, number)),
+            }
+        }
+        if let Some(overflow) = self.spans.get(ROM_SIZE) {
+            let text = self.instructions[ROM_SIZE].to_string();
+            diagnostics.push(rom_overflow(self.instructions.len(), overflow.instruction, &text));
+        }
+        let mut resolved = Vec::new();
+        for (alias, target, span) in aliases {
+            match self.symbol_table.contains(&target) {
+                true => resolved.push((alias, target)),
+                false => diagnostics.push(unresolved_alias(&alias, &target, span)),
+            }
+        }
+        if let Some(overflow) = variable_overflow(&self.instructions, &self.spans, &self.symbol_table, DATA_SEGMENT_END, false) {
+            diagnostics.push(overflow);
+        }
+        let facts = warnings::ProgramFacts {
+            instructions: &self.instructions,
+            spans: &self.spans,
+            labels: &labels,
+            aliases: &resolved,
+            symbol_table: &self.symbol_table,
+        };
+        diagnostics.extend(warnings::check(&facts));
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.span.line, diagnostic.span.start));
+        diagnostics
+    }
+
+    /// Encode the current source, allocating its variables from RAM[16] onwards.
+    ///
+    /// ### Returns
+    ///
+    /// Returns every instruction as 16 binary digits, as [`crate::Assembler::get_next_encoded_instruction`] does.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Diagnostics`] with every error in [`AssemblySession::diagnostics`], if there are any.
+    /// * Returns [`RhasmError::Diagnostics`] pointing at the first variable that does not fit in 15 bits.
+    pub fn machine_code(&self) -> Result<Vec<String>, RhasmError> {
+        let errors: Vec<Diagnostic> = self.diagnostics().iter().filter(|diagnostic| diagnostic.is_error()).cloned().collect();
+        if !errors.is_empty() {
+            return Err(RhasmError::Diagnostics(errors));
+        }
+        let mut symbol_table = self.symbol_table.clone();
+        let mut encoded = Vec::with_capacity(self.instructions.len());
+        for (instruction, spans) in self.instructions.iter().zip(&self.spans) {
+            let instruction_code = encoder::encode_instruction(instruction, &mut symbol_table)
+                .map_err(|err| locate_error(err, instruction, spans))?;
+            encoded.push(instruction_code);
+        }
+        Ok(encoded)
+    }
+}
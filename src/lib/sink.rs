@@ -0,0 +1,115 @@
+//! Module for the destinations encoded instructions can be sent to besides the [`crate::Assembler`]'s writer.
+//!
+//! An [`OutputSink`] receives every machine word as it is written, e.g. to stream a program to an attached emulator
+//! while the `.hack` file is written. Several sinks can be combined with [`OutputSink::tee`].
+
+use std::io::Write;
+use crate::{ MachineCodeFormat, RhasmError };
+
+/// A destination for the machine words of an assembled program, see [`crate::AssemblerConfig::with_sink`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use rhasm::sink::TextSink;
+/// use std::io::Cursor;
+///
+/// let mut in_file = Cursor::new("@2\nD=A\n");
+/// let mut out_file = Cursor::new(Vec::new());
+/// let mut words: Vec<u16> = Vec::new();
+/// let mut hex: Vec<u8> = Vec::new();
+/// let mut sink = (&mut words).tee(TextSink::new(&mut hex, MachineCodeFormat::Hex));
+///
+/// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_sink(&mut sink);
+/// let mut assembler = Assembler::build(config).unwrap();
+/// assembler.advance_to_end().unwrap();
+/// drop(assembler);
+/// drop(sink);
+///
+/// assert_eq!(words, vec![2, 0b1110110000010000]);
+/// assert_eq!(String::from_utf8(hex).unwrap(), "0002\nec10\n");
+/// ```
+pub trait OutputSink {
+    /// Receive the next machine word of the program.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Io`] if the word can not be delivered, which stops the assembler.
+    fn write_word(&mut self, word: u16) -> Result<(), RhasmError>;
+
+    /// Deliver any words held back by the sink, called whenever the assembler finishes writing a batch of words.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Io`] if the words can not be delivered.
+    fn flush(&mut self) -> Result<(), RhasmError> {
+        Ok(())
+    }
+
+    /// Send every word to both `self` and `other`, in that order.
+    fn tee<S: OutputSink>(self, other: S) -> Tee<Self, S> where Self: Sized {
+        Tee { first: self, second: other }
+    }
+}
+
+impl<T: OutputSink + ?Sized> OutputSink for &mut T {
+    fn write_word(&mut self, word: u16) -> Result<(), RhasmError> {
+        (**self).write_word(word)
+    }
+
+    fn flush(&mut self) -> Result<(), RhasmError> {
+        (**self).flush()
+    }
+}
+
+/// Collects the words in memory.
+impl OutputSink for Vec<u16> {
+    fn write_word(&mut self, word: u16) -> Result<(), RhasmError> {
+        self.push(word);
+        Ok(())
+    }
+}
+
+/// Two sinks receiving the same words, made by [`OutputSink::tee`].
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: OutputSink, B: OutputSink> OutputSink for Tee<A, B> {
+    fn write_word(&mut self, word: u16) -> Result<(), RhasmError> {
+        self.first.write_word(word)?;
+        self.second.write_word(word)
+    }
+
+    fn flush(&mut self) -> Result<(), RhasmError> {
+        self.first.flush()?;
+        self.second.flush()
+    }
+}
+
+/// Writes every word on its own line in a [`MachineCodeFormat`], like the [`crate::Assembler`]'s writer, e.g. to a socket.
+pub struct TextSink<W: Write> {
+    writer: W,
+    format: MachineCodeFormat,
+}
+
+impl<W: Write> TextSink<W> {
+    /// Create a sink writing to `writer` in `format`.
+    pub fn new(writer: W, format: MachineCodeFormat) -> TextSink<W> {
+        TextSink { writer, format }
+    }
+}
+
+impl<W: Write> OutputSink for TextSink<W> {
+    fn write_word(&mut self, word: u16) -> Result<(), RhasmError> {
+        writeln!(self.writer, "{}", self.format.format_word(word))?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), RhasmError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,503 @@
+//! Module containing the symbol table used to resolve labels, variables, and predefined symbols.
+
+use alloc::{ string::{ String, ToString }, vec::Vec };
+// Without std there is no HashMap, symbols are kept ordered by name instead
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as HashMap;
+#[cfg(feature = "std")]
+use std::{ collections::HashMap, io::{ self, Write } };
+#[cfg(feature = "std")]
+use crate::lib::json;
+use crate::RhasmError;
+
+/// Predefined symbols and their addresses as per the Hack Assembly Language Specification.
+pub(crate) const DEFAULT_SYMBOLS: [(&str, u16); 23] = [
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SCREEN", 16384),
+    ("KBD", 24576),
+];
+
+// Starting address for automatically allocated variables
+const VARIABLE_BASE: u16 = 16;
+
+// A-Instructions only have 15 bits for their value, so variables must stay below this
+const ADDRESS_LIMIT: u16 = 1 << 15;
+
+/// Enum to represent where a symbol's address came from.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SymbolKind {
+    /// One of the symbols defined by the Hack specification, e.g. `SP`, `R0`-`R15`, `SCREEN`, and `KBD`.
+    Predefined,
+    /// A label declared with `(LABEL)`, its address is a ROM address.
+    Label,
+    /// A variable that was automatically allocated a RAM address on first use.
+    Variable,
+}
+
+impl SymbolKind {
+    // The name of the kind in symbol files
+    #[cfg(feature = "std")]
+    fn name(self) -> &'static str {
+        match self {
+            SymbolKind::Predefined => "predefined",
+            SymbolKind::Label => "label",
+            SymbolKind::Variable => "variable",
+        }
+    }
+
+    // The kind named in a symbol file
+    #[cfg(feature = "std")]
+    fn from_name(name: &str) -> Option<SymbolKind> {
+        match name {
+            "predefined" => Some(SymbolKind::Predefined),
+            "label" => Some(SymbolKind::Label),
+            "variable" => Some(SymbolKind::Variable),
+            _ => None,
+        }
+    }
+}
+
+/// Enum to represent the format a [`SymbolTable`] is written in, see [`SymbolTable::write_symbols`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymbolFileFormat {
+    /// One `KIND NAME ADDRESS` line per symbol, e.g. `label LOOP 4`, the default.
+    #[default]
+    Sym,
+    /// A single JSON object mapping each kind to an object of names and addresses,
+    /// e.g. `{"predefined":{},"labels":{"LOOP":4},"variables":{"i":16}}`.
+    Json,
+}
+
+/// Struct to represent the symbol table of a program along with the allocator for its variables.
+///
+/// A new table already contains all predefined symbols, labels are added during the first pass,
+/// and variables are allocated from address 16 onwards as they are first used.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ SymbolKind, SymbolTable };
+///
+/// let mut symbols = SymbolTable::new();
+/// symbols.define_label("LOOP", 4);
+/// let counter = symbols.allocate_variable("counter").unwrap();
+///
+/// assert_eq!(counter, 16);
+/// assert_eq!(symbols.next_free_address(), 17);
+/// assert_eq!(symbols.kind("LOOP"), Some(SymbolKind::Label));
+/// assert!(symbols.is_predefined("SCREEN"));
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolTable {
+    symbols: HashMap<String, (u16, SymbolKind)>,
+    variable_base: u16,
+    next_variable: u16,
+}
+
+impl SymbolTable {
+    /// Create a symbol table containing only the predefined symbols.
+    pub fn new() -> SymbolTable {
+        let symbols = DEFAULT_SYMBOLS.iter()
+            .map(|(symbol, address)| (symbol.to_string(), (*address, SymbolKind::Predefined)))
+            .collect();
+        SymbolTable {
+            symbols,
+            variable_base: VARIABLE_BASE,
+            next_variable: VARIABLE_BASE,
+        }
+    }
+
+    /// Create a symbol table containing only the predefined symbols, allocating variables from `base` onwards.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::SymbolTable;
+    ///
+    /// let mut symbols = SymbolTable::with_variable_base(1024);
+    /// assert_eq!(symbols.allocate_variable("counter").unwrap(), 1024);
+    /// ```
+    pub fn with_variable_base(base: u16) -> SymbolTable {
+        SymbolTable {
+            variable_base: base,
+            next_variable: base,
+            ..SymbolTable::new()
+        }
+    }
+
+    /// Get the address of a symbol, or [`None`] if it is not in the table.
+    pub fn get(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).map(|(address, _)| *address)
+    }
+
+    /// Get the address and kind of a symbol, or [`None`] if it is not in the table.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::{ SymbolKind, SymbolTable };
+    ///
+    /// let symbols = SymbolTable::new();
+    /// assert_eq!(symbols.lookup("KBD"), Some((24576, SymbolKind::Predefined)));
+    /// assert_eq!(symbols.lookup("LOOP"), None);
+    /// ```
+    pub fn lookup(&self, name: &str) -> Option<(u16, SymbolKind)> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Check if a symbol is in the table.
+    pub fn contains(&self, name: &str) -> bool {
+        self.symbols.contains_key(name)
+    }
+
+    /// Get the kind of a symbol, or [`None`] if it is not in the table.
+    pub fn kind(&self, name: &str) -> Option<SymbolKind> {
+        self.symbols.get(name).map(|(_, kind)| *kind)
+    }
+
+    /// Check if a symbol is one of the predefined symbols of the Hack specification.
+    pub fn is_predefined(&self, name: &str) -> bool {
+        self.kind(name) == Some(SymbolKind::Predefined)
+    }
+
+    /// The RAM address the next new variable will be allocated at.
+    pub fn next_free_address(&self) -> u16 {
+        self.next_variable
+    }
+
+    /// The number of symbols in the table, including predefined symbols.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Check if the table contains no symbols at all.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Iterate over all symbols and their addresses in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        self.symbols.iter().map(|(name, (address, _))| (name.as_str(), *address))
+    }
+
+    /// Iterate over all symbols and their addresses ordered by name, e.g. to print the table reproducibly.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::SymbolTable;
+    ///
+    /// let mut symbols = SymbolTable::new();
+    /// symbols.define_label("LOOP", 4);
+    /// let names: Vec<&str> = symbols.iter_sorted().map(|(name, _)| name).take(3).collect();
+    /// assert_eq!(names, vec!["ARG", "KBD", "LCL"]);
+    /// ```
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&str, u16)> {
+        let mut sorted: Vec<(&str, u16)> = self.iter().collect();
+        sorted.sort_unstable();
+        sorted.into_iter()
+    }
+
+    /// Iterate over the symbols of a single kind, ordered by address and then by name.
+    pub fn iter_kind(&self, kind: SymbolKind) -> impl Iterator<Item = (&str, u16)> {
+        self.iter_grouped()
+            .filter(move |(symbol_kind, _, _)| *symbol_kind == kind)
+            .map(|(_, name, address)| (name, address))
+    }
+
+    /// Iterate over all symbols grouped by kind (predefined symbols, then labels, then variables),
+    /// each group ordered by address and then by name.
+    pub fn iter_grouped(&self) -> impl Iterator<Item = (SymbolKind, &str, u16)> {
+        let mut grouped: Vec<(SymbolKind, &str, u16)> = self.symbols
+            .iter()
+            .map(|(name, (address, kind))| (*kind, name.as_str(), *address))
+            .collect();
+        grouped.sort_by(|a, b| (a.0, a.2, a.1).cmp(&(b.0, b.2, b.1)));
+        grouped.into_iter()
+    }
+
+    /// Write every label and variable with its address to `writer`, e.g. so a disassembler or debugger can restore their names.
+    ///
+    /// Symbols are grouped by kind and ordered by address, then by name. Predefined symbols are only written
+    /// if they are not among those of the Hack specification, e.g. the addresses of memory mapped devices.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::Io`] if writing fails.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::SymbolTable;
+    /// use rhasm::symbol_table::SymbolFileFormat;
+    ///
+    /// let mut symbols = SymbolTable::new();
+    /// symbols.define_predefined("UART_OUT", 24577);
+    /// symbols.define_label("LOOP", 4);
+    /// symbols.allocate_variable("i").unwrap();
+    ///
+    /// let mut sym = Vec::new();
+    /// symbols.write_symbols(&mut sym, SymbolFileFormat::Sym).unwrap();
+    /// assert_eq!(String::from_utf8(sym).unwrap(), "predefined UART_OUT 24577\nlabel LOOP 4\nvariable i 16\n");
+    ///
+    /// let mut json = Vec::new();
+    /// symbols.write_symbols(&mut json, SymbolFileFormat::Json).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(json).unwrap(),
+    ///     "{\"predefined\":{\"UART_OUT\":24577},\"labels\":{\"LOOP\":4},\"variables\":{\"i\":16}}\n"
+    /// );
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn write_symbols(&self, writer: &mut dyn Write, format: SymbolFileFormat) -> Result<(), RhasmError> {
+        let symbols = self.iter_grouped().filter(|(_, name, address)| !DEFAULT_SYMBOLS.contains(&(*name, *address)));
+        match format {
+            SymbolFileFormat::Sym => {
+                for (kind, name, address) in symbols {
+                    writeln!(writer, "{} {} {}", kind.name(), name, address)?;
+                }
+            }
+            SymbolFileFormat::Json => {
+                let symbols: Vec<(SymbolKind, &str, u16)> = symbols.collect();
+                let groups: Vec<String> = [("predefined", SymbolKind::Predefined), ("labels", SymbolKind::Label), ("variables", SymbolKind::Variable)]
+                    .iter()
+                    .map(|(key, kind)| {
+                        let entries: Vec<String> = symbols
+                            .iter()
+                            .filter(|(symbol_kind, _, _)| symbol_kind == kind)
+                            .map(|(_, name, address)| format!("{}:{}", json::string(name), address))
+                            .collect();
+                        format!("\"{}\":{{{}}}", key, entries.join(","))
+                    })
+                    .collect();
+                writeln!(writer, "{{{}}}", groups.join(","))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove every variable, so the next variable is allocated at the base address again.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::SymbolTable;
+    ///
+    /// let mut symbols = SymbolTable::with_variable_base(1024);
+    /// symbols.allocate_variable("counter").unwrap();
+    /// symbols.clear_variables();
+    ///
+    /// assert!(!symbols.contains("counter"));
+    /// assert_eq!(symbols.allocate_variable("sum").unwrap(), 1024);
+    /// ```
+    pub fn clear_variables(&mut self) {
+        self.symbols.retain(|_, (_, kind)| *kind != SymbolKind::Variable);
+        self.next_variable = self.variable_base;
+    }
+
+    /// Define a label at a ROM address, replacing any existing label or variable of the same name.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::PredefinedSymbol`] without changing the table if `name` is a predefined symbol,
+    ///   as redefining e.g. `SCREEN` would silently redirect every use of it.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::{ RhasmError, SymbolTable };
+    ///
+    /// let mut symbols = SymbolTable::new();
+    /// symbols.define("LOOP", 4).unwrap();
+    /// assert_eq!(symbols.get("LOOP"), Some(4));
+    ///
+    /// assert!(matches!(symbols.define("SCREEN", 8), Err(RhasmError::PredefinedSymbol(_))));
+    /// assert_eq!(symbols.get("SCREEN"), Some(16384));
+    /// ```
+    pub fn define(&mut self, name: &str, address: u16) -> Result<(), RhasmError> {
+        if self.is_predefined(name) {
+            return Err(RhasmError::PredefinedSymbol(name.to_string()));
+        }
+        self.define_label(name, address);
+        Ok(())
+    }
+
+    /// Define a label at a ROM address, replacing any existing symbol of the same name.
+    /// Unlike [`SymbolTable::define`], predefined symbols are replaced as well.
+    pub fn define_label(&mut self, name: &str, address: u16) {
+        self.symbols.insert(name.to_string(), (address, SymbolKind::Label));
+    }
+
+    /// Define an additional predefined symbol, e.g. the address of a memory mapped device.
+    /// Replaces any existing symbol of the same name.
+    pub fn define_predefined(&mut self, name: &str, address: u16) {
+        self.symbols.insert(name.to_string(), (address, SymbolKind::Predefined));
+    }
+
+    /// Define a variable at a known RAM address, e.g. one recovered from machine code, replacing any existing symbol of the same name.
+    /// Variables allocated afterwards are placed above it.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::{ SymbolKind, SymbolTable };
+    ///
+    /// let mut symbols = SymbolTable::new();
+    /// symbols.define_variable("var_17", 17);
+    /// assert_eq!(symbols.lookup("var_17"), Some((17, SymbolKind::Variable)));
+    /// assert_eq!(symbols.allocate_variable("counter").unwrap(), 18);
+    /// ```
+    pub fn define_variable(&mut self, name: &str, address: u16) {
+        self.symbols.insert(name.to_string(), (address, SymbolKind::Variable));
+        self.next_variable = self.next_variable.max(address.saturating_add(1));
+    }
+
+    /// Define `alias` as another name for the address of `target`, with the same kind as `target`.
+    ///
+    /// ### Returns
+    ///
+    /// Returns the address of the alias, or [`None`] if `target` is not in the table, in which case nothing is defined.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use rhasm::SymbolTable;
+    ///
+    /// let mut symbols = SymbolTable::new();
+    /// symbols.define_label("MAIN_LOOP", 12);
+    /// assert_eq!(symbols.define_alias("BEGIN", "MAIN_LOOP"), Some(12));
+    /// assert_eq!(symbols.define_alias("END", "MISSING"), None);
+    /// assert_eq!(symbols.get("BEGIN"), Some(12));
+    /// ```
+    pub fn define_alias(&mut self, alias: &str, target: &str) -> Option<u16> {
+        let (address, kind) = *self.symbols.get(target)?;
+        self.symbols.insert(alias.to_string(), (address, kind));
+        Some(address)
+    }
+
+    /// Allocate the next free RAM address to a new variable and return it.
+    ///
+    /// ### Errors
+    ///
+    /// * Returns [`RhasmError::SymbolOverflow`] if the next address does not fit in an A-Instruction.
+    pub fn allocate_variable(&mut self, name: &str) -> Result<u16, RhasmError> {
+        if self.next_variable >= ADDRESS_LIMIT {
+            return Err(RhasmError::SymbolOverflow(name.to_string()));
+        }
+        let address = self.next_variable;
+        self.symbols.insert(name.to_string(), (address, SymbolKind::Variable));
+        self.next_variable += 1;
+        Ok(address)
+    }
+}
+
+/// Parse a symbol file, e.g. one listing the addresses of memory mapped devices, to define its symbols before assembling.
+///
+/// Every line is either `NAME ADDRESS`, defining a predefined symbol, or `KIND NAME ADDRESS` as written by
+/// [`SymbolTable::write_symbols`] in [`SymbolFileFormat::Sym`]. Blank lines and lines starting with `#` or `//` are ignored.
+///
+/// ### Returns
+///
+/// Returns the kind, name and address of every symbol, in the order they were listed.
+///
+/// ### Errors
+///
+/// * Returns [`RhasmError::Io`] of kind [`io::ErrorKind::InvalidData`] naming the line if it is not in either form,
+///   if a name is not a valid symbol, or if an address does not fit in 15 bits.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use rhasm::symbol_table::parse_symbol_file;
+/// use std::io::Cursor;
+///
+/// let symbols = parse_symbol_file("# Devices\nUART_OUT 24577\nlabel LOOP 4\n").unwrap();
+/// assert_eq!(symbols[0], (SymbolKind::Predefined, "UART_OUT".to_string(), 24577));
+/// assert_eq!(symbols[1], (SymbolKind::Label, "LOOP".to_string(), 4));
+///
+/// let mut in_file = Cursor::new("@UART_OUT\nM=D\n");
+/// let mut out_file = Cursor::new(Vec::new());
+/// let devices = symbols.into_iter().map(|(_, name, address)| (name, address));
+/// let config = AssemblerConfig::new(&mut in_file, &mut out_file).with_predefined_symbols(devices);
+/// let mut assembler = Assembler::build(config).unwrap();
+/// assert_eq!(assembler.get_next_encoded_instruction().unwrap(), Some(format!("{:016b}", 24577)));
+///
+/// assert!(parse_symbol_file("UART_OUT 70000\n").is_err());
+/// ```
+#[cfg(feature = "std")]
+pub fn parse_symbol_file(source: &str) -> Result<Vec<(SymbolKind, String, u16)>, RhasmError> {
+    let mut symbols = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let symbol = parse_symbol(line).ok_or_else(|| {
+            let message = format!("Malformed symbol on line {}: {}, expected `[KIND] NAME ADDRESS`", index + 1, line);
+            RhasmError::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+        })?;
+        symbols.push(symbol);
+    }
+    Ok(symbols)
+}
+
+// Parse `[KIND] NAME ADDRESS`, returning None if the line is malformed
+#[cfg(feature = "std")]
+fn parse_symbol(line: &str) -> Option<(SymbolKind, String, u16)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (kind, name, address) = match fields[..] {
+        [name, address] => (SymbolKind::Predefined, name, address),
+        [kind, name, address] => (SymbolKind::from_name(kind)?, name, address),
+        _ => {
+            return None;
+        }
+    };
+    let is_symbol = name.chars().all(|char| char.is_ascii_alphanumeric() || "_.$:".contains(char)) &&
+        !name.starts_with(|char: char| char.is_ascii_digit());
+    let address = address.parse().ok().filter(|address| *address < ADDRESS_LIMIT)?;
+    is_symbol.then(|| (kind, name.to_string(), address))
+}
+
+// Define every alias whose target is known, aliases may refer to other aliases in any order
+// Returns the aliases whose target could not be found, along with their extra data, e.g. their location
+#[cfg(feature = "std")]
+pub(crate) fn resolve_aliases<T>(
+    symbol_table: &mut SymbolTable,
+    mut aliases: Vec<(String, String, T)>
+) -> Vec<(String, String, T)> {
+    loop {
+        let pending = aliases.len();
+        aliases.retain(|(alias, target, _)| symbol_table.define_alias(alias, target).is_none());
+        if aliases.len() == pending {
+            return aliases;
+        }
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        SymbolTable::new()
+    }
+}
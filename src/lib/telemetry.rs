@@ -0,0 +1,148 @@
+//! Module for the counters kept by the [`crate::Assembler`] and [`crate::Disassembler`], for services embedding rhasm that export metrics.
+//!
+//! Every instance counts its own work, read with [`crate::Assembler::counters`] and [`crate::Disassembler::counters`].
+//! Once [`enable`] is called, the counters of every instance are also added to a process-wide total when the instance is dropped,
+//! read with [`snapshot`]. Aggregation is off by default, so programs that never call [`enable`] share no state between instances.
+
+use std::{
+    collections::BTreeMap,
+    sync::{ atomic::{ AtomicBool, Ordering }, Mutex },
+};
+use crate::RhasmError;
+
+// Whether dropped instances add their counters to `TOTAL`
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static TOTAL: Mutex<Counters> = Mutex::new(Counters::new());
+
+/// The work done by one or more assemblers and disassemblers.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::*;
+/// use std::io::Cursor;
+///
+/// let mut in_file = Cursor::new("@2\nD=A\n");
+/// let mut out_file = Cursor::new(Vec::new());
+/// let mut assembler = Assembler::build(AssemblerConfig::new(&mut in_file, &mut out_file)).unwrap();
+/// assembler.advance_to_end().unwrap();
+///
+/// let counters = assembler.counters();
+/// assert_eq!(counters.programs_assembled, 1);
+/// assert_eq!(counters.instructions_encoded, 2);
+/// assert_eq!(counters.bytes_written, 34);
+/// assert!(counters.errors.is_empty());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Programs whose last instruction was encoded.
+    pub programs_assembled: u64,
+    /// Instructions encoded by assemblers.
+    pub instructions_encoded: u64,
+    /// Instructions decoded by disassemblers.
+    pub instructions_decoded: u64,
+    /// Bytes written to the output, not counting symbol files, traces and sinks.
+    pub bytes_written: u64,
+    /// Errors returned, by kind: the code from [`crate::error_codes`] of every error diagnostic,
+    /// `io` for failures reading or writing, and `decode` for words a disassembler could not decode.
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl Counters {
+    /// Create counters that are all zero.
+    pub const fn new() -> Counters {
+        Counters {
+            programs_assembled: 0,
+            instructions_encoded: 0,
+            instructions_decoded: 0,
+            bytes_written: 0,
+            errors: BTreeMap::new(),
+        }
+    }
+
+    /// Add every counter of `other` to `self`.
+    pub fn merge(&mut self, other: &Counters) {
+        self.programs_assembled += other.programs_assembled;
+        self.instructions_encoded += other.instructions_encoded;
+        self.instructions_decoded += other.instructions_decoded;
+        self.bytes_written += other.bytes_written;
+        for (kind, count) in &other.errors {
+            *self.errors.entry(kind.clone()).or_default() += count;
+        }
+    }
+
+    /// The total number of errors of every kind.
+    pub fn error_count(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    // Count an error of the given kind
+    pub(crate) fn record_error_kind(&mut self, kind: &str) {
+        *self.errors.entry(kind.to_string()).or_default() += 1;
+    }
+
+    // Count every error in `err`, a diagnostic without a code is counted as `unknown`
+    pub(crate) fn record_error(&mut self, err: &RhasmError) {
+        match err {
+            RhasmError::Diagnostics(diagnostics) => {
+                for diagnostic in diagnostics.iter().filter(|diagnostic| diagnostic.is_error()) {
+                    self.record_error_kind(diagnostic.code.as_deref().unwrap_or("unknown"));
+                }
+            }
+            RhasmError::Io(_) => self.record_error_kind("io"),
+            err => self.record_error_kind(err.code().unwrap_or("unknown")),
+        }
+    }
+}
+
+/// Add the counters of every instance dropped from now on to the process-wide total.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Stop adding the counters of dropped instances to the process-wide total, which is kept.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Whether the counters of dropped instances are added to the process-wide total.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// A copy of the process-wide total.
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::{ assemble_str, telemetry };
+///
+/// telemetry::enable();
+/// assemble_str("@2\nD=A\n").unwrap();
+/// assemble_str("D=Q\n").unwrap_err();
+///
+/// let total = telemetry::snapshot();
+/// assert_eq!(total.programs_assembled, 1);
+/// assert_eq!(total.errors.get("E0005"), Some(&1));
+/// ```
+pub fn snapshot() -> Counters {
+    lock_total().clone()
+}
+
+/// Reset the process-wide total to zero, e.g. after exporting it.
+pub fn reset() {
+    *lock_total() = Counters::new();
+}
+
+// Add the counters of a dropped instance to the total, if enabled
+pub(crate) fn submit(counters: &Counters) {
+    if is_enabled() {
+        lock_total().merge(counters);
+    }
+}
+
+// The counters stay consistent even if a thread panicked while holding the lock, as every update is a single merge
+fn lock_total() -> std::sync::MutexGuard<'static, Counters> {
+    TOTAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,101 @@
+//! Module for generating starter test scripts for the Nand2Tetris CPU Emulator.
+
+use std::io::{ self, Cursor };
+use crate::{ Assembler, AssemblerConfig, Instruction, RhasmError, SymbolKind };
+
+// The most RAM cells listed in a generated output-list, to keep the comparison file readable
+const MAX_OUTPUT_CELLS: usize = 8;
+
+// Predefined symbols below this address name registers rather than memory maps
+const REGISTER_LIMIT: u16 = 16;
+
+// Each output column is printed as `%D2.6.2`, a 6 digit wide value padded by 2 spaces on both sides
+const COLUMN_WIDTH: usize = 10;
+
+/// A generated `.tst` test script along with the `.cmp` comparison file it refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestScript {
+    /// The test script, loading the program, running it and writing the output-list.
+    pub tst: String,
+    /// The comparison file, with a header matching the output-list and a row of placeholder values.
+    pub cmp: String,
+}
+
+/// Generate a starter test script for the program `source`, saved as `name` (e.g. `Prog.asm`).
+///
+/// The output-list contains the registers (`R0`-`R15`, `SP`, ...) referenced by the program and its variables, up to 8 cells.
+/// The program is run for ten times its instruction count, which suits straight-line code but should be adjusted for loops.
+/// The expected values in the comparison file are all `0` and need to be filled in by hand.
+///
+/// ### Errors
+///
+/// * Returns an error if the program does not assemble, see [`Assembler::build`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use rhasm::test_script::generate_test_script;
+///
+/// let script = generate_test_script("Add.asm", "@R0\nD=M\n@R1\nD=D+M\n@sum\nM=D\n").unwrap();
+///
+/// assert!(script.tst.contains("output-list RAM[0]%D2.6.2 RAM[1]%D2.6.2 RAM[16]%D2.6.2;"));
+/// assert!(script.tst.contains("repeat 60 {"));
+/// assert_eq!(script.cmp, "|  RAM[0]  |  RAM[1]  | RAM[16]  |\n|       0  |       0  |       0  |\n");
+/// ```
+pub fn generate_test_script(name: &str, source: &str) -> Result<TestScript, RhasmError> {
+    let mut reader = Cursor::new(source);
+    let mut sink = io::sink();
+    let mut assembler = Assembler::build(AssemblerConfig::new(&mut reader, &mut sink))?;
+    assembler.advance_to_end()?;
+
+    let mut cells: Vec<u16> = assembler.instructions
+        .iter()
+        .filter_map(|instruction| {
+            match instruction {
+                Instruction::AInstruction(symbol) if assembler.symbol_table.is_predefined(symbol) => {
+                    assembler.symbol_table.get(symbol)
+                }
+                _ => None,
+            }
+        })
+        .filter(|address| *address < REGISTER_LIMIT)
+        .chain(assembler.symbol_table.iter_kind(SymbolKind::Variable).map(|(_, address)| address))
+        .collect();
+    cells.sort_unstable();
+    cells.dedup();
+    cells.truncate(MAX_OUTPUT_CELLS);
+    if cells.is_empty() {
+        cells.push(0);
+    }
+
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let output_list: Vec<String> = cells.iter().map(|cell| format!("RAM[{}]%D2.6.2", cell)).collect();
+    let tst = format!(
+        "// Test script for {name}, generated by rhasm\n\
+         // Set the program's inputs below and fill in the expected values in {stem}.cmp\n\
+         load {name},\n\
+         output-file {stem}.out,\n\
+         compare-to {stem}.cmp,\n\
+         output-list {};\n\
+         \n\
+         // set RAM[0] 0,\n\
+         \n\
+         // Sized to run every instruction ten times, adjust this to the program's running time\n\
+         repeat {} {{\n    ticktock;\n}}\n\
+         output;\n",
+        output_list.join(" "),
+        assembler.instructions.len().max(1) * 10
+    );
+
+    let mut header = String::from("|");
+    let mut values = String::from("|");
+    for cell in &cells {
+        let title = format!("RAM[{}]", cell);
+        let left = (COLUMN_WIDTH.saturating_sub(title.len())) / 2;
+        header.push_str(&format!("{:left$}{:<width$}|", "", title, left = left, width = COLUMN_WIDTH - left));
+        values.push_str(&format!("  {:>6}  |", 0));
+    }
+    let cmp = format!("{}\n{}\n", header, values);
+
+    Ok(TestScript { tst, cmp })
+}
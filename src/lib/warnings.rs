@@ -0,0 +1,295 @@
+//! Module containing the checks for suspicious but valid code.
+//!
+//! The checks run at the end of the [`crate::Assembler`]'s first pass and never stop a program from being assembled,
+//! their warnings can be read from [`crate::Assembler::diagnostics`].
+//!
+//! * Labels that are never referenced.
+//! * Labels that are referenced, but never as the target of a jump.
+//! * Variables that are written to, but never read.
+//! * Symbols that only differ from another symbol by case, e.g. `LOOP` and `loop`.
+//! * Programs whose last instruction is not an unconditional jump, as running past the end of the program is undefined.
+//! * Instructions following an unconditional jump without a label, as nothing can ever jump to them.
+//!
+//! With [`crate::AssemblerConfig::with_style_checks`] the code is also checked against the conventions of the Hack platform,
+//! reported as notes rather than warnings.
+//!
+//! * Labels that are not written in upper case, apart from the `File.function` labels written by the VM translator.
+//! * Syntax that is accepted but not written the way the Hack specification does, e.g. the destination `DM` for `MD`,
+//!   noting the canonical form.
+
+use std::collections::{ HashMap, HashSet };
+use crate::{ Diagnostic, Instruction, InstructionSpans, Span, SymbolKind, SymbolTable };
+
+/// The name of every check whose level can be configured with [`crate::AssemblerConfig::with_lint_level`], as attached to its diagnostics.
+///
+/// * `unused-label`: a label is never referenced.
+/// * `label-used-as-data`: a label is referenced, but never jumped to.
+/// * `unread-variable`: a variable is written, but never read.
+/// * `symbol-case`: a symbol differs from another only by case.
+/// * `missing-end-loop`: the program does not end in an unconditional jump.
+/// * `unreachable-code`: an instruction follows an unconditional jump, but nothing jumps to it.
+/// * `label-case`: a label is not written in upper case, only checked with style checks or if its level is set.
+/// * `duplicate-label`: a label is defined twice, see [`crate::AssemblerConfig::with_duplicate_labels_as_warnings`].
+/// * `truncated-constant`: a constant is truncated to 15 bits, see [`crate::ConstantOverflow::Truncate`].
+/// * `variable-overflow`: a variable is allocated in the screen memory map, see [`crate::AssemblerConfig::with_variable_overflow_as_warning`].
+/// * `non-canonical-syntax`: accepted syntax differs from the Hack specification, only checked with style checks or if its level is set.
+pub const LINTS: &[&str] = &[
+    "unused-label",
+    "label-used-as-data",
+    "unread-variable",
+    "symbol-case",
+    "missing-end-loop",
+    "unreachable-code",
+    "label-case",
+    "duplicate-label",
+    "truncated-constant",
+    "variable-overflow",
+    "non-canonical-syntax",
+];
+
+// Everything the checks need to know about a program after its first pass
+pub(crate) struct ProgramFacts<'p> {
+    pub(crate) instructions: &'p [Instruction],
+    pub(crate) spans: &'p [InstructionSpans],
+    // Label definitions with their location
+    pub(crate) labels: &'p [(String, Span)],
+    // Resolved aliases as (alias, target)
+    pub(crate) aliases: &'p [(String, String)],
+    pub(crate) symbol_table: &'p SymbolTable,
+}
+
+// Run every check and return the warnings in source order
+pub(crate) fn check(facts: &ProgramFacts) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+    let uses = symbol_uses(facts);
+    warnings.extend(unused_labels(facts, &uses));
+    warnings.extend(unread_variables(facts, &uses));
+    warnings.extend(case_mismatches(facts));
+    warnings.extend(missing_end_loop(facts));
+    warnings.extend(unreachable_code(facts));
+    warnings.sort_by_key(|warning| (warning.span.line, warning.span.start));
+    warnings
+}
+
+// Run the convention checks and return their notes in source order
+pub(crate) fn check_style(facts: &ProgramFacts) -> Vec<Diagnostic> {
+    let mut notes = label_naming(facts);
+    notes.extend(non_canonical_syntax(facts));
+    notes.sort_by_key(|note| (note.span.line, note.span.start));
+    notes
+}
+
+fn is_symbolic(symbol: &str) -> bool {
+    !symbol.chars().all(|char| char.is_ascii_digit())
+}
+
+// Follow an alias to the symbol it stands for
+fn resolve<'f>(facts: &'f ProgramFacts, symbol: &'f str) -> &'f str {
+    let mut symbol = symbol;
+    // Aliases may chain, but never more often than there are aliases
+    for _ in 0..facts.aliases.len() {
+        match facts.aliases.iter().find(|(alias, _)| alias == symbol) {
+            Some((_, target)) => symbol = target,
+            None => break,
+        }
+    }
+    symbol
+}
+
+// The indices of the A-Instructions using each symbol in source order, aliases counting as uses of their target
+fn symbol_uses<'f>(facts: &'f ProgramFacts) -> HashMap<&'f str, Vec<usize>> {
+    let mut uses: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, instruction) in facts.instructions.iter().enumerate() {
+        if let Instruction::AInstruction(symbol) = instruction {
+            if is_symbolic(symbol) {
+                uses.entry(resolve(facts, symbol)).or_default().push(index);
+            }
+        }
+    }
+    uses
+}
+
+// Labels that are never referenced, and labels that are only ever loaded as data
+// A label copied with `D=A` counts as jumped to, as translated VM code stores return addresses and functions
+// that way and later jumps to them indirectly through `@R14 A=M 0;JMP`
+fn unused_labels(facts: &ProgramFacts, uses: &HashMap<&str, Vec<usize>>) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+    for (label, span) in facts.labels {
+        let text = format!("({})", label);
+        let Some(uses) = uses.get(label.as_str()) else {
+            warnings.push(Diagnostic::warning(*span, &text, &format!("Label {} is never used", label)).with_lint("unused-label"));
+            continue;
+        };
+        let jumped_to = uses.iter().any(|index| {
+            match facts.instructions.get(index + 1) {
+                Some(Instruction::CInstruction(dest, comp, jump)) => !jump.is_empty() || (dest.contains('D') && comp == "A"),
+                _ => false,
+            }
+        });
+        if !jumped_to {
+            warnings.push(
+                Diagnostic::warning(*span, &text, &format!("Label {} is never jumped to", label))
+                    .with_help("a label only loaded as data may be meant as a variable")
+                    .with_lint("label-used-as-data")
+            );
+        }
+    }
+    warnings
+}
+
+// Variables only ever stored to, i.e. every use is followed by an instruction writing M without reading it
+fn unread_variables(facts: &ProgramFacts, uses: &HashMap<&str, Vec<usize>>) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+    // Anything not in the table after the first pass will be allocated as a variable
+    for (variable, indices) in uses.iter().filter(|(symbol, _)| !facts.symbol_table.contains(symbol)) {
+        let only_written = indices.iter().all(|index| {
+            matches!(
+                facts.instructions.get(index + 1),
+                Some(Instruction::CInstruction(dest, comp, _)) if dest.contains('M') && !comp.contains('M')
+            )
+        });
+        if only_written {
+            let first = indices[0];
+            warnings.push(
+                Diagnostic::warning(
+                    facts.spans[first].instruction,
+                    &facts.instructions[first].to_string(),
+                    &format!("Variable {} is written but never read", variable)
+                ).with_lint("unread-variable")
+            );
+        }
+    }
+    warnings
+}
+
+// Symbols spelled like an earlier symbol apart from case, reported once per spelling
+fn case_mismatches(facts: &ProgramFacts) -> Vec<Diagnostic> {
+    let mut occurrences: Vec<(&str, Span, String)> = facts.labels
+        .iter()
+        .map(|(label, span)| (label.as_str(), *span, format!("({})", label)))
+        .collect();
+    for (index, instruction) in facts.instructions.iter().enumerate() {
+        if let Instruction::AInstruction(symbol) = instruction {
+            if is_symbolic(symbol) {
+                occurrences.push((symbol, facts.spans[index].instruction, instruction.to_string()));
+            }
+        }
+    }
+    occurrences.sort_by_key(|(_, span, _)| (span.line, span.start));
+
+    // Predefined symbols count as spelled first, even if the program never uses them
+    let mut first_spellings: HashMap<String, (&str, Option<Span>)> = facts.symbol_table
+        .iter_kind(SymbolKind::Predefined)
+        .map(|(symbol, _)| (symbol.to_lowercase(), (symbol, None)))
+        .collect();
+    let mut reported: Vec<&str> = Vec::new();
+    let mut warnings = Vec::new();
+    for (symbol, span, text) in occurrences {
+        let (first, first_span) = *first_spellings.entry(symbol.to_lowercase()).or_insert((symbol, Some(span)));
+        if first == symbol || reported.contains(&symbol) {
+            continue;
+        }
+        reported.push(symbol);
+        let warning = Diagnostic::warning(span, &text, &format!("Symbol {} differs from {} only by case", symbol, first))
+            .with_lint("symbol-case");
+        warnings.push(match first_span {
+            Some(first_span) => warning.with_note(Some(first_span), &format!("{} is first used here", first)),
+            None => warning.with_note(None, &format!("{} is a predefined symbol", first)),
+        });
+    }
+    warnings
+}
+
+// A program that does not end in an unconditional jump, e.g. the `(END) @END 0;JMP` idiom, runs on into uninitialized ROM
+fn missing_end_loop(facts: &ProgramFacts) -> Option<Diagnostic> {
+    let last = facts.instructions.last()?;
+    if is_unconditional_jump(last) {
+        return None;
+    }
+    Some(
+        Diagnostic::warning(
+            facts.spans[facts.instructions.len() - 1].instruction,
+            &last.to_string(),
+            "Program does not end in an unconditional jump"
+        ).with_help("end the program with an infinite loop, e.g. `(END) @END 0;JMP`, so it does not run past the end of ROM")
+            .with_lint("missing-end-loop")
+    )
+}
+
+fn is_unconditional_jump(instruction: &Instruction) -> bool {
+    matches!(instruction, Instruction::CInstruction(_, _, jump) if jump == "JMP")
+}
+
+// Instructions after an unconditional jump that nothing jumps to, reported once at the start of each unreachable run
+// Code reached by jumping to a constant address, as in translated VM code, counts as a jump target too
+fn unreachable_code(facts: &ProgramFacts) -> Vec<Diagnostic> {
+    let constant_targets = facts.instructions.windows(2).filter_map(|pair| {
+        match pair {
+            [Instruction::AInstruction(addr), Instruction::CInstruction(_, _, jump)] if !jump.is_empty() => {
+                addr.parse::<u16>().ok()
+            }
+            _ => None,
+        }
+    });
+    let labelled: HashSet<u16> = facts.labels
+        .iter()
+        .filter_map(|(label, _)| facts.symbol_table.get(label))
+        .chain(constant_targets)
+        .collect();
+    let mut warnings = Vec::new();
+    let mut reachable = true;
+    for (index, pair) in facts.instructions.windows(2).enumerate() {
+        let address = index + 1;
+        if labelled.contains(&(address as u16)) {
+            reachable = true;
+        } else if reachable && is_unconditional_jump(&pair[0]) {
+            reachable = false;
+            warnings.push(
+                Diagnostic::warning(facts.spans[address].instruction, &pair[1].to_string(), "Unreachable instruction")
+                    .with_note(Some(facts.spans[index].instruction), "any code after this unconditional jump is never run")
+                    .with_help("add a label before it if it is meant to be jumped to, or remove it")
+                    .with_lint("unreachable-code")
+            );
+        }
+    }
+    warnings
+}
+
+// Labels are written in upper case to tell them apart from variables, VM translator labels like `Main.main` keep the case of their function
+fn label_naming(facts: &ProgramFacts) -> Vec<Diagnostic> {
+    facts.labels
+        .iter()
+        .filter(|(label, _)| !label.contains('.') && label.chars().any(|char| char.is_ascii_lowercase()))
+        .map(|(label, span)| {
+            Diagnostic::note(*span, &format!("({})", label), &format!("Label {} is not written in upper case", label))
+                .with_help(&format!("labels are conventionally upper case to tell them apart from variables, e.g. `{}`", label.to_ascii_uppercase()))
+                .with_lint("label-case")
+        })
+        .collect()
+}
+
+// Destinations naming their registers in another order than the Hack specification, which always writes them as A, M, D
+fn non_canonical_syntax(facts: &ProgramFacts) -> Vec<Diagnostic> {
+    let mut notes = Vec::new();
+    for (index, instruction) in facts.instructions.iter().enumerate() {
+        let Instruction::CInstruction(dest, comp, jump) = instruction else {
+            continue;
+        };
+        let canonical: String = "AMD".chars().filter(|register| dest.contains(*register)).collect();
+        if *dest == canonical {
+            continue;
+        }
+        let spans = &facts.spans[index];
+        let rewritten = Instruction::CInstruction(canonical.clone(), comp.clone(), jump.clone()).to_string();
+        notes.push(
+            Diagnostic::note(
+                spans.dest.unwrap_or(spans.instruction),
+                &instruction.to_string(),
+                &format!("Destination {} is written {} by the Hack specification", dest, canonical)
+            )
+                .with_help(&format!("write `{}`", rewritten))
+                .with_lint("non-canonical-syntax")
+        );
+    }
+    notes
+}
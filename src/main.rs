@@ -1,35 +1,919 @@
-use std::{ borrow::BorrowMut, fs::File, io::{ self, Write as _ }, path::PathBuf };
-use rhasm::{ Assembler, Disassembler };
-use clap::{ Parser, ArgAction };
+use std::{ borrow::BorrowMut, fs::File, io::{ self, Cursor, IsTerminal, Read, Write }, path::{ Path, PathBuf }, thread, time::Duration };
+use rhasm::{ annotations::Annotations, diagnostic, warnings, Assembler, AssemblerConfig, ConstantOverflow, Diagnostic, Disassembler, LintLevel, MachineCodeFormat, RhasmError, Severity, Span, SymbolKind, SymbolTable };
+use rhasm::disassembler::{ name_variables, reconstruct_labels };
+use rhasm::output_format::{ OutputFormat, RawBytes, WordComments };
+use rhasm::symbol_table::{ parse_symbol_file, SymbolFileFormat };
+use log::LevelFilter;
+use clap::{ builder::PossibleValuesParser, Parser, Subcommand, ArgAction, ValueEnum };
 
 #[derive(Parser, Debug)]
 #[command(
     name = "rhasm",
     version = "0.1.1",
     about = "A simple assembler/disassembler for the Hack computer from the Nand2Tetris course",
-    author = "Muaaz Bhyat muu794@gmail.com"
+    author = "Muaaz Bhyat muu794@gmail.com",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    after_help = "Exit codes:\n  0  success\n  1  the source could not be assembled or disassembled, lint or --assert-unchanged failed\n  2  invalid command line arguments\n  3  reading or writing a file failed\n  4  refused to overwrite an existing output file"
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The input file to read from
     /// Is required and does not have an option switch
-    #[arg(required = true)]
-    in_file_path: PathBuf,
+    #[arg(required_unless_present_any = ["eval", "explain_code"])]
+    in_file_path: Option<PathBuf>,
+
+    /// Print a longer explanation of an error or warning code, e.g. `--explain E0002`, or of a lint by name
+    #[arg(long = "explain", value_name = "CODE", exclusive = true)]
+    explain_code: Option<String>,
+
+    /// Assemble (or with -d, disassemble) the given program text instead of a file and print the result
+    /// Lines can be separated with literal \n sequences, e.g. -e '@5\nD=A'
+    #[arg(
+        short,
+        long,
+        value_name = "PROGRAM",
+        conflicts_with_all = ["in_file_path", "output", "follow", "assert_unchanged", "trace_passes"]
+    )]
+    eval: Option<String>,
 
     /// The output file to write
     /// Can be specified with the -o or --output option
     #[arg(short, long)]
     output: Option<PathBuf>,
 
+    /// Overwrite the output file if it already exists, without asking
+    #[arg(long, action = ArgAction::SetTrue)]
+    force: bool,
+
+    /// Never overwrite an existing output file, exiting with status 4 instead of asking
+    /// Without --force or --no-overwrite rhasm only asks if stdin is a terminal, and otherwise refuses
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "force")]
+    no_overwrite: bool,
+
+    /// Before overwriting an existing output file, keep a copy of it as <output_file>.bak
+    #[arg(long, action = ArgAction::SetTrue)]
+    backup: bool,
+
+    /// Keep the copies made by --backup in this directory instead of next to the output file, implies --backup
+    #[arg(long, value_name = "DIR")]
+    backup_dir: Option<PathBuf>,
+
     /// Disassemble the input file
     #[arg(short, long, action = ArgAction::SetTrue)]
     disassemble: bool,
+
+    /// Keep disassembling as the input file grows, like `tail -f`
+    /// Only valid together with --disassemble, runs until interrupted
+    #[arg(short, long, action = ArgAction::SetTrue, requires = "disassemble")]
+    follow: bool,
+
+    /// Name and describe regions of ROM as comments in the disassembled output
+    /// Every line of the file is `START[-END] NAME [DESCRIPTION]`, e.g. `16-240 main_loop Poll the keyboard`
+    #[arg(long, value_name = "FILE", requires = "disassemble")]
+    annotations: Option<PathBuf>,
+
+    /// Name A-Instruction addresses after the symbols in this file and write every label as `(NAME)` above its instruction
+    /// Takes `KIND NAME ADDRESS` lines as written by --symbol-output, e.g. `label LOOP 4`
+    #[arg(long = "with_symbols", value_name = "SYMBOL_FILE", requires = "disassemble", conflicts_with = "eval")]
+    with_symbols: Option<PathBuf>,
+
+    /// Name the targets of jumps `L0`, `L1`, ... and write them as labels, the output still reassembles to the same machine code
+    #[arg(long, action = ArgAction::SetTrue, requires = "disassemble", conflicts_with_all = ["with_symbols", "follow", "eval"])]
+    reconstruct_labels: bool,
+
+    /// Name addresses from RAM[16] up that are only used to access memory `var_16`, `var_17`, ...
+    #[arg(long, action = ArgAction::SetTrue, requires = "disassemble", conflicts_with_all = ["with_symbols", "follow", "eval"])]
+    name_variables: bool,
+
+    /// Note the ROM address and machine word of every instruction as a comment, e.g. `// 0x0007 1111110111010001`
+    #[arg(long, value_enum, value_name = "PLACEMENT", requires = "disassemble", conflicts_with = "eval")]
+    word_comments: Option<WordCommentPlacement>,
+
+    /// Narrate both assembly passes to a <input_file>.trace log
+    /// Lists recorded labels, allocated variables and every resolved symbol
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "disassemble")]
+    trace_passes: bool,
+
+    /// Write every label and variable with its address to this file once assembled, for disassemblers and debuggers
+    /// When disassembling, writes the symbols the output is named after instead, e.g. the ones found by --reconstruct-labels
+    /// Written as JSON if the file name ends in `.json`, otherwise as `KIND NAME ADDRESS` lines, e.g. `label LOOP 4`
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["eval", "assert_unchanged"])]
+    symbol_output: Option<PathBuf>,
+
+    /// Write the source line of every instruction's ROM address to this file as JSON once assembled, for debuggers
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["disassemble", "eval", "assert_unchanged", "single_pass"])]
+    source_map: Option<PathBuf>,
+
+    /// Assemble in a single pass, backpatching forward label references at the end
+    /// Produces the same output without keeping every instruction in memory
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["disassemble", "trace_passes"])]
+    single_pass: bool,
+
+    /// Assemble the input and compare it against the existing output file instead of writing it
+    /// Exits with a non-zero status and a summary of the differences if they do not match
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "disassemble")]
+    assert_unchanged: bool,
+
+    /// Also accept `;` and `#` line comments and `/* */` block comments, as used by other assembler dialects
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    lenient_syntax: bool,
+
+    /// Report labels defined more than once as warnings instead of errors, jumping to the last definition
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    allow_duplicate_labels: bool,
+
+    /// Report variables allocated at or past the end of the variable RAM, the screen memory map at 16384, as warnings instead of errors
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    allow_variable_overflow: bool,
+
+    /// Define a symbol before assembling, e.g. `--define DEBUG=1`, can be given more than once
+    /// Lets build variants be selected without editing the source
+    #[arg(long = "define", value_name = "NAME=VALUE", value_parser = parse_define, conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    defines: Vec<(String, u16)>,
+
+    /// Predefine every symbol listed in this file before assembling, e.g. the addresses of memory mapped devices
+    /// Every line is `NAME ADDRESS`, or `KIND NAME ADDRESS` as written by --symbol-output, symbols given with --define win
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    symbols: Option<PathBuf>,
+
+    /// Reject source lines longer than this many bytes, without reading the rest of the line
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    max_line_length: Option<usize>,
+
+    /// Reject sources larger than this many bytes, without reading the rest of the source
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    max_input_size: Option<u64>,
+
+    /// What to do with A-Instruction constants that do not fit in 15 bits, e.g. `@70000`
+    /// `truncate` keeps the lowest 15 bits with a warning, `wrap` does the same silently
+    #[arg(long, value_enum, default_value_t = ConstantPolicy::Error, conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    constant_overflow: ConstantPolicy,
+
+    /// How to write each instruction of the output
+    /// `hex` writes 4 hexadecimal digits per line, e.g. for Logisim ROM components, `raw` writes 2 bytes per instruction
+    #[arg(long, value_enum, default_value_t = OutputEncoding::Binary, conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    output_format: OutputEncoding,
+
+    /// Do not report the findings of a lint, e.g. `--allow unused-label`, can be given more than once
+    #[arg(long, value_name = "LINT", value_parser = PossibleValuesParser::new(warnings::LINTS), conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    allow: Vec<String>,
+
+    /// Report the findings of a lint as warnings, e.g. `--warn label-case`, can be given more than once
+    #[arg(long, value_name = "LINT", value_parser = PossibleValuesParser::new(warnings::LINTS), conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    warn: Vec<String>,
+
+    /// Report the findings of a lint as errors and fail the build, e.g. `--deny unused-label`, can be given more than once
+    #[arg(long, value_name = "LINT", value_parser = PossibleValuesParser::new(warnings::LINTS), conflicts_with_all = ["disassemble", "single_pass", "eval"])]
+    deny: Vec<String>,
+
+    /// How to print errors and warnings
+    /// `gcc` prints `file:line:column: severity: message` lines understood by editors' problem matchers
+    /// `json` prints one JSON object per line for each diagnostic
+    /// `sarif` prints a single SARIF log once assembly finishes, even if nothing was found
+    #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+
+    /// Quote the offending source line and underline the problem in human readable messages
+    /// `auto` does so when stderr is a terminal, `--pretty-errors` on its own means `always`
+    #[arg(long, value_enum, default_value_t = PrettyErrors::Auto, num_args = 0..=1, default_missing_value = "always")]
+    pretty_errors: PrettyErrors,
+
+    /// Also log what the assembler and disassembler are doing, e.g. when the first pass completes
+    /// The RUST_LOG environment variable takes precedence over --verbose and --quiet
+    #[arg(short, long, action = ArgAction::SetTrue, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Only log errors, not warnings such as skipped lines
+    #[arg(short, long, action = ArgAction::SetTrue)]
+    quiet: bool,
+}
+
+impl Cli {
+    // The most detailed messages logged, as chosen with --verbose and --quiet
+    fn log_level(&self) -> LevelFilter {
+        match (self.verbose, self.quiet) {
+            (true, _) => LevelFilter::Debug,
+            (_, true) => LevelFilter::Error,
+            _ => LevelFilter::Warn,
+        }
+    }
+
+    // Whether human readable messages should quote the source
+    fn wants_pretty_errors(&self) -> bool {
+        self.message_format == MessageFormat::Human &&
+            match self.pretty_errors {
+                PrettyErrors::Auto => io::stderr().is_terminal(),
+                PrettyErrors::Always => true,
+                PrettyErrors::Never => false,
+            }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ConstantPolicy {
+    /// Reject the constant
+    Error,
+    /// Keep the lowest 15 bits and warn about it
+    Truncate,
+    /// Keep the lowest 15 bits silently
+    Wrap,
+}
+
+impl From<ConstantPolicy> for ConstantOverflow {
+    fn from(policy: ConstantPolicy) -> Self {
+        match policy {
+            ConstantPolicy::Error => ConstantOverflow::Error,
+            ConstantPolicy::Truncate => ConstantOverflow::Truncate,
+            ConstantPolicy::Wrap => ConstantOverflow::Wrap,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutputEncoding {
+    /// 16 binary digits per line, as read by the Nand2Tetris CPU Emulator
+    Binary,
+    /// 4 hexadecimal digits per line
+    Hex,
+    /// 2 bytes per instruction, the most significant first, without line breaks
+    Raw,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum WordCommentPlacement {
+    /// On its own line above each instruction
+    Above,
+    /// After each instruction on the same line
+    Inline,
+}
+
+impl From<WordCommentPlacement> for WordComments {
+    fn from(placement: WordCommentPlacement) -> Self {
+        match placement {
+            WordCommentPlacement::Above => WordComments::Above,
+            WordCommentPlacement::Inline => WordComments::Inline,
+        }
+    }
+}
+
+impl OutputEncoding {
+    // The format the assembler writes the output in
+    fn format(self) -> &'static dyn OutputFormat {
+        match self {
+            OutputEncoding::Binary => &MachineCodeFormat::Binary,
+            OutputEncoding::Hex => &MachineCodeFormat::Hex,
+            OutputEncoding::Raw => &RawBytes,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum MessageFormat {
+    /// One readable line per problem
+    Human,
+    /// GCC style `file:line:column: severity: message`
+    Gcc,
+    /// One JSON object per line with the severity, message, location and snippet
+    Json,
+    /// A single SARIF log of every diagnostic, for GitHub code scanning and other CI dashboards
+    Sarif,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum PrettyErrors {
+    /// When stderr is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Explain a single instruction bit by bit
+    Explain {
+        /// The instruction to explain, either in assembly (e.g. "D=M+1;JGT") or as 16 binary digits
+        instruction: String,
+    },
+    /// Rename a label or variable everywhere in a source file, rewriting the file in place
+    Rename {
+        /// The current name of the label or variable
+        old_name: String,
+        /// The name to rename it to
+        new_name: String,
+        /// The assembly file to rewrite
+        file: PathBuf,
+    },
+    /// Insert a label at a ROM address and use it for every load of that address, rewriting the file in place
+    Label {
+        /// The ROM address to label
+        address: u16,
+        /// The name of the new label
+        name: String,
+        /// The assembly file to rewrite
+        file: PathBuf,
+        /// Only use the label for loads followed by a jump, leaving the other loads of the address as data
+        #[arg(long, action = ArgAction::SetTrue)]
+        jumps_only: bool,
+    },
+    /// Generate a starter <file>.tst test script and <file>.cmp comparison file for the CPU Emulator
+    GenTest {
+        /// The assembly file to write a test for
+        file: PathBuf,
+    },
+    /// Print the parsed instructions, labels and comments of a source file with their locations
+    Parse {
+        /// The assembly file to parse
+        file: PathBuf,
+        /// The format to print the parse in
+        #[arg(long, value_enum, default_value_t = ParseFormat::Json)]
+        format: ParseFormat,
+        /// Also recognise `;`, `#` and `/* */` comments, see --lenient-syntax
+        #[arg(long, action = ArgAction::SetTrue)]
+        lenient_syntax: bool,
+    },
+    /// Check a source file for errors, suspicious code and departures from the Hack conventions without assembling it
+    /// Exits with a non-zero status if any finding is at least as severe as --fail-on
+    Lint {
+        /// The assembly file to check
+        file: PathBuf,
+        /// The least severe finding that makes the check fail
+        #[arg(long, value_enum, default_value_t = FailOn::Warning)]
+        fail_on: FailOn,
+        /// Also recognise `;`, `#` and `/* */` comments, see --lenient-syntax
+        #[arg(long, action = ArgAction::SetTrue)]
+        lenient_syntax: bool,
+        /// How to print the findings, see --message-format
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat,
+        /// Do not report the findings of a lint, can be given more than once
+        #[arg(long, value_name = "LINT", value_parser = PossibleValuesParser::new(warnings::LINTS))]
+        allow: Vec<String>,
+        /// Report the findings of a lint as warnings, can be given more than once
+        #[arg(long, value_name = "LINT", value_parser = PossibleValuesParser::new(warnings::LINTS))]
+        warn: Vec<String>,
+        /// Report the findings of a lint as errors, can be given more than once
+        #[arg(long, value_name = "LINT", value_parser = PossibleValuesParser::new(warnings::LINTS))]
+        deny: Vec<String>,
+    },
+    /// Pack several programs into one ROM image, each named after its file's stem
+    Pack {
+        /// The image file to write
+        output: PathBuf,
+        /// The programs to pack, either assembly (.asm) or assembled (.hack) files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum FailOn {
+    /// Fail on any finding, including departures from the conventions
+    Note,
+    /// Fail on suspicious code or errors
+    Warning,
+    /// Only fail if the program can not be assembled
+    Error,
+}
+
+impl From<FailOn> for Severity {
+    fn from(fail_on: FailOn) -> Self {
+        match fail_on {
+            FailOn::Note => Severity::Note,
+            FailOn::Warning => Severity::Warning,
+            FailOn::Error => Severity::Error,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ParseFormat {
+    /// A JSON array with one object per instruction, label, alias, comment or invalid line
+    Json,
+}
+
+// Assemble or disassemble program text given on the command line, printing the result to stdout
+fn evaluate(program: &str, disassemble: bool, reporter: &Reporter) -> io::Result<()> {
+    let mut source = Cursor::new(program.replace("\\n", "\n"));
+    if disassemble {
+        match rhasm::disassemble_str(source.get_ref()) {
+            Ok(program) => print!("{}", program),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(EXIT_INVALID_SOURCE);
+            }
+        }
+    } else {
+        // The single pass assembler needs a seekable output and does not log progress to stdout
+        let mut output = Cursor::new(Vec::new());
+        if let Err(err) = rhasm::assemble_single_pass(&mut source, &mut output) {
+            reporter.exit_with_error(err);
+        }
+        io::stdout().write_all(output.get_ref())?;
+    }
+    Ok(())
+}
+
+// Run every check of the assembler, including the style checks, and return what they found without writing any output
+// Errors the first pass does not report as diagnostics, e.g. failing to read the input, are reported and end the process
+fn lint(source: &str, lenient_syntax: bool, levels: &[(&str, LintLevel)], reporter: &Reporter) -> Vec<Diagnostic> {
+    let mut in_file = Cursor::new(source);
+    let mut config = AssemblerConfig::without_writer(&mut in_file)
+        .with_lenient_syntax(lenient_syntax)
+        .with_style_checks(true);
+    for (lint, level) in levels {
+        config = config.with_lint_level(lint, *level);
+    }
+    let mut assembler = match Assembler::build(config) {
+        Ok(assembler) => assembler,
+        Err(RhasmError::Diagnostics(diagnostics)) => {
+            return diagnostics;
+        }
+        Err(err) => reporter.exit_with_error(err),
+    };
+    // Some problems, e.g. running out of RAM for variables, are only found while encoding
+    let mut findings = assembler.diagnostics().to_vec();
+    loop {
+        match assembler.get_next_encoded_instruction() {
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(RhasmError::Diagnostics(diagnostics)) => {
+                findings.extend(diagnostics);
+                break;
+            }
+            Err(err) => reporter.exit_with_error(err),
+        }
+    }
+    findings
+}
+
+// Read an annotation file given with --annotations, a malformed file ends the process
+fn load_annotations(path: &Path) -> Annotations {
+    let source = std::fs::read_to_string(path).unwrap_or_else(|err| exit_io(path, err));
+    Annotations::parse(&source).unwrap_or_else(|err| exit_malformed(path, err))
+}
+
+// Read a symbol file given with --symbols or --with_symbols, a malformed file ends the process
+fn load_symbols(path: &Path) -> Vec<(SymbolKind, String, u16)> {
+    let source = std::fs::read_to_string(path).unwrap_or_else(|err| exit_io(path, err));
+    parse_symbol_file(&source).unwrap_or_else(|err| exit_malformed(path, err))
+}
+
+// Read the machine words of a file to disassemble, lines that are not a word keep their ROM address but are left for the disassembler to report
+fn read_words(path: &Path) -> Vec<u16> {
+    let source = std::fs::read_to_string(path).unwrap_or_else(|err| exit_io(path, err));
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| u16::from_str_radix(line, 2).unwrap_or_default())
+        .collect()
+}
+
+// Write the symbol table requested with --symbol-output, in the format its extension asks for
+fn write_symbol_output(path: &Path, symbol_table: &SymbolTable) {
+    let format = match path.extension().is_some_and(|extension| extension == "json") {
+        true => SymbolFileFormat::Json,
+        false => SymbolFileFormat::Sym,
+    };
+    write_file(path, |file| symbol_table.write_symbols(file, format));
+}
+
+// Create the file at `path` and fill it with `write`, exiting if either fails
+fn write_file(path: &Path, write: impl FnOnce(&mut File) -> Result<(), RhasmError>) {
+    let mut file = File::create(path).unwrap_or_else(|err| exit_io(path, err));
+    let written = write(&mut file).and_then(|()| Ok(file.flush()?));
+    if let Err(err) = written {
+        eprintln!("{}: {}", path.display(), err);
+        std::process::exit(EXIT_IO);
+    }
+}
+
+// Read a program to pack as machine words, assembling it first unless it is a .hack file
+fn read_program(path: &Path) -> io::Result<Vec<u16>> {
+    let source = std::fs::read_to_string(path)?;
+    let machine_code = match path.extension().is_some_and(|extension| extension == "hack") {
+        true => source,
+        false => {
+            let reporter = Reporter {
+                format: MessageFormat::Human,
+                source_name: path.display().to_string(),
+                source: None,
+            };
+            let mut output = Cursor::new(Vec::new());
+            if let Err(err) = rhasm::assemble_single_pass(&mut Cursor::new(source), &mut output) {
+                reporter.exit_with_error(err);
+            }
+            String::from_utf8_lossy(output.get_ref()).into_owned()
+        }
+    };
+    machine_code
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            u16::from_str_radix(line, 2).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{}: {:?} is not a 16 bit binary word", path.display(), line))
+            })
+        })
+        .collect()
+}
+
+// Prints diagnostics and errors in the requested format, naming the source they came from
+struct Reporter {
+    format: MessageFormat,
+    source_name: String,
+    // The source text, only kept if human readable messages should quote it
+    source: Option<String>,
+}
+
+impl Reporter {
+    fn print(&self, diagnostic: &Diagnostic) {
+        match self.format {
+            MessageFormat::Human => {
+                match &self.source {
+                    Some(source) => eprintln!("{}\n", diagnostic.to_pretty_string(&self.source_name, source)),
+                    None => eprintln!("{}", diagnostic),
+                }
+            }
+            MessageFormat::Gcc => eprintln!("{}", diagnostic.to_gcc_string(&self.source_name)),
+            MessageFormat::Json => eprintln!("{}", diagnostic.to_json(&self.source_name)),
+            MessageFormat::Sarif => self.print_all(std::slice::from_ref(diagnostic)),
+        }
+    }
+
+    // Print every diagnostic, SARIF wraps them all in a single log
+    fn print_all(&self, diagnostics: &[Diagnostic]) {
+        match self.format {
+            MessageFormat::Sarif => eprintln!("{}", diagnostic::to_sarif(diagnostics, &self.source_name)),
+            _ => {
+                for diagnostic in diagnostics {
+                    self.print(diagnostic);
+                }
+            }
+        }
+    }
+
+    // Print the summary line, JSON and SARIF output only ever contain the diagnostics themselves
+    fn print_summary(&self, summary: &str) {
+        if !matches!(self.format, MessageFormat::Json | MessageFormat::Sarif) {
+            eprintln!("{}", summary);
+        }
+    }
+
+    // Report an assembly error and exit, the CLI has no way to recover from these
+    fn exit_with_error(&self, err: RhasmError) -> ! {
+        match &err {
+            RhasmError::Diagnostics(diagnostics) => {
+                self.print_all(diagnostics);
+                self.print_summary(&format!("Assembly failed: {}", diagnostic::summarize(diagnostics)));
+            }
+            RhasmError::Parse { line, text } if self.format != MessageFormat::Human || self.source.is_some() => {
+                let diagnostic = Diagnostic::new(Span::new(*line, 0, text.len()), text, "Invalid Instruction");
+                self.print(&diagnostic.with_code(err.code().unwrap_or_default()));
+            }
+            _ if matches!(self.format, MessageFormat::Json | MessageFormat::Sarif) => {
+                // Errors without a source location, e.g. I/O errors, are reported at line 0
+                self.print(&Diagnostic::new(Span::default(), "", &err.to_string()));
+            }
+            _ => eprintln!("{}", err),
+        }
+        std::process::exit(match err {
+            RhasmError::Io(err) => io_exit_code(&err),
+            _ => EXIT_INVALID_SOURCE,
+        });
+    }
+
+    // Print the warnings and notes of a successful build followed by a summary line
+    // SARIF is always printed, so CI dashboards can tell a clean build from a missing log
+    fn report_diagnostics(&self, diagnostics: &[Diagnostic]) {
+        if diagnostics.is_empty() && self.format != MessageFormat::Sarif {
+            return;
+        }
+        self.print_all(diagnostics);
+        self.print_summary(&format!("Assembled with {}", diagnostic::summarize(diagnostics)));
+    }
+}
+
+// Parse a `NAME=VALUE` symbol definition given with `--define`
+fn parse_define(define: &str) -> Result<(String, u16), String> {
+    let (name, value) = define.split_once('=').ok_or("expected NAME=VALUE")?;
+    let is_symbol = name
+        .chars()
+        .all(|char| char.is_ascii_alphanumeric() || "_.$:".contains(char));
+    if name.is_empty() || !is_symbol || name.starts_with(|char: char| char.is_ascii_digit()) {
+        return Err(format!("{} is not a valid symbol name", name));
+    }
+    match value.parse::<u16>() {
+        Ok(value) if value < 1 << 15 => Ok((name.to_string(), value)),
+        _ => Err(format!("{} is not a value between 0 and 32767", value)),
+    }
+}
+
+// Pair every lint named with --allow, --warn or --deny with its level, a lint named more than once gets the strictest one
+fn lint_levels<'l>(allow: &'l [String], warn: &'l [String], deny: &'l [String]) -> Vec<(&'l str, LintLevel)> {
+    let named = |lints: &'l [String], level: LintLevel| lints.iter().map(move |lint| (lint.as_str(), level));
+    named(allow, LintLevel::Allow)
+        .chain(named(warn, LintLevel::Warn))
+        .chain(named(deny, LintLevel::Deny))
+        .collect()
+}
+
+// Apply the assembler options given on the command line
+fn configure<'a, R: Read, W: Write>(mut config: AssemblerConfig<'a, R, W>, args: &Cli) -> AssemblerConfig<'a, R, W> {
+    if let Some(path) = &args.symbols {
+        config = config.with_predefined_symbols(load_symbols(path).into_iter().map(|(_, name, address)| (name, address)));
+    }
+    for (name, value) in &args.defines {
+        config = config.with_predefined_symbol(name, *value);
+    }
+    for (lint, level) in lint_levels(&args.allow, &args.warn, &args.deny) {
+        config = config.with_lint_level(lint, level);
+    }
+    if let Some(max_line_length) = args.max_line_length {
+        config = config.with_max_line_length(max_line_length);
+    }
+    if let Some(max_input_size) = args.max_input_size {
+        config = config.with_max_input_size(max_input_size);
+    }
+    config
+        .with_lenient_syntax(args.lenient_syntax)
+        .with_duplicate_labels_as_warnings(args.allow_duplicate_labels)
+        .with_constant_overflow(args.constant_overflow.into())
+        .with_variable_overflow_as_warning(args.allow_variable_overflow)
+        .with_output_format(args.output_format.format())
+}
+
+// The number of differing lines listed before the summary is cut short
+const MAX_REPORTED_DIFFERENCES: usize = 10;
+
+// Assemble the input in memory and compare it line by line against the existing output
+// Prints a summary of the differences and returns whether the output is unchanged
+fn assert_unchanged(
+    in_file: &mut File,
+    out_file_path: &Path,
+    args: &Cli,
+    reporter: &Reporter
+) -> io::Result<bool> {
+    let expected = std::fs::read_to_string(out_file_path)?;
+    let mut buffer: Vec<u8> = Vec::new();
+    Assembler::build(configure(AssemblerConfig::new(in_file, &mut buffer), args))
+        .and_then(|mut assembler| assembler.advance_to_end())
+        .unwrap_or_else(|err| reporter.exit_with_error(err));
+    let actual = String::from_utf8_lossy(&buffer);
+
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let differences: Vec<usize> = (0..expected_lines.len().max(actual_lines.len()))
+        .filter(|&line| expected_lines.get(line) != actual_lines.get(line))
+        .collect();
+
+    if differences.is_empty() {
+        println!("{} is up to date", out_file_path.display());
+        return Ok(true);
+    }
+    eprintln!(
+        "{} does not match the assembled input: {} line(s) differ ({} lines on disk, {} assembled)",
+        out_file_path.display(),
+        differences.len(),
+        expected_lines.len(),
+        actual_lines.len()
+    );
+    for &line in differences.iter().take(MAX_REPORTED_DIFFERENCES) {
+        eprintln!(
+            "  line {}: on disk {}, assembled {}",
+            line + 1,
+            expected_lines.get(line).unwrap_or(&"<missing>"),
+            actual_lines.get(line).unwrap_or(&"<missing>")
+        );
+    }
+    if differences.len() > MAX_REPORTED_DIFFERENCES {
+        eprintln!("  ... and {} more", differences.len() - MAX_REPORTED_DIFFERENCES);
+    }
+    Ok(false)
+}
+
+// How long to wait before checking a followed input file for new instructions
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// The exit codes listed in the help text, clap exits with 2 for invalid arguments on its own
+const EXIT_INVALID_SOURCE: i32 = 1;
+const EXIT_IO: i32 = 3;
+const EXIT_OVERWRITE_REFUSED: i32 = 4;
+
+// The exit code for an error surfaced as an io::Error, the disassembler reports malformed input as InvalidData
+fn io_exit_code(err: &io::Error) -> i32 {
+    match err.kind() {
+        io::ErrorKind::InvalidData => EXIT_INVALID_SOURCE,
+        _ => EXIT_IO,
+    }
+}
+
+// Create the output file, asking before overwriting an existing one unless the command line already decided
+// Never asks if stdin is not a terminal, as nobody could answer in a script or CI pipeline
+fn create_output(path: &Path, args: &Cli) -> File {
+    if args.force {
+        backup(path, args);
+        return File::create(path).unwrap_or_else(|err| exit_io(path, err));
+    }
+    match File::create_new(path) {
+        Ok(file) => {
+            return file;
+        }
+        Err(err) if err.kind() != io::ErrorKind::AlreadyExists => exit_io(path, err),
+        Err(_) => {}
+    }
+    let refuse = || -> ! {
+        eprintln!("Output file {} already exists, pass --force to overwrite it", path.display());
+        std::process::exit(EXIT_OVERWRITE_REFUSED);
+    };
+    if args.no_overwrite || !io::stdin().is_terminal() {
+        refuse();
+    }
+    eprint!("Output file {} already exists, would you like to overwrite it? (y/n) ", path.display());
+    io::stderr().flush().unwrap_or_default();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap_or_else(|err| exit_io(Path::new("<stdin>"), err));
+    if input.trim().to_lowercase() != "y" {
+        refuse();
+    }
+    backup(path, args);
+    File::create(path).unwrap_or_else(|err| exit_io(path, err))
+}
+
+// Copy an output file about to be overwritten to <file>.bak, if asked to with --backup or --backup-dir
+// The output is never overwritten if the copy can not be made
+fn backup(path: &Path, args: &Cli) {
+    if (!args.backup && args.backup_dir.is_none()) || !path.exists() {
+        return;
+    }
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".bak");
+    let backup_path = match &args.backup_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir).unwrap_or_else(|err| exit_io(dir, err));
+            dir.join(file_name)
+        }
+        None => path.with_file_name(file_name),
+    };
+    std::fs::copy(path, &backup_path).unwrap_or_else(|err| exit_io(&backup_path, err));
+}
+
+// Report a malformed input file at `path` and exit
+fn exit_malformed(path: &Path, err: RhasmError) -> ! {
+    // Malformed lines are reported as invalid data, printed without the `I/O error` prefix
+    match err {
+        RhasmError::Io(err) => eprintln!("{}: {}", path.display(), err),
+        err => eprintln!("{}: {}", path.display(), err),
+    }
+    std::process::exit(EXIT_INVALID_SOURCE);
+}
+
+// Report a failure to read or write `path` and exit
+fn exit_io(path: &Path, err: io::Error) -> ! {
+    eprintln!("{}: {}", path.display(), err);
+    std::process::exit(EXIT_IO);
 }
 
-fn main() -> io::Result<()> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        std::process::exit(EXIT_IO);
+    }
+}
+
+fn run() -> io::Result<()> {
     let args = Cli::parse();
+    // Messages are logged as they were once printed, without a timestamp or level
+    env_logger::Builder::new()
+        .filter_level(args.log_level())
+        .parse_env("RUST_LOG")
+        .format(|buf, record| writeln!(buf, "{}", record.args()))
+        .init();
+
+    match &args.command {
+        Some(Command::Explain { instruction }) => {
+            match rhasm::explain_instruction(instruction) {
+                Ok(explanation) => print!("{}", explanation),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(EXIT_INVALID_SOURCE);
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Rename { old_name, new_name, file }) => {
+            let source = std::fs::read_to_string(file)?;
+            match rhasm::refactor::rename_symbol(&source, old_name, new_name) {
+                Ok(renamed) => std::fs::write(file, renamed)?,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(EXIT_INVALID_SOURCE);
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Label { address, name, file, jumps_only }) => {
+            let source = std::fs::read_to_string(file)?;
+            let insert = if *jumps_only { rhasm::refactor::insert_jump_label } else { rhasm::refactor::insert_label };
+            match insert(&source, *address, name) {
+                Ok(labelled) => std::fs::write(file, labelled)?,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(EXIT_INVALID_SOURCE);
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::GenTest { file }) => {
+            let source = std::fs::read_to_string(file)?;
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            let script = rhasm::test_script::generate_test_script(&name, &source).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(EXIT_INVALID_SOURCE);
+            });
+            // Never overwrite a test that may already have been filled in
+            for (extension, contents) in [("tst", &script.tst), ("cmp", &script.cmp)] {
+                let path = file.with_extension(extension);
+                File::create_new(&path)
+                    .and_then(|mut out| out.write_all(contents.as_bytes()))
+                    .unwrap_or_else(|err| {
+                        eprintln!("Could not create {}: {}", path.display(), err);
+                        std::process::exit(match err.kind() {
+                            io::ErrorKind::AlreadyExists => EXIT_OVERWRITE_REFUSED,
+                            _ => EXIT_IO,
+                        });
+                    });
+            }
+            return Ok(());
+        }
+        Some(Command::Parse { file, format, lenient_syntax }) => {
+            let source = std::fs::read_to_string(file)?;
+            match format {
+                ParseFormat::Json => print!("{}", rhasm::parse_tree::to_json(&source, *lenient_syntax)),
+            }
+            return Ok(());
+        }
+        Some(Command::Lint { file, fail_on, lenient_syntax, message_format, allow, warn, deny }) => {
+            let source = std::fs::read_to_string(file)?;
+            let reporter = Reporter {
+                format: *message_format,
+                source_name: file.display().to_string(),
+                source: (*message_format == MessageFormat::Human && io::stderr().is_terminal()).then(|| source.clone()),
+            };
+            let findings = lint(&source, *lenient_syntax, &lint_levels(allow, warn, deny), &reporter);
+            reporter.print_all(&findings);
+            reporter.print_summary(&format!("Lint found {}", diagnostic::summarize(&findings)));
+            let failed = findings.iter().any(|finding| finding.severity >= Severity::from(*fail_on));
+            std::process::exit(if failed { EXIT_INVALID_SOURCE } else { 0 });
+        }
+        Some(Command::Pack { output, inputs }) => {
+            let mut image = rhasm::rom_image::RomImage::new();
+            for input in inputs {
+                let name = input.file_stem().unwrap_or_default().to_string_lossy();
+                let words = read_program(input)?;
+                image.add(&name, words).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    std::process::exit(EXIT_INVALID_SOURCE);
+                });
+            }
+            std::fs::write(output, image.to_bytes())?;
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if let Some(code) = &args.explain_code {
+        match rhasm::error_codes::lookup(code) {
+            Some(entry) => {
+                print!("{}: {}\n\n{}", entry.code, entry.summary, entry.explanation);
+                if let Some(lint) = entry.lint {
+                    println!("\nThis warning is reported by the `{}` lint, see --allow, --warn and --deny.", lint);
+                }
+            }
+            None => {
+                eprintln!("{} is not a known error code or lint", code);
+                std::process::exit(EXIT_INVALID_SOURCE);
+            }
+        }
+        return Ok(());
+    }
 
     let disassemble = args.disassemble;
-    let in_file_path = args.in_file_path;
+    if let Some(program) = &args.eval {
+        let reporter = Reporter {
+            format: args.message_format,
+            source_name: "<eval>".to_string(),
+            source: args.wants_pretty_errors().then(|| program.replace("\\n", "\n")),
+        };
+        return evaluate(program, disassemble, &reporter);
+    }
+
+    let follow = args.follow;
+    // Clap only lets us get here without a subcommand, in which case the input file is required
+    let in_file_path = args.in_file_path.clone().expect("input file is required without a subcommand");
     let out_file_path = match args.output.as_ref() {
         Some(filename) => filename.clone(),
         None => {
@@ -46,41 +930,106 @@ fn main() -> io::Result<()> {
         }
     };
 
-    let mut in_file = std::fs::File::open(&in_file_path)?;
+    let mut in_file = File::open(&in_file_path).unwrap_or_else(|err| exit_io(&in_file_path, err));
+    let reporter = Reporter {
+        format: args.message_format,
+        source_name: in_file_path.display().to_string(),
+        source: args.wants_pretty_errors().then(|| std::fs::read_to_string(&in_file_path).ok()).flatten(),
+    };
 
-    let out_file_create_result = std::fs::File::create_new(&out_file_path);
-    let mut out_file = out_file_create_result.unwrap_or_else(|_| {
-        eprint!(
-            "Could not create output file, file {} already exists
-            Would you like to overwrite the file? (y/n)",
-            out_file_path.display()
-        );
-        io::stdout().flush().unwrap();
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        if input.trim().to_lowercase() == "y" {
-            std::fs::File::create(&out_file_path).unwrap()
-        } else {
-            std::process::exit(1);
-        }
-    });
+    if args.assert_unchanged {
+        let unchanged = assert_unchanged(&mut in_file, &out_file_path, &args, &reporter)?;
+        std::process::exit(if unchanged { 0 } else { 1 });
+    }
+
+    let annotations = args.annotations.as_deref().map(load_annotations).unwrap_or_default();
+    // The label file belongs to the output of the assembler, so it is only replaced when assembling, under the same policy
+    // It is created first, so refusing to overwrite it leaves the output untouched
+    let label_file = (!disassemble).then(|| create_output(&in_file_path.with_extension("labels"), &args));
+    let mut out_file = create_output(&out_file_path, &args);
 
     let reader = &mut in_file;
     let writer = Some(out_file.borrow_mut());
-    let mut label_file = File::create_new(in_file_path.with_extension("labels")).unwrap();
-    let label_table = Some(&mut label_file);
 
     if disassemble {
-        let args = rhasm::DisassemblerConfig {
-            reader,
-            writer,
+        let mut symbols = args.with_symbols.as_deref().map(load_symbols).unwrap_or_default();
+        if args.reconstruct_labels || args.name_variables {
+            let words = read_words(&in_file_path);
+            if args.reconstruct_labels {
+                symbols.extend(reconstruct_labels(&words));
+            }
+            if args.name_variables {
+                symbols.extend(name_variables(&words));
+            }
+        }
+        let config = rhasm::DisassemblerConfig::with_optional_writer(reader, writer)
+            .with_symbols(symbols.clone())
+            .with_word_comments(args.word_comments.map(WordComments::from).unwrap_or_default());
+        let mut disassembler = Disassembler::new(config).with_annotations(annotations);
+        // Decode errors name the offending line, the rest of the input is not disassembled
+        let exit_on_error = |err: io::Error| -> ! {
+            eprintln!("{}", err);
+            std::process::exit(io_exit_code(&err));
         };
-        let mut disassembler = Disassembler::new(args);
-        disassembler.write_to_end()?;
-        
+        if let Some(path) = &args.symbol_output {
+            let mut symbol_table = SymbolTable::new();
+            for (kind, name, address) in &symbols {
+                match kind {
+                    SymbolKind::Label => symbol_table.define_label(name, *address),
+                    SymbolKind::Variable => symbol_table.define_variable(name, *address),
+                    SymbolKind::Predefined => symbol_table.define_predefined(name, *address),
+                }
+            }
+            write_symbol_output(path, &symbol_table);
+        }
+        if follow {
+            loop {
+                if disassembler.poll_and_write_next().unwrap_or_else(|err| exit_on_error(err)).is_none() {
+                    thread::sleep(FOLLOW_POLL_INTERVAL);
+                }
+            }
+        }
+        disassembler.write_to_end().unwrap_or_else(|err| exit_on_error(err));
+
+    } else if args.single_pass {
+        let mut label_file = label_file.expect("the label file is created when assembling");
+        let symbol_table = rhasm::assemble_single_pass(&mut in_file, &mut out_file).unwrap_or_else(
+            |err| reporter.exit_with_error(err)
+        );
+        for (label, address) in symbol_table.iter() {
+            writeln!(label_file, "{}:{}", label, address)?;
+        }
+        if let Some(path) = &args.symbol_output {
+            write_symbol_output(path, &symbol_table);
+        }
     } else {
-        let assembler = Assembler::build(&mut in_file, &mut out_file, label_table);
-        assembler.unwrap().advance_to_end();
+        let mut trace_file = match args.trace_passes {
+            true => Some(File::create(in_file_path.with_extension("trace"))?),
+            false => None,
+        };
+        let mut label_file = label_file.expect("the label file is created when assembling");
+        let mut config = configure(AssemblerConfig::new(&mut in_file, &mut out_file), &args)
+            .with_symbol_file(&mut label_file);
+        if let Some(trace_file) = trace_file.as_mut() {
+            config = config.with_trace_file(trace_file);
+        }
+        let mut assembler = Assembler::build(config).unwrap_or_else(|err| reporter.exit_with_error(err));
+        // Warnings are reported together with any error found while encoding, so SARIF output stays a single log
+        let warnings = assembler.diagnostics().to_vec();
+        match assembler.advance_to_end() {
+            Ok(()) => reporter.report_diagnostics(&warnings),
+            Err(RhasmError::Diagnostics(errors)) => {
+                reporter.exit_with_error(RhasmError::Diagnostics(warnings.into_iter().chain(errors).collect()))
+            }
+            Err(err) => reporter.exit_with_error(err),
+        }
+        if let Some(path) = &args.symbol_output {
+            write_symbol_output(path, &assembler.symbol_table);
+        }
+        if let Some(path) = &args.source_map {
+            let source = in_file_path.display().to_string();
+            write_file(path, |file| assembler.write_source_map(file, Some(&source)));
+        }
     }
     Ok(())
 }